@@ -2,38 +2,235 @@ use crate::intermediate::constant::Constant;
 use crate::intermediate::opcode::BinaryOpcode;
 use crate::intermediate::opcode::UnaryOpcode;
 
+/// Start and end byte offsets of a node in the source text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset <= self.end
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum AST {
     Constant(Constant),
-    Identifier(String),
+    Identifier(String, Span),
     Concatenation { left: Box<AST>, right: Box<AST> },
     BinaryOp(Box<AST>, BinaryOpcode, Box<AST>),
     UnaryOp(UnaryOpcode, Box<AST>),
-    Definition(String),
+    /// Variable declaration, with optional type annotation
+    Definition(String, Span, Option<TypeAST>),
     Assignment(Box<AST>, Box<AST>),
     Block(Box<AST>),
-    Condition { exp: Box<AST>, then_block: Box<AST>, else_block: Box<AST> },
-    While { guard: Box<AST>, exp: Box<AST> },
+    /// Span is the position of the `if` keyword
+    Condition { exp: Box<AST>, then_block: Box<AST>, else_block: Box<AST>, span: Span },
+    /// Span is the position of the `while` keyword
+    While { guard: Box<AST>, exp: Box<AST>, span: Span },
+    For { var: String, span: Span, iterable: Box<AST>, exp: Box<AST> },
     List { elements: Vec<AST> },
     Subscript { element: Box<AST>, index: Box<AST> },
+    Slice { element: Box<AST>, start: Option<Box<AST>>, end: Option<Box<AST>>, step: Option<Box<AST>> },
     Function(Box<FunctionAST>),
-    Closure { args: Vec<String>, exp: Box<AST> },
-    FunctionCall { fun: Box<AST>, args: Vec<AST> },
+    Closure { params: Vec<ParamAST>, exp: Box<AST> },
+    FunctionCall { fun: Box<AST>, args: Vec<ArgAST> },
     Class(Box<ClassAST>),
-    PropertyAccess { exp: Box<AST>, property: String },
+    /// Span is the position of the property name
+    PropertyAccess { exp: Box<AST>, property: String, span: Span },
+    Trait(Box<TraitAST>),
+    Implements { exp: Box<AST>, trait_name: String },
+    Yield(Box<AST>),
+    /// Test block, evaluated only by the test runner. Offset is the position in the source text.
+    Test { name: String, offset: usize, body: Box<AST> },
 }
 
+impl AST {
+    /// Returns the statements of a concatenation, or the AST itself if it is not a concatenation
+    pub fn statements(&self) -> Vec<&AST> {
+        match self {
+            AST::Concatenation { left, right } => {
+                let mut statements = left.statements();
+                statements.extend(right.statements());
+                statements
+            },
+            ast => vec![ast],
+        }
+    }
+
+    /// Returns the position of the first named node, in source order
+    pub fn first_span(&self) -> Option<Span> {
+        match self {
+            AST::Identifier(_, span) | AST::Definition(_, span, _) => Some(*span),
+            AST::Condition { span, .. } | AST::While { span, .. } | AST::For { span, .. } => Some(*span),
+            AST::Function(function) => Some(function.span),
+            AST::Class(class) => Some(class.span),
+            AST::Trait(trait_ast) => Some(trait_ast.span),
+            AST::Test { offset, .. } => Some(Span::new(*offset, *offset + 4)),
+            _ => self.children().into_iter().find_map(AST::first_span),
+        }
+    }
+
+    /// Returns the direct subexpressions, in source order
+    pub fn children(&self) -> Vec<&AST> {
+        fn params(params: &[ParamAST]) -> Vec<&AST> {
+            params.iter().filter_map(|p| p.default.as_ref()).collect()
+        }
+        match self {
+            AST::Constant(_) | AST::Identifier(..) | AST::Definition(..) => Vec::new(),
+            AST::Concatenation { left, right } => vec![left, right],
+            AST::BinaryOp(left, _, right) | AST::Assignment(left, right) => vec![left, right],
+            AST::UnaryOp(_, exp) | AST::Block(exp) | AST::Yield(exp) => vec![exp],
+            AST::Condition { exp, then_block, else_block, .. } => vec![exp, then_block, else_block],
+            AST::While { guard, exp, .. } => vec![guard, exp],
+            AST::For { iterable, exp, .. } => vec![iterable, exp],
+            AST::List { elements } => elements.iter().collect(),
+            AST::Subscript { element, index } => vec![element, index],
+            AST::Slice { element, start, end, step } => {
+                std::iter::once(element).chain(start).chain(end).chain(step).map(|exp| exp.as_ref()).collect()
+            },
+            AST::Function(function) => {
+                let mut children = params(&function.params);
+                children.push(&function.body);
+                children
+            },
+            AST::Closure { params: closure_params, exp } => {
+                let mut children = params(closure_params);
+                children.push(exp);
+                children
+            },
+            AST::FunctionCall { fun, args } => {
+                std::iter::once(fun.as_ref()).chain(args.iter().map(|arg| match arg {
+                    ArgAST::Positional(exp) | ArgAST::Named(_, exp) => exp,
+                })).collect()
+            },
+            AST::Class(class) => {
+                let fields = class.fields.iter().chain(class.static_fields.iter()).filter_map(|f| f.value.as_ref());
+                let methods = class.methods.iter().chain(class.static_methods.iter()).flat_map(|m| {
+                    let mut children = params(&m.function.params);
+                    children.push(&m.function.body);
+                    children
+                });
+                fields.chain(methods).collect()
+            },
+            AST::PropertyAccess { exp, .. } | AST::Implements { exp, .. } => vec![exp],
+            AST::Trait(trait_ast) => trait_ast.methods.iter().flat_map(|m| params(&m.params)).collect(),
+            AST::Test { body, .. } => vec![body],
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct FunctionAST {
     pub name: String,
-    pub args: Vec<String>,
+    pub span: Span,
+    pub params: Vec<ParamAST>,
+    pub return_type: Option<TypeAST>,
     pub body: AST,
 }
 
+/// Function parameter, eventually with a type annotation, a default value or variadic
+#[derive(Debug, PartialEq)]
+pub struct ParamAST {
+    pub name: String,
+    pub span: Span,
+    pub annotation: Option<TypeAST>,
+    pub default: Option<AST>,
+    pub variadic: bool,
+}
+
+/// Type annotation, the name of a built-in type or of a class
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeAST {
+    pub name: String,
+    pub span: Span,
+}
+
+/// Function call argument, either positional or named
+#[derive(Debug, PartialEq)]
+pub enum ArgAST {
+    Positional(AST),
+    Named(String, AST),
+}
+
+#[derive(Debug, PartialEq)]
 pub struct ClassAST {
     pub name: String,
+    pub span: Span,
+    pub traits: Vec<String>,
     pub fields: Vec<FieldAST>,
-    pub methods: Vec<FunctionAST>,
+    pub methods: Vec<MethodAST>,
+    pub static_fields: Vec<FieldAST>,
+    pub static_methods: Vec<MethodAST>,
+}
+
+impl ClassAST {
+    pub fn new(name: String, span: Span, traits: Vec<String>, members: Vec<ClassMemberAST>) -> ClassAST {
+        let mut class = ClassAST {
+            name,
+            span,
+            traits,
+            fields: Vec::new(),
+            methods: Vec::new(),
+            static_fields: Vec::new(),
+            static_methods: Vec::new(),
+        };
+        for member in members {
+            match member {
+                ClassMemberAST::Field(f) => class.fields.push(f),
+                ClassMemberAST::Method(m) => class.methods.push(m),
+                ClassMemberAST::StaticField(f) => class.static_fields.push(f),
+                ClassMemberAST::StaticMethod(m) => class.static_methods.push(m),
+            }
+        }
+        class
+    }
 }
 
+pub enum ClassMemberAST {
+    Field(FieldAST),
+    Method(MethodAST),
+    StaticField(FieldAST),
+    StaticMethod(MethodAST),
+}
+
+/// Class field. Instance fields initializers are evaluated for each new object,
+/// static fields initializers are evaluated once when the class is defined.
+#[derive(Debug, PartialEq)]
 pub struct FieldAST {
     pub name: String,
+    pub span: Span,
+    pub annotation: Option<TypeAST>,
+    pub value: Option<AST>,
+    pub public: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MethodAST {
+    pub function: FunctionAST,
+    pub public: bool,
+}
+
+/// Set of methods that a class must implement
+#[derive(Debug, PartialEq)]
+pub struct TraitAST {
+    pub name: String,
+    pub span: Span,
+    pub methods: Vec<TraitMethodAST>,
+}
+
+/// Trait method signature. Methods with `self` as first parameter are
+/// implemented as instance methods, the others as static methods.
+#[derive(Debug, PartialEq)]
+pub struct TraitMethodAST {
+    pub name: String,
+    pub span: Span,
+    pub params: Vec<ParamAST>,
 }
@@ -0,0 +1,718 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+use crate::intermediate::constant::{Constant, Type};
+use crate::intermediate::opcode::{BinaryOpcode, UnaryOpcode};
+
+use super::ast::{ArgAST, ClassAST, FieldAST, ParamAST, Span, TypeAST, AST};
+use super::compiler::NAMESPACES;
+use super::error::TypeError;
+
+/// Type of an expression known before evaluation
+#[derive(Clone, Debug, PartialEq)]
+pub enum StaticType {
+    /// Type not known statically, compatible with any other type
+    Any,
+    Value(Type),
+    /// Object created by the named class
+    Object(String),
+}
+
+impl StaticType {
+    /// Returns true if a value of this type can be used where the expected type is required
+    pub fn fits(&self, expected: &StaticType) -> bool {
+        match (self, expected) {
+            (StaticType::Any, _) | (_, StaticType::Any) => true,
+            (StaticType::Object(_), StaticType::Value(Type::Object)) => true,
+            (found, expected) => found == expected,
+        }
+    }
+}
+
+impl Display for StaticType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StaticType::Any => write!(f, "Any"),
+            StaticType::Value(t) => write!(f, "{}", t),
+            StaticType::Object(class) => write!(f, "{}", class),
+        }
+    }
+}
+
+/// Variables and classes declared in a block, as the compiler frames
+#[derive(Clone)]
+struct Scope {
+    variables: HashMap<String, Variable>,
+    classes: HashMap<String, Rc<ClassType>>,
+    isolated: bool,
+}
+
+#[derive(Clone)]
+struct Variable {
+    /// Annotated type, or type of the initial value for variables without annotation
+    ty: StaticType,
+    /// True if the type is inferred from the initial value, so that assigning the variable can change it
+    inferred: bool,
+    /// Signature of a function declared with fn, unless its variable is reassigned
+    function: Option<Rc<Signature>>,
+}
+
+/// Parameters and return type of a function, a method or a constructor
+struct Signature {
+    name: String,
+    span: Span,
+    params: Vec<ParamType>,
+    returns: StaticType,
+    is_generator: bool,
+}
+
+struct ParamType {
+    name: String,
+    ty: StaticType,
+    variadic: bool,
+}
+
+impl Signature {
+    /// Type of the values returned by calls
+    fn call_type(&self) -> StaticType {
+        if self.is_generator { StaticType::Value(Type::Generator) } else { self.returns.clone() }
+    }
+}
+
+#[derive(Default)]
+struct ClassType {
+    fields: HashMap<String, StaticType>,
+    static_fields: HashMap<String, StaticType>,
+    methods: HashMap<String, Rc<Signature>>,
+    static_methods: HashMap<String, Rc<Signature>>,
+}
+
+/// Checks the type annotations before compilation, inferring the types of the expressions where
+/// possible. Expressions whose type is not known are accepted, so code without annotations is never rejected.
+#[derive(Clone)]
+pub struct TypeChecker {
+    scopes: Vec<Scope>,
+    /// Names of the variables assigned with `=`, whose function signature cannot be trusted
+    reassigned: HashSet<String>,
+    span: Span,
+}
+
+impl TypeChecker {
+    pub fn new() -> TypeChecker {
+        TypeChecker {
+            scopes: vec![Scope::new(false)],
+            reassigned: HashSet::new(),
+            span: Span::default(),
+        }
+    }
+
+    /// Checks a program, its top-level declarations are kept for the next programs as in the REPL
+    pub fn check(&mut self, ast: &AST) -> Result<(), TypeError> {
+        collect_reassigned(ast, &mut self.reassigned);
+        self.infer(ast).map(|_| ())
+    }
+
+    /// Position of the last named node checked, used to locate type errors
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    fn infer(&mut self, ast: &AST) -> Result<StaticType, TypeError> {
+        match ast {
+            AST::Constant(constant) => Ok(StaticType::Value(constant_type(constant))),
+
+            AST::Identifier(name, span) => {
+                self.span = *span;
+                if let Some(variable) = self.variable(name) {
+                    return Ok(variable.ty.clone())
+                }
+                if self.class(name).is_some() {
+                    return Ok(StaticType::Value(Type::Class))
+                }
+                Ok(StaticType::Any)
+            },
+
+            AST::Concatenation { left, right } => {
+                self.infer(left)?;
+                self.infer(right)
+            },
+
+            AST::BinaryOp(left, op, right) => {
+                let left = self.infer(left)?;
+                let right = self.infer(right)?;
+                Ok(binary_type(*op, &left, &right))
+            },
+
+            AST::UnaryOp(op, exp) => {
+                self.infer(exp)?;
+                match op {
+                    UnaryOpcode::Not => Ok(StaticType::Value(Type::Bool)),
+                }
+            },
+
+            AST::Definition(name, span, annotation) => {
+                self.span = *span;
+                let ty = self.resolve(annotation)?;
+                self.define(name, ty, None);
+                Ok(StaticType::Any)
+            },
+
+            AST::Assignment(left, right) => {
+                let (name, expected, span) = match left.as_ref() {
+                    AST::Definition(name, span, _) => {
+                        self.infer(left)?;
+                        (name.clone(), self.variable(name).map_or(StaticType::Any, |v| v.ty.clone()), *span)
+                    },
+                    AST::Identifier(name, span) => {
+                        let expected = self.infer(left)?;
+                        let expected = match self.variable_mut(name) {
+                            // Inferred in a previous program, as in the REPL, before the assignment was known
+                            Some(variable) if variable.inferred => {
+                                variable.ty = StaticType::Any;
+                                StaticType::Any
+                            },
+                            Some(_) => expected,
+                            None => StaticType::Any,
+                        };
+                        (name.clone(), expected, *span)
+                    },
+                    AST::PropertyAccess { exp, property, span } => {
+                        let expected = self.member_type(exp, property)?;
+                        (property.clone(), expected, *span)
+                    },
+                    left => {
+                        self.infer(left)?;
+                        (String::new(), StaticType::Any, Span::default())
+                    },
+                };
+                let found = self.infer(right)?;
+                if !found.fits(&expected) {
+                    self.span = span;
+                    return Err(TypeError::MismatchedAssignment(name, expected, found))
+                }
+                // Variables without annotation have the type of their initial value, unless they are assigned again
+                if let AST::Definition(name, _, None) = left.as_ref() {
+                    if !self.reassigned.contains(name) {
+                        if let Some(variable) = self.scopes.last_mut().unwrap().variables.get_mut(name) {
+                            variable.ty = found.clone();
+                            variable.inferred = true;
+                        }
+                    }
+                }
+                Ok(found)
+            },
+
+            AST::Block(exp) => self.block(exp, false),
+
+            AST::Condition { exp, then_block, else_block, .. } => {
+                self.infer(exp)?;
+                let then_type = self.block(then_block, false)?;
+                let else_type = self.block(else_block, false)?;
+                Ok(if then_type == else_type { then_type } else { StaticType::Any })
+            },
+
+            AST::While { guard, exp, .. } => {
+                self.infer(guard)?;
+                self.infer(exp)?;
+                Ok(StaticType::Value(Type::Unit))
+            },
+
+            AST::For { var, iterable, exp, .. } => {
+                self.infer(iterable)?;
+                self.scopes.push(Scope::new(false));
+                self.define(var, StaticType::Any, None);
+                let result = self.infer(exp);
+                self.scopes.pop();
+                result.map(|_| StaticType::Any)
+            },
+
+            AST::List { elements } => {
+                for element in elements {
+                    self.infer(element)?;
+                }
+                Ok(StaticType::Value(Type::List))
+            },
+
+            AST::Subscript { element, index } => {
+                self.infer(element)?;
+                self.infer(index)?;
+                Ok(StaticType::Any)
+            },
+
+            AST::Slice { element, start, end, step } => {
+                let element = self.infer(element)?;
+                for bound in [start, end, step].iter().copied().flatten() {
+                    self.infer(bound)?;
+                }
+                match element {
+                    StaticType::Value(Type::List) | StaticType::Value(Type::String) => Ok(element),
+                    _ => Ok(StaticType::Any),
+                }
+            },
+
+            AST::Function(f) => {
+                self.span = f.span;
+                let signature = Rc::new(self.signature(&f.name, f.span, &f.params, &f.return_type, &f.body)?);
+                self.function(&signature, Some(&f.name), None, &f.params, &f.body)?;
+                // Function is assigned to a new variable in current scope
+                let function = if self.reassigned.contains(&f.name) { None } else { Some(signature) };
+                self.define(&f.name, StaticType::Any, function);
+                Ok(StaticType::Value(Type::Function))
+            },
+
+            AST::Closure { params, exp } => {
+                let signature = Rc::new(self.signature("closure", self.span, params, &None, exp)?);
+                self.function(&signature, None, None, params, exp)?;
+                Ok(StaticType::Value(Type::Function))
+            },
+
+            AST::FunctionCall { fun, args } => {
+                let (signature, result) = self.callee(fun)?;
+                let span = self.span;
+                let mut found = Vec::with_capacity(args.len());
+                for arg in args {
+                    match arg {
+                        ArgAST::Positional(exp) => found.push((None, self.infer(exp)?)),
+                        ArgAST::Named(name, exp) => found.push((Some(name), self.infer(exp)?)),
+                    }
+                }
+                if let Some(signature) = signature {
+                    self.span = span;
+                    check_arguments(&signature, found)?;
+                }
+                Ok(result)
+            },
+
+            AST::Class(class_ast) => {
+                self.class_def(class_ast)?;
+                Ok(StaticType::Value(Type::Class))
+            },
+
+            AST::PropertyAccess { exp, property, span } => {
+                let ty = self.member_type(exp, property)?;
+                self.span = *span;
+                Ok(ty)
+            },
+
+            AST::Trait(trait_ast) => {
+                for method in trait_ast.methods.iter() {
+                    for param in method.params.iter() {
+                        self.resolve(&param.annotation)?;
+                    }
+                }
+                Ok(StaticType::Value(Type::Unit))
+            },
+
+            AST::Implements { exp, .. } => {
+                self.infer(exp)?;
+                Ok(StaticType::Value(Type::Bool))
+            },
+
+            AST::Yield(exp) => {
+                self.infer(exp)?;
+                Ok(StaticType::Any)
+            },
+
+            // Test blocks are checked separately, as they are compiled
+            AST::Test { .. } => Ok(StaticType::Value(Type::Unit)),
+        }
+    }
+
+    fn block(&mut self, ast: &AST, isolated: bool) -> Result<StaticType, TypeError> {
+        self.scopes.push(Scope::new(isolated));
+        let result = self.infer(ast);
+        // Pops scope before eventually returning error
+        self.scopes.pop();
+        result
+    }
+
+    /// Checks the default values and the body of a function, a closure or a method
+    fn function(&mut self, signature: &Rc<Signature>, name: Option<&str>, class: Option<&str>, params: &[ParamAST], body: &AST) -> Result<(), TypeError> {
+        self.scopes.push(Scope::new(true));
+        let result = self.function_body(signature, name, class, params, body);
+        // Pops scope before eventually returning error
+        self.scopes.pop();
+        result
+    }
+
+    fn function_body(&mut self, signature: &Rc<Signature>, name: Option<&str>, class: Option<&str>, params: &[ParamAST], body: &AST) -> Result<(), TypeError> {
+        if let Some(name) = name {
+            // Function is assigned to a variable in its own scope to enable recursion
+            let function = if self.reassigned.contains(name) { None } else { Some(signature.clone()) };
+            self.define(name, StaticType::Any, function);
+        }
+        if let Some(class) = class {
+            self.define("self", StaticType::Object(class.to_owned()), None);
+        }
+        for (param, param_type) in params.iter().zip(signature.params.iter()) {
+            if let Some(default) = &param.default {
+                let found = self.block(default, false)?;
+                if !found.fits(&param_type.ty) {
+                    self.span = param.span;
+                    return Err(TypeError::MismatchedAssignment(param.name.clone(), param_type.ty.clone(), found))
+                }
+            }
+            self.define(&param.name, param_type.ty.clone(), None);
+        }
+        let found = self.infer(body)?;
+        let (found, expected) = if signature.is_generator {
+            (StaticType::Value(Type::Generator), signature.returns.clone())
+        } else {
+            (found, signature.returns.clone())
+        };
+        if !found.fits(&expected) {
+            self.span = signature.span;
+            return Err(TypeError::MismatchedReturn(signature.name.clone(), expected, found))
+        }
+        Ok(())
+    }
+
+    fn signature(&mut self, name: &str, span: Span, params: &[ParamAST], return_type: &Option<TypeAST>, body: &AST) -> Result<Signature, TypeError> {
+        let mut param_types = Vec::with_capacity(params.len());
+        for param in params {
+            let ty = match self.resolve(&param.annotation)? {
+                // Variadic arguments are collected in a list
+                StaticType::Any if param.variadic => StaticType::Value(Type::List),
+                ty => ty,
+            };
+            param_types.push(ParamType { name: param.name.clone(), ty, variadic: param.variadic });
+        }
+        Ok(Signature {
+            name: name.to_owned(),
+            span,
+            params: param_types,
+            returns: self.resolve(return_type)?,
+            is_generator: contains_yield(body),
+        })
+    }
+
+    /// Returns the signature of the called function if known, with the type of the result
+    fn callee(&mut self, fun: &AST) -> Result<(Option<Rc<Signature>>, StaticType), TypeError> {
+        match fun {
+            AST::Identifier(name, span) => {
+                self.span = *span;
+                if let Some(variable) = self.variable(name) {
+                    let signature = variable.function.clone();
+                    let result = signature.as_ref().map_or(StaticType::Any, |s| s.call_type());
+                    return Ok((signature, result))
+                }
+                if let Some(class) = self.class(name) {
+                    return Ok((class.methods.get("self").cloned(), StaticType::Object(name.clone())))
+                }
+                Ok((None, builtin_type(name)))
+            },
+            AST::PropertyAccess { exp, property, span } => {
+                let signature = match self.static_class(exp) {
+                    Some(class) => class.static_methods.get(property).cloned(),
+                    None => match self.infer(exp)? {
+                        StaticType::Object(class) => self.class(&class).and_then(|c| c.methods.get(property).cloned()),
+                        _ => None,
+                    },
+                };
+                self.span = *span;
+                let result = signature.as_ref().map_or(StaticType::Any, |s| s.call_type());
+                Ok((signature, result))
+            },
+            fun => {
+                self.infer(fun)?;
+                Ok((None, StaticType::Any))
+            },
+        }
+    }
+
+    /// Returns the annotated type of a field accessed on an object or on a class
+    fn member_type(&mut self, exp: &AST, property: &str) -> Result<StaticType, TypeError> {
+        if let Some(class) = self.static_class(exp) {
+            return Ok(class.static_fields.get(property).cloned().unwrap_or(StaticType::Any))
+        }
+        if matches!(exp, AST::Identifier(name, _) if self.is_namespace(name)) {
+            return Ok(StaticType::Any)
+        }
+        match self.infer(exp)? {
+            StaticType::Object(class) => {
+                let class = self.class(&class);
+                Ok(class.and_then(|c| c.fields.get(property).cloned()).unwrap_or(StaticType::Any))
+            },
+            _ => Ok(StaticType::Any),
+        }
+    }
+
+    fn class_def(&mut self, class_ast: &ClassAST) -> Result<(), TypeError> {
+        self.span = class_ast.span;
+        // Class is declared before resolving the annotations, so its members can refer to it
+        self.scopes.last_mut().unwrap().classes.insert(class_ast.name.clone(), Rc::new(ClassType::default()));
+        let mut class = ClassType::default();
+        for field in class_ast.fields.iter() {
+            class.fields.insert(field.name.clone(), self.resolve(&field.annotation)?);
+        }
+        for field in class_ast.static_fields.iter() {
+            class.static_fields.insert(field.name.clone(), self.resolve(&field.annotation)?);
+        }
+        let mut methods = Vec::new();
+        for (method, is_static) in class_ast.methods.iter().map(|m| (m, false)).chain(class_ast.static_methods.iter().map(|m| (m, true))) {
+            let f = &method.function;
+            let name = if f.name == "self" { class_ast.name.clone() } else { format!("{}.{}", class_ast.name, f.name) };
+            let signature = Rc::new(self.signature(&name, f.span, &f.params, &f.return_type, &f.body)?);
+            let members = if is_static { &mut class.static_methods } else { &mut class.methods };
+            members.insert(f.name.clone(), signature.clone());
+            methods.push((f, is_static, signature));
+        }
+        self.scopes.last_mut().unwrap().classes.insert(class_ast.name.clone(), Rc::new(class));
+
+        for field in class_ast.fields.iter() {
+            // Instance fields are initialized with self as the only variable in scope
+            self.scopes.push(Scope::new(true));
+            self.define("self", StaticType::Object(class_ast.name.clone()), None);
+            let result = self.field(field);
+            self.scopes.pop();
+            result?;
+        }
+        for field in class_ast.static_fields.iter() {
+            self.scopes.push(Scope::new(false));
+            let result = self.field(field);
+            self.scopes.pop();
+            result?;
+        }
+        for (f, is_static, signature) in methods {
+            let class = if is_static { None } else { Some(class_ast.name.as_str()) };
+            self.function(&signature, None, class, &f.params, &f.body)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the initial value of a field against its annotation
+    fn field(&mut self, field: &FieldAST) -> Result<(), TypeError> {
+        if let Some(value) = &field.value {
+            let expected = self.resolve(&field.annotation)?;
+            let found = self.infer(value)?;
+            if !found.fits(&expected) {
+                self.span = field.span;
+                return Err(TypeError::MismatchedAssignment(field.name.clone(), expected, found))
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the type named by an annotation, Any if there is no annotation
+    fn resolve(&mut self, annotation: &Option<TypeAST>) -> Result<StaticType, TypeError> {
+        let annotation = match annotation {
+            Some(annotation) => annotation,
+            None => return Ok(StaticType::Any),
+        };
+        if annotation.name == "Any" {
+            return Ok(StaticType::Any)
+        }
+        if let Some(t) = Type::from_name(&annotation.name) {
+            return Ok(StaticType::Value(t))
+        }
+        if self.class(&annotation.name).is_some() {
+            return Ok(StaticType::Object(annotation.name.clone()))
+        }
+        self.span = annotation.span;
+        Err(TypeError::UnknownType(annotation.name.clone()))
+    }
+
+    fn define(&mut self, name: &str, ty: StaticType, function: Option<Rc<Signature>>) {
+        self.scopes.last_mut().unwrap().variables.insert(name.to_owned(), Variable { ty, inferred: false, function });
+    }
+
+    /// Returns the innermost variable with a name to update its type, not looking past isolated scopes
+    fn variable_mut(&mut self, name: &str) -> Option<&mut Variable> {
+        for scope in self.scopes.iter_mut().rev() {
+            if scope.variables.contains_key(name) {
+                return scope.variables.get_mut(name)
+            }
+            if scope.isolated { break }
+        }
+        None
+    }
+
+    /// Returns the innermost variable with a name, not looking past isolated scopes
+    fn variable(&self, name: &str) -> Option<&Variable> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(variable) = scope.variables.get(name) {
+                return Some(variable)
+            }
+            if scope.isolated { break }
+        }
+        None
+    }
+
+    fn class(&self, name: &str) -> Option<Rc<ClassType>> {
+        self.scopes.iter().rev().find_map(|s| s.classes.get(name).cloned())
+    }
+
+    /// Returns the class named by an identifier, unless a variable has the same name
+    fn static_class(&self, exp: &AST) -> Option<Rc<ClassType>> {
+        match exp {
+            AST::Identifier(name, _) if self.variable(name).is_none() => self.class(name),
+            _ => None,
+        }
+    }
+
+    fn is_namespace(&self, name: &str) -> bool {
+        NAMESPACES.contains(&name) && self.variable(name).is_none() && self.class(name).is_none()
+    }
+}
+
+impl Scope {
+    fn new(isolated: bool) -> Scope {
+        Scope { variables: HashMap::new(), classes: HashMap::new(), isolated }
+    }
+}
+
+/// Checks the arguments of a call, bound to the parameters as done at runtime
+fn check_arguments(signature: &Signature, args: Vec<(Option<&String>, StaticType)>) -> Result<(), TypeError> {
+    let positional = signature.params.iter().filter(|p| !p.variadic).count();
+    let mut index = 0;
+    for (name, found) in args {
+        let param = match name {
+            Some(name) => signature.params.iter().find(|p| &p.name == name && !p.variadic),
+            None => {
+                index += 1;
+                // Extra arguments are collected by the variadic parameter, whose elements are not annotated
+                signature.params.get(index - 1).filter(|_| index <= positional)
+            },
+        };
+        if let Some(param) = param {
+            if !found.fits(&param.ty) {
+                return Err(TypeError::MismatchedArgument(param.name.clone(), signature.name.clone(), param.ty.clone(), found))
+            }
+        }
+    }
+    Ok(())
+}
+
+fn constant_type(constant: &Constant) -> Type {
+    match constant {
+        Constant::Unit => Type::Unit,
+        Constant::Int(_) => Type::Int,
+        Constant::Float(_) => Type::Float,
+        Constant::String(_) => Type::String,
+        Constant::Bool(_) => Type::Bool,
+        Constant::Done => Type::Done,
+    }
+}
+
+/// Result type of a binary operation, following the runtime operations
+fn binary_type(op: BinaryOpcode, left: &StaticType, right: &StaticType) -> StaticType {
+    use StaticType::Value;
+    let numeric = |t: &StaticType| matches!(t, Value(Type::Int) | Value(Type::Float));
+    let t = match op {
+        BinaryOpcode::Add => match (left, right) {
+            (Value(Type::Int), Value(Type::Int)) => Type::Int,
+            (l, r) if numeric(l) && numeric(r) => Type::Float,
+            (Value(Type::String), _) => Type::String,
+            (l, Value(Type::String)) if numeric(l) => Type::String,
+            (Value(Type::List), Value(Type::List)) => Type::List,
+            _ => return StaticType::Any,
+        },
+        BinaryOpcode::Sub | BinaryOpcode::Mul | BinaryOpcode::Div => match (left, right) {
+            (Value(Type::Int), Value(Type::Int)) => Type::Int,
+            (l, r) if numeric(l) && numeric(r) => Type::Float,
+            _ => return StaticType::Any,
+        },
+        BinaryOpcode::Equals | BinaryOpcode::NotEquals | BinaryOpcode::Lower | BinaryOpcode::Greater
+        | BinaryOpcode::LowerEquals | BinaryOpcode::GreaterEquals => Type::Bool,
+        // Logical operators evaluate to one of their operands
+        BinaryOpcode::And | BinaryOpcode::Or => match (left, right) {
+            (Value(Type::Bool), Value(Type::Bool)) => Type::Bool,
+            _ => return StaticType::Any,
+        },
+    };
+    Value(t)
+}
+
+/// Type returned by a built-in function
+fn builtin_type(name: &str) -> StaticType {
+    let t = match name {
+        "input" | "type_of" | "str" => Type::String,
+        "int" => Type::Int,
+        "float" => Type::Float,
+        "bool" | "is_instance" => Type::Bool,
+        "args" => Type::List,
+        "iter" => Type::Iterator,
+        _ => return StaticType::Any,
+    };
+    StaticType::Value(t)
+}
+
+/// Returns true if a function body contains yield, not considering nested functions
+fn contains_yield(ast: &AST) -> bool {
+    match ast {
+        AST::Yield(_) => true,
+        AST::Function(_) | AST::Closure { .. } | AST::Class(_) => false,
+        ast => ast.children().into_iter().any(contains_yield),
+    }
+}
+
+/// Collects the names of the variables assigned with `=`
+fn collect_reassigned(ast: &AST, names: &mut HashSet<String>) {
+    if let AST::Assignment(left, _) = ast {
+        if let AST::Identifier(name, _) = left.as_ref() {
+            names.insert(name.clone());
+        }
+    }
+    for child in ast.children() {
+        collect_reassigned(child, names);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval, ProgramError};
+    use crate::compiler::ast::AST;
+    use crate::compiler::epilang::ASTParser;
+    use crate::compiler::error::TypeError;
+    use crate::intermediate::constant::Type;
+
+    use super::{StaticType, TypeChecker};
+
+    fn type_error(text: &str) -> TypeError {
+        match eval(text) {
+            Err(ProgramError::TypeError(e)) => e,
+            result => panic!("expected a type error, got {:?}", result),
+        }
+    }
+
+    fn parse(text: &str) -> AST {
+        ASTParser::new().parse(text).unwrap()
+    }
+
+    #[test]
+    fn annotations() {
+        assert!(matches!(type_error("let n: Int = \"a\""), TypeError::MismatchedAssignment(name, StaticType::Value(Type::Int), StaticType::Value(Type::String)) if name == "n"));
+        assert!(matches!(type_error("fn f(a: Int) { a }; f(1.5)"), TypeError::MismatchedArgument(..)));
+        assert!(matches!(type_error("fn f() -> String { 1 }"), TypeError::MismatchedReturn(..)));
+        assert!(matches!(type_error("class P { let x: Int }; let p = P(); p.x = \"a\""), TypeError::MismatchedAssignment(..)));
+        assert!(matches!(type_error("let p: Point = 1"), TypeError::UnknownType(name) if name == "Point"));
+        assert!(matches!(type_error("class P {}; fn f(p: P) { p }; f(1)"), TypeError::MismatchedArgument(..)));
+        assert_eq!(eval("class P {}; fn f(p: P) { 1 }; f(P())").unwrap(), "1");
+    }
+
+    #[test]
+    fn unannotated_variables_have_the_type_of_their_value() {
+        assert!(matches!(type_error("let x = 1; let s: String = x"), TypeError::MismatchedAssignment(name, _, StaticType::Value(Type::Int)) if name == "s"));
+        assert!(matches!(type_error("fn f(s: String) { s }; let x = [1]; f(x)"), TypeError::MismatchedArgument(..)));
+        // Variables assigned again can hold values of any type
+        assert_eq!(eval("let x = 1; x = \"a\"; let s: String = x; s").unwrap(), "\"a\"");
+        assert_eq!(eval("let x = 1; { x = \"a\" }; x").unwrap(), "\"a\"");
+    }
+
+    #[test]
+    fn inferred_types_across_programs() {
+        let mut checker = TypeChecker::new();
+        checker.check(&parse("let x = 1")).unwrap();
+        assert!(checker.check(&parse("let s: String = x")).is_err());
+        // Assigned in a later program, as in the REPL
+        checker.check(&parse("x = \"a\"")).unwrap();
+        checker.check(&parse("let s: String = x")).unwrap();
+    }
+
+    #[test]
+    fn code_without_annotations_is_not_checked() {
+        assert_eq!(eval("fn f(a) { a }; [f(1), f(\"a\")]").unwrap(), "[1, a]");
+        assert!(matches!(eval("let x = 1; x.push(2)"), Err(ProgramError::RuntimeError(_))));
+    }
+}
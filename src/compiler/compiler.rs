@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
-use crate::intermediate::exp::{Exp, FunctionExp, ClassExp, BuiltInFunction, FieldExp};
+use crate::intermediate::constant::Constant;
+use crate::intermediate::exp::{Exp, FunctionExp, ClassExp, BuiltInFunction, FieldExp, ParamExp, StatementInfo, StaticFieldExp};
 
-use super::ast::AST;
+use super::ast::{AST, ArgAST, ClassAST, FunctionAST, ParamAST, TraitAST, TypeAST};
 use super::error::CompilerError;
-use super::context::CompilerContext;
+use super::context::{CompilerContext, SymbolKind, TraitDef, TraitMethodDef};
 
 pub fn compile(ast: &AST, ctx: &mut CompilerContext) -> Result<Exp, CompilerError> {
 
@@ -13,27 +15,32 @@ pub fn compile(ast: &AST, ctx: &mut CompilerContext) -> Result<Exp, CompilerErro
             Ok(Exp::Constant { value: value.clone() })
         },
 
-        AST::Identifier(name) => {
+        AST::Identifier(name, span) => {
+            ctx.set_span(*span);
             match ctx.variable_scope(name) {
                 // If identifier matches a variable name return variable expression
-                Some(scope) => Ok(Exp::Variable { scope }),
+                Some(scope) => {
+                    ctx.reference(ctx.variable_symbol(name), *span);
+                    Ok(Exp::Variable { scope })
+                },
                 // If identifier matches a class name return class expression
                 None => match ctx.class_id(name) {
-                    Some(id) => Ok(Exp::Class { id }),
+                    Some(id) => {
+                        ctx.reference(ctx.class_symbol(id), *span);
+                        Ok(Exp::Class { id })
+                    },
                     // Check if identifier matches some built-in function name
-                    None => match name.as_str() {
-                        "print" => Ok(Exp::BuiltInFunction(BuiltInFunction::Print)),
-                        "println" => Ok(Exp::BuiltInFunction(BuiltInFunction::Println)),
-                        "input" => Ok(Exp::BuiltInFunction(BuiltInFunction::Input)),
-                        _ => Err(CompilerError::UnknownIdentifier(name.clone()))
+                    None => match BUILTIN_FUNCTIONS.iter().find(|(n, _)| n == name) {
+                        Some((_, function)) => Ok(Exp::BuiltInFunction(*function)),
+                        None => Err(CompilerError::UnknownIdentifier(name.clone())),
                     }
                 },
             }
         },
 
         AST::Concatenation { left, right } => {
-            let exp1 = compile(left, ctx)?;
-            let exp2 = compile(right, ctx)?;
+            let exp1 = compile_statement(left, ctx)?;
+            let exp2 = compile_statement(right, ctx)?;
             Ok(Exp::Concatenation { first: Box::new(exp1), second: Box::new(exp2) })
         },
 
@@ -48,12 +55,19 @@ pub fn compile(ast: &AST, ctx: &mut CompilerContext) -> Result<Exp, CompilerErro
             Ok(Exp::UnaryOp { op: *op, arg: Box::new(exp) })
         },
 
-        AST::Definition(name) => {
+        AST::Definition(name, span, annotation) => {
+            ctx.set_span(*span);
+            let shadowed = ctx.variable_symbol(name);
             let scope = ctx.define_variable(name.clone());
+            let detail = format!("let {}{}", name, annotation_signature(annotation));
+            let symbol = ctx.declare(name, SymbolKind::Variable, *span, detail);
+            ctx.shadow(symbol, shadowed);
+            ctx.bind_variable(name, symbol);
             Ok(Exp::Let { scope })
         },
 
         AST::Assignment(left, right) => {
+            check_self_field_assignment(left, ctx)?;
             let left_exp = compile(left, ctx)?;
             let right_exp = compile(right, ctx)?;
             match left_exp {
@@ -66,9 +80,9 @@ pub fn compile(ast: &AST, ctx: &mut CompilerContext) -> Result<Exp, CompilerErro
                         })
                     })
                 },
-                Exp::Variable { scope: _ } |
-                Exp::Subscript { element: _, index: _ } |
-                Exp::PropertyAccess { exp: _, property: _ } => {
+                Exp::Variable { .. } |
+                Exp::Subscript { .. } |
+                Exp::PropertyAccess { .. } => {
                     Ok(Exp::Assignment {
                         left: Box::new(left_exp),
                         right: Box::new(right_exp)
@@ -83,7 +97,7 @@ pub fn compile(ast: &AST, ctx: &mut CompilerContext) -> Result<Exp, CompilerErro
             Ok(Exp::Block { exp: Box::new(exp) })
         },
 
-        AST::Condition { exp, then_block, else_block } => {
+        AST::Condition { exp, then_block, else_block, .. } => {
             let exp = compile(exp, ctx)?;
             // then block
             let then_block = compile_block(then_block, ctx, false)?;
@@ -96,12 +110,25 @@ pub fn compile(ast: &AST, ctx: &mut CompilerContext) -> Result<Exp, CompilerErro
             })
         },
 
-        AST::While {guard, exp } => {
+        AST::While { guard, exp, .. } => {
             let guard = compile(guard, ctx)?;
-            let exp = compile(exp, ctx)?;
+            let exp = compile_statement(exp, ctx)?;
             Ok(Exp::While { guard: Box::new(guard), exp: Box::new(exp) })
         },
 
+        AST::For { var, span, iterable, exp } => {
+            let iterable = compile(iterable, ctx)?;
+            // Loop variable is defined in the loop body scope
+            ctx.push_frame(false);
+            ctx.define_variable(var.clone());
+            let symbol = ctx.declare(var, SymbolKind::Variable, *span, format!("let {}", var));
+            ctx.bind_variable(var, symbol);
+            let result = compile_statement(exp, ctx);
+            // Pops frame before eventually returning error
+            ctx.pop_frame();
+            Ok(Exp::For { iterable: Box::new(iterable), exp: Box::new(result?) })
+        },
+
         AST::List { elements } => {
             let mut list = Vec::with_capacity(elements.len());
             for element in elements {
@@ -114,93 +141,615 @@ pub fn compile(ast: &AST, ctx: &mut CompilerContext) -> Result<Exp, CompilerErro
             let e = compile(element, ctx)?;
             let i = compile(index, ctx)?;
             Ok(Exp::Subscript { element: Box::new(e), index: Box::new(i) })
+        },
+
+        AST::Slice { element, start, end, step } => {
+            let e = compile(element, ctx)?;
+            let mut compile_bound = |bound: &Option<Box<AST>>| -> Result<Option<Box<Exp>>, CompilerError> {
+                match bound {
+                    Some(ast) => Ok(Some(Box::new(compile(ast, ctx)?))),
+                    None => Ok(None),
+                }
+            };
+            let start = compile_bound(start)?;
+            let end = compile_bound(end)?;
+            let step = compile_bound(step)?;
+            Ok(Exp::Slice { element: Box::new(e), start, end, step })
         }
 
         AST::Function(f) => {
-            let fn_exp = compile_function(Some(&f.name), &f.args, &f.body, ctx)?;
+            ctx.set_span(f.span);
+            let symbol = ctx.declare(&f.name, SymbolKind::Function, f.span, signature(f));
+            let fn_exp = compile_function(Some((&f.name, symbol)), f.name.clone(), false, &f.params, &f.body, ctx)?;
             // Function is assigned to a new variable in current scope
             ctx.define_variable(f.name.clone());
+            ctx.bind_variable(&f.name, symbol);
             Ok(Exp::Function(Box::new(fn_exp)))
         },
 
-        AST::Closure { args, exp } => {
-            let fn_exp = compile_function(None, args, exp, ctx)?;
+        AST::Closure { params, exp } => {
+            let fn_exp = compile_function(None, "<closure>".to_owned(), false, params, exp, ctx)?;
             Ok(Exp::Closure(Box::new(fn_exp)))
         },
 
         AST::FunctionCall { fun, args } => {
             let fun_exp = compile(fun, ctx)?;
             let mut args_exps = Vec::new();
+            let mut named_args_exps: Vec<(String, Exp)> = Vec::new();
             for arg in args {
-                let arg_exp = compile(arg, ctx)?;
-                args_exps.push(arg_exp);
+                match arg {
+                    ArgAST::Positional(arg) => {
+                        if !named_args_exps.is_empty() {
+                            return Err(CompilerError::PositionalArgumentAfterNamed)
+                        }
+                        args_exps.push(compile(arg, ctx)?);
+                    },
+                    ArgAST::Named(name, arg) => {
+                        if named_args_exps.iter().any(|(n, _)| n == name) {
+                            return Err(CompilerError::DuplicateNamedArgument(name.clone()))
+                        }
+                        named_args_exps.push((name.clone(), compile(arg, ctx)?));
+                    },
+                }
             };
-            Ok(Exp::FunctionCall { fun: Box::new(fun_exp), args: args_exps })
+            let offset = ast.first_span().map_or(0, |span| span.start);
+            Ok(Exp::FunctionCall { fun: Box::new(fun_exp), args: args_exps, named_args: named_args_exps, offset })
         },
 
         AST::Class(class_ast) => {
             let class_ast = class_ast.as_ref();
-            // Build class fields
-            let mut fields = Vec::with_capacity(class_ast.fields.len());
-            for field in class_ast.fields.iter() {
-                fields.push(FieldExp { name: field.name.clone() })
+            ctx.set_span(class_ast.span);
+            check_class_members(class_ast)?;
+            // Class is defined before compiling methods, so they can refer to it
+            let traits = class_ast.traits.iter().map(|name| {
+                ctx.trait_id(name).ok_or_else(|| CompilerError::UnknownTrait(name.clone()))
+            }).collect::<Result<Vec<usize>, CompilerError>>()?;
+            for trait_id in traits.iter() {
+                check_trait_implementation(class_ast, ctx.get_trait(*trait_id))?;
             }
-            // Build class constructor (if present)
-            let mut constructor = None;
-            // Build class methods
-            let mut methods = HashMap::with_capacity(class_ast.methods.len());
-            for m in &class_ast.methods {
-                let mut args = Vec::with_capacity(m.args.len());
-                args.push("self".to_owned()); // Push self as implicit first argument in methods
-                args.extend_from_slice(&m.args);
-                let function_exp = compile_function(None, &args, &m.body, ctx)?;
-                if m.name == "self" {
-                    constructor = Some(function_exp);
-                } else {
-                    methods.insert(m.name.clone(), function_exp);
+            let id = ctx.define_class(class_ast.name.clone())?;
+            declare_class_symbols(id, class_ast, ctx);
+            let field_names = class_ast.fields.iter().map(|f| f.name.clone()).collect();
+            ctx.enter_class(id, class_ast.name.clone(), field_names);
+            let result = compile_class(id, traits, class_ast, ctx);
+            // Exits class before eventually returning error
+            ctx.exit_class();
+            Ok(Exp::ClassDef(Box::new(result?)))
+        },
+
+        AST::PropertyAccess { exp, property, span } => {
+            ctx.set_span(*span);
+            if let AST::Identifier(namespace, _) = exp.as_ref() {
+                // Built-in namespaces can be shadowed by variables and classes
+                if NAMESPACES.contains(&namespace.as_str()) && ctx.variable_scope(namespace).is_none() && ctx.class_id(namespace).is_none() {
+                    return namespace_member(namespace, property)
+                        .ok_or_else(|| CompilerError::UnknownNamespaceMember(namespace.clone(), property.clone()))
                 }
             }
-            // Build class
-            let class_exp = ClassExp {
-                id: ctx.define_class(class_ast.name.clone())?,
-                name: class_ast.name.clone(),
-                fields,
-                constructor: constructor.unwrap_or(FunctionExp::default_constructor()),
-                methods,
+            let on_self = matches!(exp.as_ref(), AST::Identifier(name, _) if name == "self");
+            let exp = compile(exp, ctx)?;
+            let from_class = ctx.current_class().map(|c| c.id);
+            // Members are resolved statically when accessed on self or on a class
+            let class_id = match &exp {
+                Exp::Variable { .. } if on_self => from_class,
+                Exp::Class { id } => Some(*id),
+                _ => None,
             };
-            Ok(Exp::ClassDef(Box::new(class_exp)))
+            if let Some(class_id) = class_id {
+                ctx.reference(ctx.member_symbol(class_id, property), *span);
+            }
+            Ok(Exp::PropertyAccess { exp: Box::new(exp), property: property.clone(), from_class })
         },
 
-        AST::PropertyAccess { exp, property } => {
+        AST::Trait(trait_ast) => {
+            ctx.set_span(trait_ast.span);
+            let trait_def = compile_trait(trait_ast)?;
+            ctx.declare(&trait_ast.name, SymbolKind::Trait, trait_ast.span, format!("trait {}", trait_ast.name));
+            ctx.define_trait(trait_def);
+            Ok(Exp::Constant { value: Constant::Unit })
+        },
+
+        // Test blocks are skipped, the test runner evaluates them separately
+        AST::Test { .. } => Ok(Exp::Constant { value: Constant::Unit }),
+
+        AST::Yield(exp) => {
+            if !ctx.mark_generator() {
+                return Err(CompilerError::YieldOutsideFunction)
+            }
+            let exp = compile(exp, ctx)?;
+            Ok(Exp::Yield { exp: Box::new(exp) })
+        },
+
+        AST::Implements { exp, trait_name } => {
             let exp = compile(exp, ctx)?;
-            Ok(Exp::PropertyAccess { exp: Box::new(exp), property: property.clone() })
+            let trait_id = ctx.trait_id(trait_name)
+                .ok_or_else(|| CompilerError::UnknownTrait(trait_name.clone()))?;
+            Ok(Exp::Implements { exp: Box::new(exp), trait_id })
         },
     }
 }
 
-fn compile_function(name: Option<&str>, args: &Vec<String>, body: &AST, ctx: &mut CompilerContext) -> Result<FunctionExp, CompilerError> {
+/// Compiles a source file to resolve its symbols, which must be recorded by the context.
+/// Test blocks are compiled after the other statements, as done by the test runner.
+pub fn resolve_symbols(ast: &AST, ctx: &mut CompilerContext) -> Result<(), CompilerError> {
+    compile(ast, ctx)?;
+    for statement in ast.statements() {
+        if let AST::Test { body, .. } = statement {
+            compile_block(body, ctx, false)?;
+        }
+    }
+    Ok(())
+}
+
+/// Built-in functions available in any scope
+pub const BUILTIN_FUNCTIONS: &[(&str, BuiltInFunction)] = &[
+    ("print", BuiltInFunction::Print),
+    ("println", BuiltInFunction::Println),
+    ("input", BuiltInFunction::Input),
+    ("iter", BuiltInFunction::Iter),
+    ("args", BuiltInFunction::Args),
+    ("env", BuiltInFunction::Env),
+    ("exit", BuiltInFunction::Exit),
+    ("type_of", BuiltInFunction::TypeOf),
+    ("int", BuiltInFunction::ToInt),
+    ("float", BuiltInFunction::ToFloat),
+    ("str", BuiltInFunction::ToStr),
+    ("bool", BuiltInFunction::ToBool),
+    ("is_instance", BuiltInFunction::IsInstance),
+    ("assert", BuiltInFunction::Assert),
+    ("assert_eq", BuiltInFunction::AssertEq),
+];
+
+/// Built-in namespaces, whose members are resolved at compile time
+pub const NAMESPACES: [&str; 6] = ["math", "fs", "json", "time", "random", "regex"];
+
+/// Functions of the built-in namespaces
+pub const NAMESPACE_FUNCTIONS: &[(&str, &str, BuiltInFunction)] = &[
+    ("math", "sqrt", BuiltInFunction::MathSqrt),
+    ("math", "pow", BuiltInFunction::MathPow),
+    ("math", "abs", BuiltInFunction::MathAbs),
+    ("math", "floor", BuiltInFunction::MathFloor),
+    ("math", "ceil", BuiltInFunction::MathCeil),
+    ("math", "round", BuiltInFunction::MathRound),
+    ("math", "min", BuiltInFunction::MathMin),
+    ("math", "max", BuiltInFunction::MathMax),
+    ("math", "sin", BuiltInFunction::MathSin),
+    ("math", "cos", BuiltInFunction::MathCos),
+    ("math", "tan", BuiltInFunction::MathTan),
+    ("math", "asin", BuiltInFunction::MathAsin),
+    ("math", "acos", BuiltInFunction::MathAcos),
+    ("math", "atan", BuiltInFunction::MathAtan),
+    ("math", "atan2", BuiltInFunction::MathAtan2),
+    ("math", "log", BuiltInFunction::MathLog),
+    ("math", "log2", BuiltInFunction::MathLog2),
+    ("math", "log10", BuiltInFunction::MathLog10),
+    ("math", "exp", BuiltInFunction::MathExp),
+    ("fs", "read_file", BuiltInFunction::FsReadFile),
+    ("fs", "write_file", BuiltInFunction::FsWriteFile),
+    ("fs", "append_file", BuiltInFunction::FsAppendFile),
+    ("fs", "exists", BuiltInFunction::FsExists),
+    ("fs", "list_dir", BuiltInFunction::FsListDir),
+    ("fs", "mkdir", BuiltInFunction::FsMkdir),
+    ("fs", "remove", BuiltInFunction::FsRemove),
+    ("fs", "remove_dir_all", BuiltInFunction::FsRemoveDirAll),
+    ("fs", "join", BuiltInFunction::FsJoin),
+    ("json", "parse", BuiltInFunction::JsonParse),
+    ("json", "stringify", BuiltInFunction::JsonStringify),
+    ("time", "now", BuiltInFunction::TimeNow),
+    ("time", "clock", BuiltInFunction::TimeClock),
+    ("time", "sleep", BuiltInFunction::TimeSleep),
+    ("random", "seed", BuiltInFunction::RandomSeed),
+    ("random", "int", BuiltInFunction::RandomInt),
+    ("random", "float", BuiltInFunction::RandomFloat),
+    ("random", "choice", BuiltInFunction::RandomChoice),
+    ("random", "shuffle", BuiltInFunction::RandomShuffle),
+    ("regex", "compile", BuiltInFunction::RegexCompile),
+];
+
+/// Constants of the built-in namespaces
+pub const NAMESPACE_CONSTANTS: &[(&str, &str, f32)] = &[
+    ("math", "PI", std::f32::consts::PI),
+    ("math", "E", std::f32::consts::E),
+];
+
+fn namespace_member(namespace: &str, member: &str) -> Option<Exp> {
+    if let Some((_, _, value)) = NAMESPACE_CONSTANTS.iter().find(|(ns, name, _)| *ns == namespace && *name == member) {
+        return Some(Exp::Constant { value: Constant::Float(*value) })
+    }
+    NAMESPACE_FUNCTIONS.iter()
+        .find(|(ns, name, _)| *ns == namespace && *name == member)
+        .map(|(_, _, function)| Exp::BuiltInFunction(*function))
+}
+
+/// Compiles a function, a closure or a method. The label names the function in the debugger.
+/// Methods receive `self` as implicit first parameter.
+fn compile_function(name: Option<(&str, Option<usize>)>, label: String, is_method: bool, params: &[ParamAST], body: &AST, ctx: &mut CompilerContext) -> Result<FunctionExp, CompilerError> {
     ctx.push_frame(true);
-    if name.is_some() {
+    ctx.enter_function(label);
+    if let Some((name, symbol)) = name {
         // Function is assigned to a variable in its own scope to enable recursion
-        ctx.define_variable(name.unwrap().to_owned());
+        ctx.define_variable(name.to_owned());
+        ctx.bind_variable(name, symbol);
     }
-    for arg in args {
-        ctx.define_variable(arg.clone());
-    }
-    let result = compile(body, ctx);
+    let result = compile_params(is_method, params, ctx)
+        .and_then(|params| Ok((params, compile_statement(body, ctx)?)));
     // Pops frame before eventually returning error
+    let is_generator = ctx.exit_function();
     ctx.pop_frame();
+    let (params, body) = result?;
+    if is_generator {
+        check_yield_positions(&body, true)?;
+        for default in params.iter().filter_map(|p| p.default.as_ref()) {
+            check_yield_positions(default, false)?;
+        }
+    }
     Ok(FunctionExp {
-        num_args: args.len(),
+        params,
         external_vars: Vec::new(), // TODO: extract external variables from frame
-        body: result?
+        body,
+        is_generator,
+    })
+}
+
+/// Generators can suspend only at yield statements, in the function body or in the blocks,
+/// conditions and loops that are statements. Nested functions are checked when they are compiled.
+fn check_yield_positions(exp: &Exp, is_statement: bool) -> Result<(), CompilerError> {
+    let children: Vec<&Exp> = match exp {
+        Exp::Yield { exp } if is_statement => vec![exp],
+        Exp::Yield { .. } => return Err(CompilerError::YieldInExpression),
+        Exp::Concatenation { first, second } => {
+            check_yield_positions(first, is_statement)?;
+            return check_yield_positions(second, is_statement)
+        },
+        Exp::Block { exp } | Exp::Statement { exp, .. } => return check_yield_positions(exp, is_statement),
+        Exp::Condition { exp, then_block, else_block } => {
+            check_yield_positions(exp, false)?;
+            check_yield_positions(then_block, is_statement)?;
+            return check_yield_positions(else_block, is_statement)
+        },
+        Exp::While { guard: head, exp } | Exp::For { iterable: head, exp } => {
+            check_yield_positions(head, false)?;
+            return check_yield_positions(exp, is_statement)
+        },
+        Exp::Constant { .. } | Exp::Variable { .. } | Exp::Let { .. } | Exp::Class { .. } | Exp::BuiltInFunction(_) => Vec::new(),
+        Exp::Function(_) | Exp::Closure(_) => Vec::new(),
+        Exp::ClassDef(class) => class.fields.iter().filter_map(|f| f.value.as_ref())
+            .chain(class.static_fields.iter().map(|f| &f.value))
+            .collect(),
+        Exp::BinaryOp { arg1, arg2, .. } => vec![arg1, arg2],
+        Exp::UnaryOp { arg: exp, .. } | Exp::PropertyAccess { exp, .. } | Exp::Implements { exp, .. } => vec![exp],
+        Exp::Assignment { left, right } => vec![left, right],
+        Exp::List { elements } => elements.iter().collect(),
+        Exp::Subscript { element, index } => vec![element, index],
+        Exp::Slice { element, start, end, step } => std::iter::once(element).chain(start).chain(end).chain(step)
+            .map(Box::as_ref)
+            .collect(),
+        Exp::FunctionCall { fun, args, named_args, .. } => std::iter::once(fun.as_ref()).chain(args)
+            .chain(named_args.iter().map(|(_, arg)| arg))
+            .collect(),
+    };
+    children.into_iter().try_for_each(|child| check_yield_positions(child, false))
+}
+
+/// Defines function parameters in current frame. Default values are compiled
+/// in the function scope, so they can refer to the previous parameters.
+fn compile_params(is_method: bool, params: &[ParamAST], ctx: &mut CompilerContext) -> Result<Vec<ParamExp>, CompilerError> {
+    let mut params_exps = Vec::with_capacity(params.len() + 1);
+    if is_method {
+        // Push self as implicit first parameter in methods
+        ctx.define_variable("self".to_owned());
+        params_exps.push(ParamExp::new("self".to_owned()));
+    }
+    for (i, param) in params.iter().enumerate() {
+        ctx.set_span(param.span);
+        if params_exps.iter().any(|p: &ParamExp| p.name == param.name) {
+            return Err(CompilerError::DuplicateParameter(param.name.clone()))
+        }
+        if param.variadic && i + 1 < params.len() {
+            return Err(CompilerError::VariadicParameterNotLast(param.name.clone()))
+        }
+        if param.default.is_none() && !param.variadic && params_exps.iter().any(|p| p.default.is_some()) {
+            return Err(CompilerError::RequiredParameterAfterDefault(param.name.clone()))
+        }
+        let default = match &param.default {
+            Some(ast) => Some(compile_block(ast, ctx, false)?),
+            None => None,
+        };
+        ctx.define_variable(param.name.clone());
+        let symbol = ctx.declare(&param.name, SymbolKind::Parameter, param.span, param_signature(param));
+        ctx.bind_variable(&param.name, symbol);
+        params_exps.push(ParamExp { name: param.name.clone(), default, variadic: param.variadic });
+    }
+    Ok(params_exps)
+}
+
+fn compile_class(id: usize, traits: Vec<usize>, class_ast: &ClassAST, ctx: &mut CompilerContext) -> Result<ClassExp, CompilerError> {
+    // Build class fields
+    let mut fields = Vec::with_capacity(class_ast.fields.len());
+    for field in class_ast.fields.iter() {
+        ctx.set_span(field.span);
+        let value = match &field.value {
+            Some(value) => Some(compile_field_initializer(value, ctx)?),
+            None => None,
+        };
+        fields.push(FieldExp { name: field.name.clone(), value })
+    }
+    // Build class constructor (if present)
+    let mut constructor = None;
+    // Build class methods
+    let mut methods = HashMap::with_capacity(class_ast.methods.len());
+    for m in class_ast.methods.iter().map(|m| &m.function) {
+        ctx.set_span(m.span);
+        let function_exp = compile_function(None, format!("{}.{}", class_ast.name, m.name), true, &m.params, &m.body, ctx)?;
+        if m.name == "self" {
+            constructor = Some(function_exp);
+        } else {
+            methods.insert(m.name.clone(), function_exp);
+        }
+    }
+    // Build static fields, initialized in the scope where the class is defined
+    let mut static_fields = Vec::with_capacity(class_ast.static_fields.len());
+    for field in &class_ast.static_fields {
+        ctx.set_span(field.span);
+        let value = match &field.value {
+            Some(value) => compile_block(value, ctx, false)?,
+            None => Exp::default(),
+        };
+        static_fields.push(StaticFieldExp { name: field.name.clone(), value });
+    }
+    // Build static methods, which do not receive self
+    let mut static_methods = HashMap::with_capacity(class_ast.static_methods.len());
+    for m in class_ast.static_methods.iter().map(|m| &m.function) {
+        ctx.set_span(m.span);
+        let function_exp = compile_function(None, format!("{}.{}", class_ast.name, m.name), false, &m.params, &m.body, ctx)?;
+        static_methods.insert(m.name.clone(), function_exp);
+    }
+    // Members declared as private are accessible only from the class body, the constructor is always public
+    let private_members = class_ast.fields.iter().chain(&class_ast.static_fields)
+        .filter(|f| !f.public).map(|f| f.name.clone())
+        .chain(class_ast.methods.iter().chain(&class_ast.static_methods)
+            .filter(|m| !m.public && m.function.name != "self").map(|m| m.function.name.clone()))
+        .collect();
+    // Build class
+    Ok(ClassExp {
+        id,
+        name: class_ast.name.clone(),
+        fields,
+        constructor: constructor.unwrap_or(FunctionExp::default_constructor()),
+        methods,
+        static_fields,
+        static_methods,
+        private_members,
+        traits,
     })
 }
 
+fn compile_trait(trait_ast: &TraitAST) -> Result<TraitDef, CompilerError> {
+    let mut methods: Vec<TraitMethodDef> = Vec::with_capacity(trait_ast.methods.len());
+    for m in trait_ast.methods.iter() {
+        if methods.iter().any(|other| other.name == m.name) {
+            return Err(CompilerError::DuplicateTraitMethod(trait_ast.name.clone(), m.name.clone()))
+        }
+        let is_static = m.params.first().is_none_or(|p| p.name != "self");
+        let num_params = if is_static { m.params.len() } else { m.params.len() - 1 };
+        methods.push(TraitMethodDef { name: m.name.clone(), num_params, is_static });
+    }
+    Ok(TraitDef { name: trait_ast.name.clone(), methods })
+}
+
+/// Checks that a class implements each trait method with a public method
+/// accepting the same number of arguments
+fn check_trait_implementation(class_ast: &ClassAST, trait_def: &TraitDef) -> Result<(), CompilerError> {
+    for required in trait_def.methods.iter() {
+        let methods = if required.is_static { &class_ast.static_methods } else { &class_ast.methods };
+        let method = methods.iter().find(|m| m.function.name == required.name)
+            .ok_or_else(|| CompilerError::MissingTraitMethod(
+                class_ast.name.clone(), trait_def.name.clone(), required.name.clone()
+            ))?;
+        let params = &method.function.params;
+        let min_args = params.iter().filter(|p| p.default.is_none() && !p.variadic).count();
+        let variadic = params.last().is_some_and(|p| p.variadic);
+        let max_args = if variadic { usize::MAX } else { params.len() };
+        if required.num_params < min_args || required.num_params > max_args {
+            return Err(CompilerError::TraitMethodArity(
+                class_ast.name.clone(), trait_def.name.clone(), required.name.clone(), required.num_params
+            ))
+        }
+        if !method.public {
+            return Err(CompilerError::PrivateTraitMethod(
+                class_ast.name.clone(), trait_def.name.clone(), required.name.clone()
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Compiles an instance field initializer, with `self` as the only variable in scope
+fn compile_field_initializer(value: &AST, ctx: &mut CompilerContext) -> Result<Exp, CompilerError> {
+    ctx.push_frame(true);
+    ctx.define_variable("self".to_owned());
+    let result = compile(value, ctx);
+    // Pops frame before eventually returning error
+    ctx.pop_frame();
+    result
+}
+
+/// Assigning an undeclared field to `self` inside a class body is detected at compile time
+fn check_self_field_assignment(left: &AST, ctx: &CompilerContext) -> Result<(), CompilerError> {
+    if let (AST::PropertyAccess { exp, property, .. }, Some(class)) = (left, ctx.current_class()) {
+        let is_self = matches!(exp.as_ref(), AST::Identifier(name, _) if name == "self");
+        if is_self && ctx.variable_scope("self").is_some() && !class.fields.contains(property) {
+            return Err(CompilerError::UndeclaredField(class.name.clone(), property.clone()))
+        }
+    }
+    Ok(())
+}
+
+/// Records the class and its members, so that references in the class body can be resolved
+fn declare_class_symbols(id: usize, class_ast: &ClassAST, ctx: &mut CompilerContext) {
+    let symbol = ctx.declare(&class_ast.name, SymbolKind::Class, class_ast.span, format!("class {}", class_ast.name));
+    ctx.bind_class(id, symbol);
+    let fields = class_ast.fields.iter().map(|f| (f, "")).chain(class_ast.static_fields.iter().map(|f| (f, "static ")));
+    for (field, modifier) in fields {
+        let visibility = if field.public { "" } else { "private " };
+        let detail = format!("{}{}let {}{}", visibility, modifier, field.name, annotation_signature(&field.annotation));
+        let symbol = ctx.declare(&field.name, SymbolKind::Field, field.span, detail);
+        ctx.bind_member(id, &field.name, symbol);
+    }
+    let methods = class_ast.methods.iter().map(|m| (m, "")).chain(class_ast.static_methods.iter().map(|m| (m, "static ")));
+    for (method, modifier) in methods {
+        let visibility = if method.public { "" } else { "private " };
+        let detail = format!("{}{}{}", visibility, modifier, signature(&method.function));
+        let symbol = ctx.declare(&method.function.name, SymbolKind::Method, method.function.span, detail);
+        ctx.bind_member(id, &method.function.name, symbol);
+    }
+}
+
+/// Function signature shown by editor tooling, eg: `fn add(a: Int, b = …) -> Int`
+fn signature(function: &FunctionAST) -> String {
+    let params: Vec<String> = function.params.iter().map(param_signature).collect();
+    let return_type = function.return_type.as_ref().map_or(String::new(), |t| format!(" -> {}", t.name));
+    format!("fn {}({}){}", function.name, params.join(", "), return_type)
+}
+
+fn param_signature(param: &ParamAST) -> String {
+    let annotation = annotation_signature(&param.annotation);
+    match (&param.default, param.variadic) {
+        (_, true) => format!("...{}{}", param.name, annotation),
+        (Some(_), false) => format!("{}{} = …", param.name, annotation),
+        (None, false) => format!("{}{}", param.name, annotation),
+    }
+}
+
+fn annotation_signature(annotation: &Option<TypeAST>) -> String {
+    annotation.as_ref().map_or(String::new(), |t| format!(": {}", t.name))
+}
+
+/// Checks that each class member name is declared only once
+fn check_class_members(class_ast: &ClassAST) -> Result<(), CompilerError> {
+    let names = class_ast.fields.iter().map(|f| &f.name)
+        .chain(class_ast.methods.iter().map(|m| &m.function.name))
+        .chain(class_ast.static_fields.iter().map(|f| &f.name))
+        .chain(class_ast.static_methods.iter().map(|m| &m.function.name));
+    let mut declared = HashSet::new();
+    for name in names {
+        if !declared.insert(name) {
+            return Err(CompilerError::DuplicateClassMember(class_ast.name.clone(), name.clone()))
+        }
+    }
+    Ok(())
+}
+
 fn compile_block(ast: &AST, ctx: &mut CompilerContext, isolated: bool) -> Result<Exp, CompilerError> {
     ctx.push_frame(isolated);
-    let result = compile(ast, ctx);
+    let result = compile_statement(ast, ctx);
     // Pops frame before eventually returning error
     ctx.pop_frame();
     result
 }
+
+/// Compiles a statement. When statements are marked, the statement records its position and
+/// the variables in scope, so that the debugger can pause before evaluating it.
+pub fn compile_statement(ast: &AST, ctx: &mut CompilerContext) -> Result<Exp, CompilerError> {
+    let span = match ast {
+        // Each statement of a sequence is marked on its own
+        AST::Concatenation { .. } | AST::Test { .. } => None,
+        ast if ctx.marks_statements() => ast.first_span(),
+        _ => None,
+    };
+    let offset = match span {
+        Some(span) => span.start,
+        None => return compile(ast, ctx),
+    };
+    let locals = ctx.local_variables();
+    let exp = compile(ast, ctx)?;
+    // Blocks whose first statement is at the same position are marked only once
+    if starts_with_statement(&exp, offset) {
+        return Ok(exp)
+    }
+    ctx.mark_statement(offset);
+    let info = StatementInfo { offset, function: ctx.function_name().map(str::to_owned), locals };
+    Ok(Exp::Statement { info: Arc::new(info), exp: Box::new(exp) })
+}
+
+fn starts_with_statement(exp: &Exp, offset: usize) -> bool {
+    match exp {
+        Exp::Statement { info, .. } => info.offset == offset,
+        Exp::Block { exp } => starts_with_statement(exp, offset),
+        Exp::Concatenation { first, .. } => starts_with_statement(first, offset),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval, ProgramError};
+    use super::CompilerError;
+
+    fn compiler_error(text: &str) -> CompilerError {
+        match eval(text) {
+            Err(ProgramError::CompilerError(e)) => e,
+            result => panic!("expected a compiler error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        assert!(matches!(compiler_error("fn f(a, a) { a }"), CompilerError::DuplicateParameter(name) if name == "a"));
+        assert!(matches!(compiler_error("fn f(a = 1, b) { a }"), CompilerError::RequiredParameterAfterDefault(name) if name == "b"));
+        assert!(matches!(compiler_error("fn f(...a, b) { a }"), CompilerError::VariadicParameterNotLast(name) if name == "a"));
+        assert!(matches!(compiler_error("class A { fn m(self) { 1 } }"), CompilerError::DuplicateParameter(name) if name == "self"));
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert!(matches!(compiler_error("fn f(a, b) { a }; f(a: 1, 2)"), CompilerError::PositionalArgumentAfterNamed));
+        assert!(matches!(compiler_error("fn f(a, b) { a }; f(a: 1, a: 2)"), CompilerError::DuplicateNamedArgument(name) if name == "a"));
+    }
+
+    #[test]
+    fn invalid_class_members() {
+        assert!(matches!(compiler_error("class A { let x fn self() { self.y = 1 } }"), CompilerError::UndeclaredField(_, name) if name == "y"));
+        assert!(matches!(compiler_error("class A { let x fn x() { 1 } }"), CompilerError::DuplicateClassMember(_, name) if name == "x"));
+    }
+
+    #[test]
+    fn trait_implementations_are_checked() {
+        let drawable = "trait Drawable { fn draw(self, scale); fn create(); };";
+        assert!(eval(&format!("{} class C implements Drawable {{ fn draw(s, offset = 0) {{ s }} static fn create() {{ C() }} }}", drawable)).is_ok());
+        assert!(matches!(
+            compiler_error(&format!("{} class C implements Drawable {{ static fn create() {{ C() }} }}", drawable)),
+            CompilerError::MissingTraitMethod(_, _, name) if name == "draw"
+        ));
+        // Static trait methods must be implemented by static methods
+        assert!(matches!(
+            compiler_error(&format!("{} class C implements Drawable {{ fn draw(s) {{ s }} fn create() {{ C() }} }}", drawable)),
+            CompilerError::MissingTraitMethod(_, _, name) if name == "create"
+        ));
+        assert!(matches!(
+            compiler_error(&format!("{} class C implements Drawable {{ fn draw() {{ 1 }} static fn create() {{ C() }} }}", drawable)),
+            CompilerError::TraitMethodArity(_, _, name, 1) if name == "draw"
+        ));
+        assert!(matches!(
+            compiler_error(&format!("{} class C implements Drawable {{ private fn draw(s) {{ s }} static fn create() {{ C() }} }}", drawable)),
+            CompilerError::PrivateTraitMethod(_, _, name) if name == "draw"
+        ));
+    }
+
+    #[test]
+    fn invalid_traits() {
+        assert!(matches!(compiler_error("class C implements Missing { }"), CompilerError::UnknownTrait(name) if name == "Missing"));
+        assert!(matches!(compiler_error("1 implements Missing"), CompilerError::UnknownTrait(name) if name == "Missing"));
+        assert!(matches!(compiler_error("trait T { fn a(self); fn a(self); }"), CompilerError::DuplicateTraitMethod(_, name) if name == "a"));
+    }
+
+    #[test]
+    fn yield_positions() {
+        assert!(eval("fn g(l) { yield 1; if l { yield 2 }; while false { yield 3 }; for x in l { { yield x } } }").is_ok());
+        assert!(matches!(compiler_error("yield 1"), CompilerError::YieldOutsideFunction));
+        assert!(matches!(compiler_error("fn g(f) { f((yield 1)) }"), CompilerError::YieldInExpression));
+        assert!(matches!(compiler_error("fn g() { let x = if true { (yield 1) } else { 2 } }"), CompilerError::YieldInExpression));
+        assert!(matches!(compiler_error("fn g(a = (yield 1)) { a }"), CompilerError::YieldInExpression));
+        assert!(matches!(compiler_error("fn g() { yield (yield 1) }"), CompilerError::YieldInExpression));
+    }
+}
@@ -1,21 +1,95 @@
 use std::collections::HashMap;
 
+use super::ast::Span;
 use super::error::CompilerError;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 struct Frame {
     pub var_scope: usize,
     // Maps each variable name to its scope
     pub variables: HashMap<String, usize>,
     // Maps each class name with its id
     pub classes: HashMap<String, usize>,
+    // Maps each trait name with its id
+    pub traits: HashMap<String, usize>,
+    // Maps each variable name to its symbol, when symbols are recorded
+    pub symbols: HashMap<String, usize>,
     pub isolated: bool,
 }
 
-#[derive(Debug)]
+/// Class whose members are being compiled
+#[derive(Clone, Debug)]
+pub struct ClassScope {
+    pub id: usize,
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+/// Trait declaration, used to check classes implementing it
+#[derive(Clone, Debug)]
+pub struct TraitDef {
+    pub name: String,
+    pub methods: Vec<TraitMethodDef>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TraitMethodDef {
+    pub name: String,
+    /// Number of parameters, not including self
+    pub num_params: usize,
+    pub is_static: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    Variable,
+    Parameter,
+    Function,
+    Class,
+    Trait,
+    Field,
+    Method,
+}
+
+/// Named declaration in the source text
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub span: Span,
+    /// Short description of the declaration, eg: the function signature
+    pub detail: String,
+}
+
+/// Declarations and resolved references collected during compilation, used by editor tooling
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    pub symbols: Vec<Symbol>,
+    /// Each reference is the id of the referenced symbol with the reference position
+    pub references: Vec<(usize, Span)>,
+    /// Variables declared while another variable with the same name is in scope,
+    /// each is the id of the new symbol with the id of the shadowed one
+    pub shadowed: Vec<(usize, usize)>,
+    classes: HashMap<usize, usize>,
+    members: HashMap<(usize, String), usize>,
+}
+
+#[derive(Clone, Debug)]
 pub struct CompilerContext {
     frames: Vec<Frame>,
     class_count: usize,
+    class_scopes: Vec<ClassScope>,
+    traits: Vec<TraitDef>,
+    // For each function being compiled, whether it contains yield
+    generator_flags: Vec<bool>,
+    // Names of the functions being compiled
+    function_names: Vec<String>,
+    // Present when statements are marked for the debugger, with the offsets of the marked statements
+    statements: Option<Vec<usize>>,
+    // Present when declarations and references are recorded
+    symbols: Option<SymbolTable>,
+    // Position of the last named node compiled
+    span: Span,
 }
 
 impl CompilerContext {
@@ -24,10 +98,34 @@ impl CompilerContext {
             var_scope: 0,
             variables: HashMap::new(),
             classes: HashMap::new(),
+            traits: HashMap::new(),
+            symbols: HashMap::new(),
             isolated: false,
         };
         let frames = vec![root_frame];
-        CompilerContext { frames, class_count: 0 }
+        CompilerContext {
+            frames,
+            class_count: 0,
+            class_scopes: Vec::new(),
+            traits: Vec::new(),
+            generator_flags: Vec::new(),
+            function_names: Vec::new(),
+            statements: None,
+            symbols: None,
+            span: Span::default(),
+        }
+    }
+
+    /// Creates a context where the variables and classes of a running program are visible,
+    /// used to compile the expressions evaluated by the debugger in a paused frame
+    pub fn from_frame(variables: &[(String, usize)], classes: &[(String, usize)], var_scope: usize) -> CompilerContext {
+        let mut ctx = CompilerContext::new();
+        let frame = &mut ctx.frames[0];
+        frame.variables = variables.iter().cloned().collect();
+        frame.classes = classes.iter().cloned().collect();
+        frame.var_scope = var_scope;
+        ctx.class_count = classes.iter().map(|(_, id)| id + 1).max().unwrap_or(0);
+        ctx
     }
 
     pub fn push_frame(&mut self, isolated: bool) {
@@ -36,6 +134,8 @@ impl CompilerContext {
             var_scope: if isolated { 0 } else { last.var_scope },
             variables: HashMap::new(),
             classes: HashMap::new(),
+            traits: HashMap::new(),
+            symbols: HashMap::new(),
             isolated
         };
         self.frames.push(new_frame)
@@ -46,16 +146,48 @@ impl CompilerContext {
     }
 
     pub fn variable_scope(&self, variable_name: &str) -> Option<usize> {
+        self.variable_frame(variable_name).and_then(|frame| frame.variables.get(variable_name).copied())
+    }
+
+    /// Returns the innermost frame defining a variable, not looking past isolated frames
+    fn variable_frame(&self, variable_name: &str) -> Option<&Frame> {
         for frame in self.frames.iter().rev() {
-            if let Some(value) = frame.variables.get(variable_name) {
-                return Some(*value)
+            if frame.variables.contains_key(variable_name) {
+                return Some(frame)
             }
             if frame.isolated { break }
-
         }
         None
     }
 
+    /// Variables defined in the root frame with their scopes, sorted by scope
+    pub fn global_variables(&self) -> Vec<(&str, usize)> {
+        let mut variables: Vec<(&str, usize)> = self.frames[0].variables.iter().map(|(name, scope)| (name.as_str(), *scope)).collect();
+        variables.sort_by_key(|(_, scope)| *scope);
+        variables
+    }
+
+    /// Variables visible in the function being compiled with their scopes, sorted by scope
+    pub fn local_variables(&self) -> Vec<(String, usize)> {
+        let mut variables: HashMap<&str, usize> = HashMap::new();
+        for frame in self.frames.iter().rev() {
+            for (name, scope) in &frame.variables {
+                // Inner frames shadow the outer ones
+                variables.entry(name).or_insert(*scope);
+            }
+            if frame.isolated { break }
+        }
+        let mut variables: Vec<(String, usize)> = variables.into_iter().map(|(name, scope)| (name.to_owned(), scope)).collect();
+        variables.sort_by_key(|(_, scope)| *scope);
+        variables
+    }
+
+    /// Names of the classes and traits defined in the root frame
+    pub fn global_types(&self) -> Vec<&str> {
+        let frame = &self.frames[0];
+        frame.classes.keys().chain(frame.traits.keys()).map(String::as_str).collect()
+    }
+
     pub fn define_variable(&mut self, variable_name: String) -> usize {
         let frame = self.frames.last_mut().unwrap();
         frame.variables.insert(variable_name, frame.var_scope);
@@ -80,4 +212,161 @@ impl CompilerContext {
         self.class_count += 1;
         Ok(class_id)
     }
+
+    pub fn trait_id(&self, trait_name: &str) -> Option<usize> {
+        for frame in self.frames.iter().rev() {
+            if let Some(value) = frame.traits.get(trait_name) {
+                return Some(*value)
+            }
+        }
+        None
+    }
+
+    pub fn get_trait(&self, id: usize) -> &TraitDef {
+        &self.traits[id]
+    }
+
+    pub fn define_trait(&mut self, trait_def: TraitDef) -> usize {
+        let frame = self.frames.last_mut().unwrap();
+        let trait_id = self.traits.len();
+        frame.traits.insert(trait_def.name.clone(), trait_id);
+        self.traits.push(trait_def);
+        trait_id
+    }
+
+    /// Marks the beginning of a function body
+    pub fn enter_function(&mut self, name: String) {
+        self.generator_flags.push(false);
+        self.function_names.push(name)
+    }
+
+    /// Marks the end of a function body, returns true if the function is a generator
+    pub fn exit_function(&mut self) -> bool {
+        self.function_names.pop();
+        self.generator_flags.pop().unwrap_or(false)
+    }
+
+    /// Name of the function being compiled, None at the top level
+    pub fn function_name(&self) -> Option<&str> {
+        self.function_names.last().map(String::as_str)
+    }
+
+    /// Marks the function being compiled as a generator.
+    /// Returns false when not compiling a function.
+    pub fn mark_generator(&mut self) -> bool {
+        match self.generator_flags.last_mut() {
+            Some(flag) => {
+                *flag = true;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Marks the beginning of a class body, where private members are accessible
+    pub fn enter_class(&mut self, id: usize, name: String, fields: Vec<String>) {
+        self.class_scopes.push(ClassScope { id, name, fields })
+    }
+
+    pub fn exit_class(&mut self) {
+        self.class_scopes.pop();
+    }
+
+    /// Returns the class whose body is being compiled, if any
+    pub fn current_class(&self) -> Option<&ClassScope> {
+        self.class_scopes.last()
+    }
+
+    /// Position of the last named node compiled, used to locate compiler errors
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn set_span(&mut self, span: Span) {
+        self.span = span
+    }
+
+    /// Starts marking statements, so the debugger can pause before them
+    pub fn mark_statements(&mut self) {
+        self.statements = Some(Vec::new())
+    }
+
+    /// Records the offset of a marked statement
+    pub fn mark_statement(&mut self, offset: usize) {
+        if let Some(statements) = self.statements.as_mut() {
+            statements.push(offset)
+        }
+    }
+
+    /// Returns true if statements are marked for the debugger
+    pub fn marks_statements(&self) -> bool {
+        self.statements.is_some()
+    }
+
+    /// Returns the offsets of the marked statements, in compilation order
+    pub fn take_statements(&mut self) -> Option<Vec<usize>> {
+        self.statements.take()
+    }
+
+    /// Starts recording declarations and references
+    pub fn record_symbols(&mut self) {
+        self.symbols = Some(SymbolTable::default())
+    }
+
+    pub fn take_symbols(&mut self) -> Option<SymbolTable> {
+        self.symbols.take()
+    }
+
+    /// Records a declaration. Returns the symbol id if symbols are recorded.
+    pub fn declare(&mut self, name: &str, kind: SymbolKind, span: Span, detail: String) -> Option<usize> {
+        let table = self.symbols.as_mut()?;
+        table.symbols.push(Symbol { name: name.to_owned(), kind, span, detail });
+        Some(table.symbols.len() - 1)
+    }
+
+    /// Records a reference to a symbol
+    pub fn reference(&mut self, symbol: Option<usize>, span: Span) {
+        if let (Some(table), Some(symbol)) = (self.symbols.as_mut(), symbol) {
+            table.references.push((symbol, span))
+        }
+    }
+
+    /// Records that a declaration shadows another variable
+    pub fn shadow(&mut self, symbol: Option<usize>, shadowed: Option<usize>) {
+        if let (Some(table), Some(symbol), Some(shadowed)) = (self.symbols.as_mut(), symbol, shadowed) {
+            table.shadowed.push((symbol, shadowed))
+        }
+    }
+
+    /// Associates a symbol with a variable of the current frame
+    pub fn bind_variable(&mut self, variable_name: &str, symbol: Option<usize>) {
+        if let Some(symbol) = symbol {
+            self.frames.last_mut().unwrap().symbols.insert(variable_name.to_owned(), symbol);
+        }
+    }
+
+    pub fn variable_symbol(&self, variable_name: &str) -> Option<usize> {
+        self.variable_frame(variable_name).and_then(|frame| frame.symbols.get(variable_name).copied())
+    }
+
+    pub fn bind_class(&mut self, class_id: usize, symbol: Option<usize>) {
+        if let (Some(table), Some(symbol)) = (self.symbols.as_mut(), symbol) {
+            table.classes.insert(class_id, symbol);
+        }
+    }
+
+    pub fn class_symbol(&self, class_id: usize) -> Option<usize> {
+        self.symbols.as_ref()?.classes.get(&class_id).copied()
+    }
+
+    /// Associates a symbol with a field or a method of a class
+    pub fn bind_member(&mut self, class_id: usize, member_name: &str, symbol: Option<usize>) {
+        if let (Some(table), Some(symbol)) = (self.symbols.as_mut(), symbol) {
+            table.members.insert((class_id, member_name.to_owned()), symbol);
+        }
+    }
+
+    pub fn member_symbol(&self, class_id: usize, member_name: &str) -> Option<usize> {
+        self.symbols.as_ref()?.members.get(&(class_id, member_name.to_owned())).copied()
+    }
 }
@@ -1,11 +1,55 @@
 use thiserror::Error;
 
+use super::checker::StaticType;
+
 #[derive(Error, Debug)]
 pub enum CompilerError {
     #[error("Unknown identifier '{0}'")]
     UnknownIdentifier(String),
+    #[error("Module {0} has no member '{1}'")]
+    UnknownNamespaceMember(String, String),
     #[error("Class {0} is already declared in this module")]
     ClassNameAlreadyDeclared(String),
+    #[error("Class {0} declares member '{1}' more than once")]
+    DuplicateClassMember(String, String),
+    #[error("Class {0} has no field '{1}', fields must be declared in the class body")]
+    UndeclaredField(String, String),
+    #[error("Unknown trait '{0}'")]
+    UnknownTrait(String),
+    #[error("Trait {0} declares method '{1}' more than once")]
+    DuplicateTraitMethod(String, String),
+    #[error("Class {0} must implement method '{2}' of trait {1}")]
+    MissingTraitMethod(String, String, String),
+    #[error("Method '{2}' of class {0} must accept {3} argument(s) to implement trait {1}")]
+    TraitMethodArity(String, String, String, usize),
+    #[error("Method '{2}' of class {0} cannot be private to implement trait {1}")]
+    PrivateTraitMethod(String, String, String),
+    #[error("'yield' outside function")]
+    YieldOutsideFunction,
+    #[error("'yield' can only be used as a statement")]
+    YieldInExpression,
     #[error("Invalid left expression")]
     InvalidLeftSideAssignment,
+    #[error("Duplicate parameter '{0}' in function definition")]
+    DuplicateParameter(String),
+    #[error("Parameter '{0}' without a default value follows a parameter with a default value")]
+    RequiredParameterAfterDefault(String),
+    #[error("Variadic parameter '{0}' must be the last parameter")]
+    VariadicParameterNotLast(String),
+    #[error("Positional argument follows named argument")]
+    PositionalArgumentAfterNamed,
+    #[error("Named argument '{0}' is repeated")]
+    DuplicateNamedArgument(String),
+}
+
+#[derive(Error, Debug)]
+pub enum TypeError {
+    #[error("Unknown type '{0}'")]
+    UnknownType(String),
+    #[error("Cannot assign a value of type {2} to '{0}' of type {1}")]
+    MismatchedAssignment(String, StaticType, StaticType),
+    #[error("Argument '{0}' of '{1}' must be of type {2}, found {3}")]
+    MismatchedArgument(String, String, StaticType, StaticType),
+    #[error("Function '{0}' must return {1}, found {2}")]
+    MismatchedReturn(String, StaticType, StaticType),
 }
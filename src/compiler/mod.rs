@@ -1,4 +1,6 @@
 pub mod ast;
+pub mod checker;
+#[allow(clippy::module_inception)]
 pub mod compiler;
 pub mod context;
 pub mod error;
@@ -0,0 +1,320 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, StdinLock, StdoutLock};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Receiver};
+
+use serde_json::{json, Value as Json};
+
+use crate::intermediate::exp::Exp;
+use crate::lsp::{read_message, write_message};
+use crate::runtime::debugger::{Debugger, Frame};
+use crate::runtime::executor::ExpressionError;
+use crate::runtime::module::Module;
+use crate::ProgramError;
+
+use super::{evaluate_in_frame, locals, run, Program, Reason, Resume, Stepper};
+
+/// The only thread reported to the client, as generators run on the thread evaluating the program
+const THREAD_ID: usize = 1;
+
+/// Runs a debug adapter, speaking the Debug Adapter Protocol over stdin and stdout.
+/// The program is evaluated on the thread reading the requests, so requests are handled
+/// only before the program starts and while it is paused. Returns the exit status of the process.
+pub fn run_dap() -> i32 {
+    let adapter = Rc::new(RefCell::new(Adapter {
+        reader: io::stdin().lock(),
+        writer: io::stdout().lock(),
+        seq: 0,
+        program: None,
+        requested: Vec::new(),
+        stepper: Stepper::new(false),
+        output: None,
+        disconnected: false,
+    }));
+    // Compiled program with its arguments, evaluated when the client is done with the configuration
+    let mut launched: Option<(Exp, Vec<String>)> = None;
+    let mut configured = false;
+    loop {
+        {
+            let mut adapter = adapter.borrow_mut();
+            let request = match adapter.read() {
+                Some(request) => request,
+                None => return 1,
+            };
+            let arguments = &request["arguments"];
+            match request["command"].as_str().unwrap_or_default() {
+                "initialize" => adapter.respond(&request, Ok(json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsEvaluateForHovers": true,
+                }))),
+                "launch" => match Program::load(arguments["program"].as_str().unwrap_or_default()) {
+                    Ok((program, exp)) => {
+                        let args = arguments["args"].as_array().map_or(Vec::new(), |args| {
+                            args.iter().filter_map(Json::as_str).map(str::to_owned).collect()
+                        });
+                        launched = Some((exp, args));
+                        adapter.stepper = Stepper::new(arguments["stopOnEntry"].as_bool().unwrap_or(false));
+                        adapter.program = Some(program);
+                        adapter.resolve_breakpoints();
+                        adapter.respond(&request, Ok(Json::Null));
+                        // Breakpoints can be verified once the program is loaded
+                        adapter.event("initialized", Json::Null);
+                    },
+                    Err(e) => adapter.respond(&request, Err(e)),
+                },
+                "configurationDone" => {
+                    configured = true;
+                    adapter.respond(&request, Ok(Json::Null));
+                },
+                "disconnect" | "terminate" => {
+                    adapter.respond(&request, Ok(Json::Null));
+                    return 0
+                },
+                _ => adapter.handle(&request, &[], None),
+            }
+        }
+        // The program starts once it is launched and the client is done with the configuration
+        let program = if configured { launched.take() } else { None };
+        if let Some((exp, args)) = program {
+            let (sender, receiver) = channel();
+            adapter.borrow_mut().output = Some(receiver);
+            let debugger = DapDebugger { adapter: adapter.clone() };
+            let result = run(&exp, Box::new(debugger), args, Some(sender));
+
+            let mut adapter = adapter.borrow_mut();
+            adapter.flush_output();
+            let exit_code = match result {
+                Ok(_) => 0,
+                Err(e) => {
+                    if !matches!(e, ProgramError::RuntimeError(ExpressionError::Exit(_))) {
+                        adapter.event("output", json!({ "category": "stderr", "output": format!("{}\n", e) }));
+                    }
+                    e.exit_code()
+                },
+            };
+            adapter.event("exited", json!({ "exitCode": exit_code }));
+            adapter.event("terminated", Json::Null);
+            if adapter.disconnected {
+                return 0
+            }
+        }
+    }
+}
+
+struct Adapter {
+    reader: StdinLock<'static>,
+    writer: StdoutLock<'static>,
+    seq: u64,
+    program: Option<Program>,
+    /// Breakpoint lines requested by the client, resolved when the program is loaded
+    requested: Vec<usize>,
+    stepper: Stepper,
+    /// Text printed by the program, forwarded to the client as output events
+    output: Option<Receiver<String>>,
+    /// Set when the client disconnects while the program is paused
+    disconnected: bool,
+}
+
+impl Adapter {
+    fn read(&mut self) -> Option<Json> {
+        match read_message(&mut self.reader) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("Debug adapter error: {}", e);
+                None
+            },
+        }
+    }
+
+    fn send(&mut self, mut message: Json) {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        if let Err(e) = write_message(&mut self.writer, &message) {
+            eprintln!("Debug adapter error: {}", e);
+        }
+    }
+
+    fn respond(&mut self, request: &Json, result: Result<Json, String>) {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Json) {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn flush_output(&mut self) {
+        let text: String = self.output.as_ref().map_or(String::new(), |output| output.try_iter().collect());
+        if !text.is_empty() {
+            self.event("output", json!({ "category": "stdout", "output": text }))
+        }
+    }
+
+    /// Sets the breakpoints requested by the client, returns the verified breakpoints
+    fn resolve_breakpoints(&mut self) -> Vec<Json> {
+        self.stepper.clear_breakpoints();
+        let mut breakpoints = Vec::with_capacity(self.requested.len());
+        for line in &self.requested {
+            match self.program.as_ref().and_then(|program| program.resolve_line(*line)) {
+                Some((line, offset)) => {
+                    self.stepper.set_breakpoint(offset);
+                    breakpoints.push(json!({ "verified": true, "line": line }))
+                },
+                None => breakpoints.push(json!({ "verified": false, "line": line })),
+            }
+        }
+        breakpoints
+    }
+
+    /// Handles the requests that do not resume the evaluation.
+    /// Frames and module are present while the program is paused.
+    fn handle(&mut self, request: &Json, frames: &[Frame], module: Option<&mut Module>) {
+        let arguments = &request["arguments"];
+        let result = match request["command"].as_str().unwrap_or_default() {
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "setBreakpoints" => {
+                let path = arguments["source"]["path"].as_str().unwrap_or_default();
+                let lines = arguments["breakpoints"].as_array().map_or(Vec::new(), |breakpoints| {
+                    breakpoints.iter().filter_map(|b| b["line"].as_u64()).map(|line| line as usize).collect()
+                });
+                // Only the launched program can be debugged
+                let is_program = self.program.as_ref().is_none_or(|program| same_file(&program.path, path));
+                if is_program {
+                    self.requested = lines;
+                    Ok(json!({ "breakpoints": self.resolve_breakpoints() }))
+                } else {
+                    Ok(json!({ "breakpoints": lines.iter().map(|line| json!({ "verified": false, "line": line })).collect::<Vec<_>>() }))
+                }
+            },
+            "stackTrace" => {
+                let program = self.program.as_ref();
+                let stack_frames: Vec<Json> = frames.iter().enumerate().rev().filter_map(|(id, frame)| {
+                    let (program, statement) = (program?, frame.statement.as_ref()?);
+                    let name = Path::new(&program.path).file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+                    Some(json!({
+                        "id": id,
+                        "name": statement.function.as_deref().unwrap_or("<module>"),
+                        "line": program.line(statement.offset),
+                        "column": 1,
+                        "source": { "name": name, "path": program.path },
+                    }))
+                }).collect();
+                Ok(json!({ "stackFrames": stack_frames, "totalFrames": stack_frames.len() }))
+            },
+            "scopes" => {
+                let id = arguments["frameId"].as_u64().unwrap_or_default();
+                // Variable references start from 1, as 0 means no variables
+                Ok(json!({ "scopes": [{ "name": "Locals", "variablesReference": id + 1, "expensive": false }] }))
+            },
+            "variables" => {
+                let frame = arguments["variablesReference"].as_u64().and_then(|id| frames.get((id as usize).wrapping_sub(1)));
+                let variables: Vec<Json> = match (frame, module) {
+                    (Some(frame), Some(module)) => locals(frame, module).into_iter().map(|(name, value)| {
+                        json!({ "name": name, "value": value, "variablesReference": 0 })
+                    }).collect(),
+                    _ => Vec::new(),
+                };
+                Ok(json!({ "variables": variables }))
+            },
+            "evaluate" => {
+                let frame = match arguments["frameId"].as_u64() {
+                    Some(id) => frames.get(id as usize),
+                    None => frames.last(),
+                };
+                match (frame, module) {
+                    (Some(frame), Some(module)) => {
+                        match evaluate_in_frame(arguments["expression"].as_str().unwrap_or_default(), frame, module) {
+                            Ok(v) => Ok(json!({ "result": v.as_ref().repr(), "variablesReference": 0 })),
+                            Err(e) => Err(e.to_string()),
+                        }
+                    },
+                    _ => Err("The program is not paused".to_owned()),
+                }
+            },
+            "pause" => Ok(Json::Null),
+            command => Err(format!("Unsupported request '{}'", command)),
+        };
+        self.respond(request, result)
+    }
+}
+
+fn same_file(a: &str, b: &str) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Pauses the program and handles the client requests until the evaluation is resumed
+struct DapDebugger {
+    adapter: Rc<RefCell<Adapter>>,
+}
+
+impl Debugger for DapDebugger {
+    fn statement(&mut self, frames: &[Frame], module: &mut Module) -> Result<(), ExpressionError> {
+        let mut adapter = self.adapter.borrow_mut();
+        adapter.flush_output();
+        let offset = match frames.last().and_then(|frame| frame.statement.as_ref()) {
+            Some(statement) => statement.offset,
+            None => return Ok(()),
+        };
+        let reason = match adapter.stepper.check(offset, frames.len()) {
+            Some(Reason::Entry) => "entry",
+            Some(Reason::Breakpoint) => "breakpoint",
+            Some(Reason::Step) => "step",
+            None => return Ok(()),
+        };
+        adapter.event("stopped", json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }));
+        loop {
+            // End of the input stops the program
+            let request = adapter.read().ok_or(ExpressionError::Exit(1))?;
+            let resume = match request["command"].as_str().unwrap_or_default() {
+                "continue" => Resume::Continue,
+                "next" => Resume::StepOver,
+                "stepIn" => Resume::StepInto,
+                "stepOut" => Resume::StepOut,
+                "disconnect" | "terminate" => {
+                    adapter.respond(&request, Ok(Json::Null));
+                    adapter.disconnected = true;
+                    return Err(ExpressionError::Exit(0))
+                },
+                _ => {
+                    adapter.handle(&request, frames, Some(module));
+                    continue
+                },
+            };
+            adapter.respond(&request, Ok(json!({ "allThreadsContinued": true })));
+            adapter.stepper.resume(resume, frames.len());
+            return Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_source_paths() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("epilang_dap_{}.epi", std::process::id()));
+        fs::write(&path, "1").unwrap();
+        let relative = dir.join(".").join(path.file_name().unwrap());
+        assert!(same_file(&path.display().to_string(), &relative.display().to_string()));
+        fs::remove_file(&path).unwrap();
+        // Paths that do not exist are compared as they are
+        assert!(same_file("missing.epi", "missing.epi"));
+        assert!(!same_file("missing.epi", "./missing.epi"));
+    }
+}
@@ -0,0 +1,475 @@
+mod dap;
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::Sender;
+
+use crate::compiler::checker::TypeChecker;
+use crate::compiler::compiler::{compile, compile_statement};
+use crate::compiler::context::CompilerContext;
+use crate::compiler::epilang::ASTParser;
+use crate::intermediate::exp::Exp;
+use crate::runtime::debugger::{DebugState, Debugger, Frame};
+use crate::runtime::executor::{evaluate, ExpressionError};
+use crate::runtime::module::Module;
+use crate::runtime::value::V;
+use crate::ProgramError;
+
+pub use dap::run_dap;
+
+/// Source of a program being debugged, with the positions where the evaluation can pause
+pub struct Program {
+    pub path: String,
+    text: String,
+    /// Start offset of each line
+    line_starts: Vec<usize>,
+    /// For each line with statements, the offset of its first statement
+    statement_lines: BTreeMap<usize, usize>,
+}
+
+impl Program {
+    /// Reads, checks and compiles a program file, marking its statements
+    pub fn load(path: &str) -> Result<(Program, Exp), String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Unable to read the program file {}: {}", path, e))?;
+        let ast = ASTParser::new().parse(&text).map_err(|e| ProgramError::SyntaxError(e.to_string()).to_string())?;
+        TypeChecker::new().check(&ast).map_err(|e| ProgramError::TypeError(e).to_string())?;
+        let mut ctx = CompilerContext::new();
+        ctx.mark_statements();
+        let exp = compile_statement(&ast, &mut ctx).map_err(|e| ProgramError::CompilerError(e).to_string())?;
+
+        let line_starts = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        let mut program = Program { path: path.to_owned(), text, line_starts, statement_lines: BTreeMap::new() };
+        for offset in ctx.take_statements().unwrap_or_default() {
+            let first = program.statement_lines.entry(program.line(offset)).or_insert(offset);
+            *first = offset.min(*first);
+        }
+        Ok((program, exp))
+    }
+
+    /// Line of an offset, starting from 1
+    pub fn line(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|start| *start <= offset)
+    }
+
+    pub fn line_text(&self, line: usize) -> &str {
+        self.text.lines().nth(line.wrapping_sub(1)).unwrap_or_default()
+    }
+
+    /// Moves a breakpoint to the first line with a statement at or after the given line.
+    /// Returns the line and the offset of its first statement.
+    pub fn resolve_line(&self, line: usize) -> Option<(usize, usize)> {
+        self.statement_lines.range(line..).next().map(|(line, offset)| (*line, *offset))
+    }
+}
+
+/// How the evaluation continues after a pause
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resume {
+    Continue,
+    /// Pauses at the next statement, entering called functions
+    StepInto,
+    /// Pauses at the next statement of the current function or of its callers
+    StepOver,
+    /// Pauses at the next statement after the current function returns
+    StepOut,
+}
+
+/// Why the evaluation paused
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+    Entry,
+    Breakpoint,
+    Step,
+}
+
+/// Decides before which statements the evaluation pauses
+pub struct Stepper {
+    /// Offsets of the first statement of the lines with a breakpoint
+    breakpoints: HashSet<usize>,
+    resume: Resume,
+    /// Call stack depth of the last pause
+    depth: usize,
+    started: bool,
+}
+
+impl Stepper {
+    pub fn new(stop_on_entry: bool) -> Stepper {
+        Stepper { breakpoints: HashSet::new(), resume: Resume::Continue, depth: 0, started: !stop_on_entry }
+    }
+
+    pub fn set_breakpoint(&mut self, offset: usize) {
+        self.breakpoints.insert(offset);
+    }
+
+    pub fn remove_breakpoint(&mut self, offset: usize) -> bool {
+        self.breakpoints.remove(&offset)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear()
+    }
+
+    /// Returns the reason to pause before a statement, if any
+    pub fn check(&mut self, offset: usize, depth: usize) -> Option<Reason> {
+        if !self.started {
+            self.started = true;
+            return Some(Reason::Entry)
+        }
+        if self.breakpoints.contains(&offset) {
+            return Some(Reason::Breakpoint)
+        }
+        let step = match self.resume {
+            Resume::Continue => false,
+            Resume::StepInto => true,
+            Resume::StepOver => depth <= self.depth,
+            Resume::StepOut => depth < self.depth,
+        };
+        if step { Some(Reason::Step) } else { None }
+    }
+
+    /// Continues the evaluation after a pause at the given call stack depth
+    pub fn resume(&mut self, resume: Resume, depth: usize) {
+        self.resume = resume;
+        self.depth = depth;
+    }
+}
+
+/// Variables in scope in a frame with their values, sorted by scope
+pub fn locals(frame: &Frame, module: &Module) -> Vec<(String, String)> {
+    let statement = match &frame.statement {
+        Some(statement) => statement,
+        None => return Vec::new(),
+    };
+    statement.locals.iter()
+        .filter_map(|(name, scope)| {
+            module.variables.get(frame.stack_start + scope).map(|value| (name.clone(), value.as_ref().repr()))
+        })
+        .collect()
+}
+
+/// Evaluates an expression in a paused frame, where the variables of the frame and the classes of the program are visible
+pub fn evaluate_in_frame(text: &str, frame: &Frame, module: &mut Module) -> Result<V, ProgramError> {
+    let ast = ASTParser::new().parse(text).map_err(|e| ProgramError::SyntaxError(e.to_string()))?;
+    let locals = frame.statement.as_ref().map_or(&[][..], |statement| &statement.locals[..]);
+    let classes: Vec<(String, usize)> = module.classes.iter().map(|(id, class)| (class.as_ref().name.clone(), *id)).collect();
+    let mut ctx = CompilerContext::from_frame(locals, &classes, module.variables.len() - frame.stack_start);
+    let exp = compile(&ast, &mut ctx).map_err(ProgramError::CompilerError)?;
+    // Variables declared by the expression are discarded
+    let scope = module.variables.len();
+    let result = evaluate(&exp, module, frame.stack_start);
+    module.variables.truncate(scope);
+    result.map_err(ProgramError::RuntimeError)
+}
+
+/// Evaluates a compiled program in a new module, pausing according to the debugger
+pub fn run(exp: &Exp, debugger: Box<dyn Debugger>, args: Vec<String>, output: Option<Sender<String>>) -> Result<V, ProgramError> {
+    let mut module = Module { args, debugger: Some(DebugState::new(debugger)), output, ..Default::default() };
+    evaluate(exp, &mut module, 0).map_err(ProgramError::RuntimeError)
+}
+
+const HELP: [(&str, &str); 10] = [
+    ("break [line]", "Sets a breakpoint, or lists the breakpoints"),
+    ("delete <line>", "Removes a breakpoint"),
+    ("continue", "Continues until the next breakpoint"),
+    ("step", "Steps to the next statement, entering function calls"),
+    ("next", "Steps to the next statement, over function calls"),
+    ("finish", "Continues until the current function returns"),
+    ("locals", "Lists the variables in scope with their values"),
+    ("print <expr>", "Evaluates an expression in the current frame"),
+    ("backtrace", "Shows the call stack"),
+    ("quit", "Stops the program"),
+];
+
+/// Debugs a program file from the command line.
+/// Expects the program file path followed by the program arguments, returns the process exit status.
+pub fn run_debug(args: &[String]) -> i32 {
+    let (path, program_args) = match args.split_first() {
+        Some((path, program_args)) if path != "--dap" => (path, program_args.to_vec()),
+        Some(_) => return run_dap(),
+        None => {
+            eprintln!("Usage: epilang debug path/to/file.epi [args...]");
+            return 1
+        },
+    };
+    let (program, exp) = match Program::load(path) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1
+        },
+    };
+    println!("Debugging {}, type help to list the commands", path);
+    let console = Console { program, stepper: Stepper::new(true), breakpoints: BTreeMap::new() };
+    match run(&exp, Box::new(console), program_args, None) {
+        Ok(v) => {
+            println!("Result: {}", v.as_ref());
+            0
+        },
+        Err(e) => {
+            if !matches!(e, ProgramError::RuntimeError(ExpressionError::Exit(_))) {
+                eprintln!("{}", e);
+            }
+            e.exit_code()
+        },
+    }
+}
+
+/// Command line debugger, reading commands from the standard input while paused
+struct Console {
+    program: Program,
+    stepper: Stepper,
+    /// Lines with a breakpoint, with the offset of their first statement
+    breakpoints: BTreeMap<usize, usize>,
+}
+
+impl Debugger for Console {
+    fn statement(&mut self, frames: &[Frame], module: &mut Module) -> Result<(), ExpressionError> {
+        let statement = match frames.last().and_then(|frame| frame.statement.as_ref()) {
+            Some(statement) => statement,
+            None => return Ok(()),
+        };
+        if self.stepper.check(statement.offset, frames.len()).is_none() {
+            return Ok(())
+        }
+        let line = self.program.line(statement.offset);
+        let function = statement.function.as_deref().unwrap_or("<module>");
+        println!("{}:{} in {}", self.program.path, line, function);
+        println!("{:>5} | {}", line, self.program.line_text(line));
+
+        let stdin = io::stdin();
+        loop {
+            print!("(debug) ");
+            io::stdout().flush().ok();
+            let mut input = String::new();
+            // End of the input stops the program
+            if stdin.lock().read_line(&mut input).unwrap_or(0) == 0 {
+                return Err(ExpressionError::Exit(1))
+            }
+            let input = input.trim();
+            let (command, arg) = input.split_once(char::is_whitespace).map_or((input, ""), |(c, arg)| (c, arg.trim()));
+            let resume = match command {
+                "" => continue,
+                "c" | "continue" => Resume::Continue,
+                "s" | "step" => Resume::StepInto,
+                "n" | "next" => Resume::StepOver,
+                "f" | "finish" => Resume::StepOut,
+                "q" | "quit" => return Err(ExpressionError::Exit(1)),
+                _ => {
+                    self.command(command, arg, frames, module);
+                    continue
+                },
+            };
+            self.stepper.resume(resume, frames.len());
+            return Ok(())
+        }
+    }
+}
+
+impl Console {
+    /// Runs a command that does not resume the evaluation
+    fn command(&mut self, command: &str, arg: &str, frames: &[Frame], module: &mut Module) {
+        match command {
+            "b" | "break" if arg.is_empty() => {
+                for line in self.breakpoints.keys() {
+                    println!("{}:{}", self.program.path, line);
+                }
+            },
+            "b" | "break" => match arg.parse().ok().and_then(|line| self.program.resolve_line(line)) {
+                Some((line, offset)) => {
+                    self.breakpoints.insert(line, offset);
+                    self.stepper.set_breakpoint(offset);
+                    println!("Breakpoint at {}:{}", self.program.path, line);
+                },
+                None => eprintln!("No statement at or after line {}", arg),
+            },
+            "d" | "delete" => match arg.parse().ok().and_then(|line| self.breakpoints.remove(&line)) {
+                Some(offset) => {
+                    self.stepper.remove_breakpoint(offset);
+                },
+                None => eprintln!("No breakpoint at line {}", arg),
+            },
+            "l" | "locals" => {
+                if let Some(frame) = frames.last() {
+                    for (name, value) in locals(frame, module) {
+                        println!("{} = {}", name, value);
+                    }
+                }
+            },
+            "p" | "print" if !arg.is_empty() => {
+                if let Some(frame) = frames.last() {
+                    match evaluate_in_frame(arg, frame, module) {
+                        Ok(v) => println!("{}", v.as_ref().repr()),
+                        Err(e) => eprintln!("{}", e),
+                    }
+                }
+            },
+            "p" | "print" => eprintln!("Usage: print <expr>"),
+            "bt" | "backtrace" => {
+                for (i, frame) in frames.iter().rev().enumerate() {
+                    if let Some(statement) = &frame.statement {
+                        let function = statement.function.as_deref().unwrap_or("<module>");
+                        println!("#{} {} at {}:{}", i, function, self.program.path, self.program.line(statement.offset));
+                    }
+                }
+            },
+            "h" | "help" => {
+                for (usage, description) in HELP.iter() {
+                    println!("{:<14} {}", usage, description);
+                }
+            },
+            _ => eprintln!("Unknown command '{}', type help to list the commands", command),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::{env, process};
+
+    use super::*;
+
+    const PROGRAM: &str = "fn double(n) {
+    let d = n * 2;
+    d
+};
+let a = 1;
+
+let b = double(a);
+b";
+
+    /// Pause seen by the test debugger: line, function, reason, locals and the value of `print`
+    type Pause = (usize, String, Reason, Vec<(String, String)>, Option<String>);
+
+    /// Resumes with the given commands in order, recording each pause
+    struct Recorder {
+        program: Program,
+        stepper: Stepper,
+        resumes: Vec<Resume>,
+        print: &'static str,
+        pauses: Rc<RefCell<Vec<Pause>>>,
+    }
+
+    impl Debugger for Recorder {
+        fn statement(&mut self, frames: &[Frame], module: &mut Module) -> Result<(), ExpressionError> {
+            let frame = frames.last().unwrap();
+            let statement = frame.statement.as_ref().unwrap();
+            let reason = match self.stepper.check(statement.offset, frames.len()) {
+                Some(reason) => reason,
+                None => return Ok(()),
+            };
+            let printed = evaluate_in_frame(self.print, frame, module).ok().map(|v| v.as_ref().repr());
+            let function = statement.function.clone().unwrap_or_default();
+            self.pauses.borrow_mut().push((self.program.line(statement.offset), function, reason, locals(frame, module), printed));
+            let resume = if self.resumes.is_empty() { Resume::Continue } else { self.resumes.remove(0) };
+            self.stepper.resume(resume, frames.len());
+            Ok(())
+        }
+    }
+
+    fn load(text: &str) -> Result<(Program, Exp), String> {
+        let path = env::temp_dir().join(format!("epilang_debug_{}_{}.epi", process::id(), text.len()));
+        fs::write(&path, text).unwrap();
+        let loaded = Program::load(&path.display().to_string());
+        fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    fn debug(breakpoints: &[usize], resumes: Vec<Resume>, print: &'static str) -> (String, Vec<Pause>) {
+        let (program, exp) = load(PROGRAM).unwrap();
+        let mut stepper = Stepper::new(breakpoints.is_empty());
+        for line in breakpoints {
+            stepper.set_breakpoint(program.resolve_line(*line).unwrap().1);
+        }
+        let pauses = Rc::new(RefCell::new(Vec::new()));
+        let recorder = Recorder { program, stepper, resumes, print, pauses: pauses.clone() };
+        let result = run(&exp, Box::new(recorder), Vec::new(), None).unwrap();
+        let pauses = pauses.borrow().clone();
+        (result.as_ref().repr(), pauses)
+    }
+
+    fn lines(pauses: &[Pause]) -> Vec<usize> {
+        pauses.iter().map(|pause| pause.0).collect()
+    }
+
+    #[test]
+    fn program_lines() {
+        let (program, _) = load(PROGRAM).unwrap();
+        assert_eq!(program.line(0), 1);
+        assert_eq!(program.line(PROGRAM.find("let a").unwrap()), 5);
+        assert_eq!(program.line_text(7), "let b = double(a);");
+        assert_eq!(program.line_text(20), "");
+        // Breakpoints on lines without statements move to the next statement
+        assert_eq!(program.resolve_line(6), Some((7, PROGRAM.find("b = double").unwrap())));
+        assert_eq!(program.resolve_line(4).map(|(line, _)| line), Some(5));
+        assert_eq!(program.resolve_line(9), None);
+    }
+
+    #[test]
+    fn load_errors() {
+        assert!(load("let x = ").err().unwrap().starts_with("SyntaxError"));
+        assert!(load("let x: Int = \"a\"").err().unwrap().starts_with("TypeError"));
+        assert!(load("undefined").err().unwrap().starts_with("CompilerError"));
+        assert!(Program::load("/nonexistent/file.epi").err().unwrap().starts_with("Unable to read"));
+    }
+
+    #[test]
+    fn stepper_decisions() {
+        let mut stepper = Stepper::new(true);
+        assert_eq!(stepper.check(0, 1), Some(Reason::Entry));
+        assert_eq!(stepper.check(5, 1), None);
+        stepper.set_breakpoint(10);
+        assert_eq!(stepper.check(10, 3), Some(Reason::Breakpoint));
+        stepper.resume(Resume::StepOver, 2);
+        assert_eq!(stepper.check(20, 3), None);
+        assert_eq!(stepper.check(20, 2), Some(Reason::Step));
+        stepper.resume(Resume::StepOut, 2);
+        assert_eq!(stepper.check(20, 2), None);
+        assert_eq!(stepper.check(20, 1), Some(Reason::Step));
+        stepper.resume(Resume::StepInto, 1);
+        assert_eq!(stepper.check(20, 2), Some(Reason::Step));
+        assert!(stepper.remove_breakpoint(10));
+        assert!(!stepper.remove_breakpoint(10));
+        stepper.set_breakpoint(30);
+        stepper.clear_breakpoints();
+        stepper.resume(Resume::Continue, 1);
+        assert_eq!(stepper.check(30, 1), None);
+    }
+
+    #[test]
+    fn steps_into_and_over_calls() {
+        let (result, pauses) = debug(&[], vec![Resume::StepOver; 10], "0");
+        assert_eq!(result, "2");
+        assert_eq!(lines(&pauses), vec![1, 5, 7, 8]);
+        assert_eq!(pauses[0].2, Reason::Entry);
+        assert_eq!(pauses[1].2, Reason::Step);
+
+        let (_, pauses) = debug(&[], vec![Resume::StepInto; 10], "0");
+        assert_eq!(lines(&pauses), vec![1, 5, 7, 2, 3, 8]);
+        assert_eq!(pauses[3].1, "double");
+
+        // Stepping out of the function pauses after the call returns
+        let (_, pauses) = debug(&[2], vec![Resume::StepOut], "0");
+        assert_eq!(lines(&pauses), vec![2, 8]);
+        assert_eq!(pauses[0].2, Reason::Breakpoint);
+    }
+
+    #[test]
+    fn inspects_paused_frames() {
+        let (_, pauses) = debug(&[3], vec![Resume::StepOut], "d + n");
+        let (line, function, _, locals, printed) = &pauses[0];
+        assert_eq!((*line, function.as_str()), (3, "double"));
+        // The function itself is in scope, for recursion
+        assert_eq!(locals.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), vec!["double", "n", "d"]);
+        assert_eq!(&locals[1..], &[("n".to_owned(), "1".to_owned()), ("d".to_owned(), "2".to_owned())]);
+        assert_eq!(printed.as_deref(), Some("3"));
+        // Variables of the function are not visible back in the module frame
+        assert_eq!(pauses[1].4, None);
+        let (_, pauses) = debug(&[8], Vec::new(), "a + b");
+        assert_eq!(pauses.len(), 1);
+        assert_eq!(pauses[0].4.as_deref(), Some("3"));
+        assert_eq!(&pauses[0].3[1..], &[("a".to_owned(), "1".to_owned()), ("b".to_owned(), "2".to_owned())]);
+    }
+}
@@ -0,0 +1,455 @@
+use std::fs;
+
+use serde_json::{json, Map, Value as Json};
+
+use crate::compiler::ast::{ArgAST, ClassAST, FieldAST, FunctionAST, MethodAST, ParamAST, Span, TypeAST, AST};
+use crate::compiler::compiler::compile;
+use crate::compiler::context::CompilerContext;
+use crate::compiler::epilang::ASTParser;
+use crate::intermediate::constant::Constant;
+use crate::intermediate::exp::{ClassExp, Exp, FunctionExp, ParamExp};
+use crate::ProgramError;
+
+/// Node of a dumped tree, printed either as indented text or as JSON.
+/// Each representation of a program is converted to nodes, so all of them share the two output formats.
+pub struct Node {
+    kind: &'static str,
+    attributes: Vec<(&'static str, Json)>,
+    children: Vec<(&'static str, Child)>,
+}
+
+enum Child {
+    One(Node),
+    Many(Vec<Node>),
+}
+
+impl Node {
+    fn new(kind: &'static str) -> Node {
+        Node { kind, attributes: Vec::new(), children: Vec::new() }
+    }
+
+    fn attr(mut self, name: &'static str, value: impl Into<Json>) -> Node {
+        self.attributes.push((name, value.into()));
+        self
+    }
+
+    fn child(mut self, label: &'static str, node: Node) -> Node {
+        self.children.push((label, Child::One(node)));
+        self
+    }
+
+    fn optional(self, label: &'static str, node: Option<Node>) -> Node {
+        match node {
+            Some(node) => self.child(label, node),
+            None => self,
+        }
+    }
+
+    fn children(mut self, label: &'static str, nodes: Vec<Node>) -> Node {
+        self.children.push((label, Child::Many(nodes)));
+        self
+    }
+
+    /// Indented text, one node per line with its attributes
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        self.write_text(&mut out, None, 0);
+        out
+    }
+
+    fn write_text(&self, out: &mut String, label: Option<String>, depth: usize) {
+        out.push_str(&"  ".repeat(depth));
+        if let Some(label) = label {
+            out.push_str(&label);
+            out.push_str(": ");
+        }
+        out.push_str(self.kind);
+        for (name, value) in &self.attributes {
+            out.push_str(&format!(" {}={}", name, value));
+        }
+        out.push('\n');
+        for (label, child) in &self.children {
+            match child {
+                Child::One(node) => node.write_text(out, Some(label.to_string()), depth + 1),
+                Child::Many(nodes) => for (i, node) in nodes.iter().enumerate() {
+                    node.write_text(out, Some(format!("{}[{}]", label, i)), depth + 1)
+                },
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> Json {
+        let mut map = Map::new();
+        map.insert("kind".to_owned(), json!(self.kind));
+        for (name, value) in &self.attributes {
+            map.insert(name.to_string(), value.clone());
+        }
+        for (label, child) in &self.children {
+            let value = match child {
+                Child::One(node) => node.to_json(),
+                Child::Many(nodes) => Json::Array(nodes.iter().map(Node::to_json).collect()),
+            };
+            map.insert(label.to_string(), value);
+        }
+        Json::Object(map)
+    }
+}
+
+/// Prints the syntax tree and/or the compiled expression of a program file without evaluating it.
+/// Expects the `--dump-ast`, `--dump-exp` and `--emit=json` options followed by the file path.
+/// Returns the process exit status.
+pub fn run_dump(args: &[String]) -> i32 {
+    let (options, paths): (Vec<&String>, Vec<&String>) = args.iter().partition(|arg| arg.starts_with("--"));
+    let (mut dump_ast, mut dump_exp, mut emit_json) = (false, false, false);
+    for option in options {
+        match option.as_str() {
+            "--dump-ast" => dump_ast = true,
+            "--dump-exp" => dump_exp = true,
+            "--emit=json" => emit_json = true,
+            "--emit=text" => emit_json = false,
+            option => {
+                eprintln!("Unknown option {}", option);
+                return 1
+            },
+        }
+    }
+    let path = match paths.as_slice() {
+        // The output format alone does not select anything to print
+        [path] if dump_ast || dump_exp => path,
+        _ => {
+            eprintln!("Usage: epilang --dump-ast|--dump-exp [--emit=json] path/to/file.epi");
+            return 1
+        },
+    };
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Unable to read the program file {}: {}", path, e);
+            return 1
+        },
+    };
+
+    let ast = match ASTParser::new().parse(&text) {
+        Ok(ast) => ast,
+        Err(e) => {
+            eprintln!("{}", ProgramError::SyntaxError(e.to_string()));
+            return 1
+        },
+    };
+    let mut dumps = Vec::new();
+    if dump_ast {
+        dumps.push(("ast", ast_tree(&ast)));
+    }
+    if dump_exp {
+        match compile(&ast, &mut CompilerContext::new()) {
+            Ok(exp) => dumps.push(("exp", exp_tree(&exp))),
+            Err(e) => {
+                eprintln!("{}", ProgramError::CompilerError(e));
+                return 1
+            },
+        }
+    }
+
+    if emit_json {
+        let map: Map<String, Json> = dumps.iter().map(|(name, node)| (name.to_string(), node.to_json())).collect();
+        println!("{}", serde_json::to_string_pretty(&map).unwrap_or_default());
+    } else {
+        for (_, node) in dumps {
+            print!("{}", node.to_text());
+        }
+    }
+    0
+}
+
+fn constant(node: Node, value: &Constant) -> Node {
+    match value {
+        Constant::Unit => node.attr("type", "Unit"),
+        Constant::Int(i) => node.attr("type", "Int").attr("value", *i),
+        Constant::Float(f) => node.attr("type", "Float").attr("value", *f),
+        Constant::String(s) => node.attr("type", "String").attr("value", s.as_str()),
+        Constant::Bool(b) => node.attr("type", "Bool").attr("value", *b),
+        Constant::Done => node.attr("type", "Done"),
+    }
+}
+
+/// Tree of the expression produced by the compiler, with the resolved variable
+/// scopes, class ids and the variables captured by functions
+pub fn exp_tree(exp: &Exp) -> Node {
+    match exp {
+        Exp::Constant { value } => constant(Node::new("Constant"), value),
+        Exp::Variable { scope } => Node::new("Variable").attr("scope", *scope),
+        Exp::Concatenation { first, second } => Node::new("Concatenation")
+            .child("first", exp_tree(first))
+            .child("second", exp_tree(second)),
+        Exp::BinaryOp { op, arg1, arg2 } => Node::new("BinaryOp")
+            .attr("op", op.to_string())
+            .child("arg1", exp_tree(arg1))
+            .child("arg2", exp_tree(arg2)),
+        Exp::UnaryOp { op, arg } => Node::new("UnaryOp").attr("op", op.to_string()).child("arg", exp_tree(arg)),
+        Exp::Let { scope } => Node::new("Let").attr("scope", *scope),
+        Exp::Assignment { left, right } => Node::new("Assignment")
+            .child("left", exp_tree(left))
+            .child("right", exp_tree(right)),
+        Exp::Block { exp } => Node::new("Block").child("exp", exp_tree(exp)),
+        Exp::Condition { exp, then_block, else_block } => Node::new("Condition")
+            .child("exp", exp_tree(exp))
+            .child("then_block", exp_tree(then_block))
+            .child("else_block", exp_tree(else_block)),
+        Exp::While { guard, exp } => Node::new("While").child("guard", exp_tree(guard)).child("exp", exp_tree(exp)),
+        Exp::For { iterable, exp } => Node::new("For").child("iterable", exp_tree(iterable)).child("exp", exp_tree(exp)),
+        Exp::List { elements } => Node::new("List").children("elements", elements.iter().map(exp_tree).collect()),
+        Exp::Subscript { element, index } => Node::new("Subscript")
+            .child("element", exp_tree(element))
+            .child("index", exp_tree(index)),
+        Exp::Slice { element, start, end, step } => Node::new("Slice")
+            .child("element", exp_tree(element))
+            .optional("start", start.as_deref().map(exp_tree))
+            .optional("end", end.as_deref().map(exp_tree))
+            .optional("step", step.as_deref().map(exp_tree)),
+        Exp::Function(function) => function_exp(Node::new("Function"), function),
+        Exp::Closure(function) => function_exp(Node::new("Closure"), function),
+        Exp::BuiltInFunction(function) => Node::new("BuiltInFunction").attr("name", format!("{:?}", function)),
+        Exp::FunctionCall { fun, args, named_args, offset } => Node::new("FunctionCall")
+            .attr("offset", *offset)
+            .child("fun", exp_tree(fun))
+            .children("args", args.iter().map(exp_tree).collect())
+            .children("named_args", named_args.iter().map(|(name, exp)| {
+                Node::new("NamedArg").attr("name", name.as_str()).child("exp", exp_tree(exp))
+            }).collect()),
+        Exp::ClassDef(class) => class_exp(class),
+        Exp::Class { id } => Node::new("Class").attr("id", *id),
+        Exp::PropertyAccess { exp, property, from_class } => Node::new("PropertyAccess")
+            .attr("property", property.as_str())
+            .attr("from_class", *from_class)
+            .child("exp", exp_tree(exp)),
+        Exp::Implements { exp, trait_id } => Node::new("Implements").attr("trait_id", *trait_id).child("exp", exp_tree(exp)),
+        Exp::Yield { exp } => Node::new("Yield").child("exp", exp_tree(exp)),
+        Exp::Statement { info, exp } => Node::new("Statement")
+            .attr("offset", info.offset)
+            .attr("function", info.function.as_deref())
+            .attr("locals", info.locals.iter().map(|(name, scope)| (name.clone(), json!(scope))).collect::<Map<String, Json>>())
+            .child("exp", exp_tree(exp)),
+    }
+}
+
+fn function_exp(node: Node, function: &FunctionExp) -> Node {
+    node.attr("captures", function.external_vars.clone())
+        .attr("generator", function.is_generator)
+        .children("params", function.params.iter().map(param_exp).collect())
+        .child("body", exp_tree(&function.body))
+}
+
+fn param_exp(param: &ParamExp) -> Node {
+    Node::new("Param")
+        .attr("name", param.name.as_str())
+        .attr("variadic", param.variadic)
+        .optional("default", param.default.as_ref().map(exp_tree))
+}
+
+fn class_exp(class: &ClassExp) -> Node {
+    // Methods are stored in hash maps, they are sorted by name for a stable output
+    let methods = |methods: &std::collections::HashMap<String, FunctionExp>| {
+        let mut names: Vec<&String> = methods.keys().collect();
+        names.sort();
+        names.into_iter().map(|name| function_exp(Node::new("Method").attr("name", name.as_str()), &methods[name])).collect()
+    };
+    let mut private_members: Vec<&str> = class.private_members.iter().map(String::as_str).collect();
+    private_members.sort_unstable();
+    Node::new("ClassDef")
+        .attr("id", class.id)
+        .attr("name", class.name.as_str())
+        .attr("traits", class.traits.clone())
+        .attr("private", private_members)
+        .children("fields", class.fields.iter().map(|field| {
+            Node::new("Field").attr("name", field.name.as_str()).optional("value", field.value.as_ref().map(exp_tree))
+        }).collect())
+        .child("constructor", function_exp(Node::new("Constructor"), &class.constructor))
+        .children("methods", methods(&class.methods))
+        .children("static_fields", class.static_fields.iter().map(|field| {
+            Node::new("StaticField").attr("name", field.name.as_str()).child("value", exp_tree(&field.value))
+        }).collect())
+        .children("static_methods", methods(&class.static_methods))
+}
+
+fn span(node: Node, span: &Span) -> Node {
+    node.attr("span", vec![span.start, span.end])
+}
+
+fn annotation(node: Node, annotation: &Option<TypeAST>) -> Node {
+    match annotation {
+        Some(annotation) => node.attr("type", annotation.name.as_str()),
+        None => node,
+    }
+}
+
+/// Tree of the syntax tree produced by the parser, with the spans of the named nodes
+pub fn ast_tree(ast: &AST) -> Node {
+    match ast {
+        AST::Constant(value) => constant(Node::new("Constant"), value),
+        AST::Identifier(name, s) => span(Node::new("Identifier").attr("name", name.as_str()), s),
+        AST::Concatenation { left, right } => Node::new("Concatenation")
+            .child("left", ast_tree(left))
+            .child("right", ast_tree(right)),
+        AST::BinaryOp(left, op, right) => Node::new("BinaryOp")
+            .attr("op", op.to_string())
+            .child("left", ast_tree(left))
+            .child("right", ast_tree(right)),
+        AST::UnaryOp(op, exp) => Node::new("UnaryOp").attr("op", op.to_string()).child("exp", ast_tree(exp)),
+        AST::Definition(name, s, ty) => annotation(span(Node::new("Definition").attr("name", name.as_str()), s), ty),
+        AST::Assignment(left, right) => Node::new("Assignment")
+            .child("left", ast_tree(left))
+            .child("right", ast_tree(right)),
+        AST::Block(exp) => Node::new("Block").child("exp", ast_tree(exp)),
+        AST::Condition { exp, then_block, else_block, span: s } => span(Node::new("Condition"), s)
+            .child("exp", ast_tree(exp))
+            .child("then_block", ast_tree(then_block))
+            .child("else_block", ast_tree(else_block)),
+        AST::While { guard, exp, span: s } => span(Node::new("While"), s)
+            .child("guard", ast_tree(guard))
+            .child("exp", ast_tree(exp)),
+        AST::For { var, span: s, iterable, exp } => span(Node::new("For").attr("var", var.as_str()), s)
+            .child("iterable", ast_tree(iterable))
+            .child("exp", ast_tree(exp)),
+        AST::List { elements } => Node::new("List").children("elements", elements.iter().map(ast_tree).collect()),
+        AST::Subscript { element, index } => Node::new("Subscript")
+            .child("element", ast_tree(element))
+            .child("index", ast_tree(index)),
+        AST::Slice { element, start, end, step } => Node::new("Slice")
+            .child("element", ast_tree(element))
+            .optional("start", start.as_deref().map(ast_tree))
+            .optional("end", end.as_deref().map(ast_tree))
+            .optional("step", step.as_deref().map(ast_tree)),
+        AST::Function(function) => function_ast(Node::new("Function"), function),
+        AST::Closure { params, exp } => Node::new("Closure")
+            .children("params", params.iter().map(param_ast).collect())
+            .child("exp", ast_tree(exp)),
+        AST::FunctionCall { fun, args } => Node::new("FunctionCall")
+            .child("fun", ast_tree(fun))
+            .children("args", args.iter().map(|arg| match arg {
+                ArgAST::Positional(exp) => Node::new("Positional").child("exp", ast_tree(exp)),
+                ArgAST::Named(name, exp) => Node::new("Named").attr("name", name.as_str()).child("exp", ast_tree(exp)),
+            }).collect()),
+        AST::Class(class) => class_ast(class),
+        AST::PropertyAccess { exp, property, span: s } => span(Node::new("PropertyAccess").attr("property", property.as_str()), s)
+            .child("exp", ast_tree(exp)),
+        AST::Trait(trait_ast) => span(Node::new("Trait").attr("name", trait_ast.name.as_str()), &trait_ast.span)
+            .children("methods", trait_ast.methods.iter().map(|method| {
+                span(Node::new("TraitMethod").attr("name", method.name.as_str()), &method.span)
+                    .children("params", method.params.iter().map(param_ast).collect())
+            }).collect()),
+        AST::Implements { exp, trait_name } => Node::new("Implements")
+            .attr("trait", trait_name.as_str())
+            .child("exp", ast_tree(exp)),
+        AST::Yield(exp) => Node::new("Yield").child("exp", ast_tree(exp)),
+        AST::Test { name, offset, body } => Node::new("Test")
+            .attr("name", name.as_str())
+            .attr("offset", *offset)
+            .child("body", ast_tree(body)),
+    }
+}
+
+fn function_ast(node: Node, function: &FunctionAST) -> Node {
+    let node = span(node.attr("name", function.name.as_str()), &function.span);
+    annotation(node, &function.return_type)
+        .children("params", function.params.iter().map(param_ast).collect())
+        .child("body", ast_tree(&function.body))
+}
+
+fn param_ast(param: &ParamAST) -> Node {
+    let node = span(Node::new("Param").attr("name", param.name.as_str()), &param.span);
+    annotation(node, &param.annotation)
+        .attr("variadic", param.variadic)
+        .optional("default", param.default.as_ref().map(ast_tree))
+}
+
+fn class_ast(class: &ClassAST) -> Node {
+    let field = |kind, field: &FieldAST| {
+        let node = span(Node::new(kind).attr("name", field.name.as_str()), &field.span);
+        annotation(node, &field.annotation)
+            .attr("public", field.public)
+            .optional("value", field.value.as_ref().map(ast_tree))
+    };
+    let method = |kind, method: &MethodAST| function_ast(Node::new(kind), &method.function).attr("public", method.public);
+    span(Node::new("Class").attr("name", class.name.as_str()), &class.span)
+        .attr("traits", class.traits.clone())
+        .children("fields", class.fields.iter().map(|f| field("Field", f)).collect())
+        .children("methods", class.methods.iter().map(|m| method("Method", m)).collect())
+        .children("static_fields", class.static_fields.iter().map(|f| field("StaticField", f)).collect())
+        .children("static_methods", class.static_methods.iter().map(|m| method("StaticMethod", m)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    fn parse(text: &str) -> AST {
+        ASTParser::new().parse(text).unwrap()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn ast_as_text() {
+        let expected = "\
+Concatenation
+  left: Assignment
+    left: Definition name=\"x\" span=[4,5]
+    right: BinaryOp op=\"+\"
+      left: Constant type=\"Int\" value=1
+      right: Constant type=\"Int\" value=2
+  right: Identifier name=\"x\" span=[15,16]
+";
+        assert_eq!(ast_tree(&parse("let x = 1 + 2; x")).to_text(), expected);
+    }
+
+    #[test]
+    fn exp_as_text_and_json() {
+        let exp = compile(&parse("let x = [1]; x"), &mut CompilerContext::new()).unwrap();
+        let expected = "\
+Concatenation
+  first: Concatenation
+    first: Let scope=0
+    second: Assignment
+      left: Variable scope=0
+      right: List
+        elements[0]: Constant type=\"Int\" value=1
+  second: Variable scope=0
+";
+        assert_eq!(exp_tree(&exp).to_text(), expected);
+        let json = exp_tree(&exp).to_json();
+        assert_eq!(json["kind"], "Concatenation");
+        assert_eq!(json["first"]["second"]["right"]["elements"], json!([{ "kind": "Constant", "type": "Int", "value": 1 }]));
+        assert_eq!(json["second"], json!({ "kind": "Variable", "scope": 0 }));
+    }
+
+    #[test]
+    fn exp_shows_captures_and_classes() {
+        let exp = compile(&parse("let a = 1; fn f(b) { b }; class C { }"), &mut CompilerContext::new()).unwrap();
+        let text = exp_tree(&exp).to_text();
+        assert!(text.contains("Function captures=[] generator=false"), "{}", text);
+        assert!(text.contains("ClassDef"), "{}", text);
+    }
+
+    #[test]
+    fn run_dump_exit_status() {
+        let path = env::temp_dir().join(format!("epilang_dump_{}.epi", process::id()));
+        let path_arg = path.display().to_string();
+        fs::write(&path, "let x = 1; x").unwrap();
+        assert_eq!(run_dump(&args(&["--dump-ast", "--dump-exp", &path_arg])), 0);
+        assert_eq!(run_dump(&args(&["--dump-exp", "--emit=json", &path_arg])), 0);
+        assert_eq!(run_dump(&args(&["--dump-bytecode", &path_arg])), 1);
+        assert_eq!(run_dump(&args(&["--dump-ast"])), 1);
+        assert_eq!(run_dump(&args(&["--emit=json", &path_arg])), 1);
+        fs::write(&path, "let x = ").unwrap();
+        assert_eq!(run_dump(&args(&["--dump-ast", &path_arg])), 1);
+        fs::write(&path, "undefined").unwrap();
+        assert_eq!(run_dump(&args(&["--dump-ast", &path_arg])), 0);
+        assert_eq!(run_dump(&args(&["--dump-exp", &path_arg])), 1);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(run_dump(&args(&["--dump-ast", &path_arg])), 1);
+    }
+}
@@ -0,0 +1,63 @@
+/// Comment in the source text, which the parser skips
+pub struct Comment {
+    pub start: usize,
+    pub end: usize,
+    /// True if the comment follows some code on the same line
+    pub trailing: bool,
+}
+
+/// Scans the source text for the comments, the positions of the matching curly braces and
+/// the positions of the semicolons, skipping string literals. Braces are sorted by the position
+/// of the opening one.
+pub fn scan(text: &str) -> (Vec<Comment>, Vec<(usize, usize)>, Vec<usize>) {
+    let bytes = text.as_bytes();
+    let mut comments = Vec::new();
+    let mut braces = Vec::new();
+    let mut semicolons = Vec::new();
+    let mut open = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (b'"', _) => {
+                i = text[i + 1..].find('"').map_or(text.len(), |j| i + j + 2);
+            },
+            (b'/', Some(b'/')) => {
+                let end = text[i..].find(['\n', '\r']).map_or(text.len(), |j| i + j);
+                comments.push(Comment { start: i, end, trailing: is_trailing(text, i) });
+                i = end;
+            },
+            (b'/', Some(b'*')) => {
+                let end = text[i + 2..].find("*/").map_or(text.len(), |j| i + j + 4);
+                comments.push(Comment { start: i, end, trailing: is_trailing(text, i) });
+                i = end;
+            },
+            (b'{', _) => {
+                open.push(i);
+                i += 1;
+            },
+            (b'}', _) => {
+                if let Some(start) = open.pop() {
+                    braces.push((start, i));
+                }
+                i += 1;
+            },
+            (b';', _) => {
+                semicolons.push(i);
+                i += 1;
+            },
+            _ => i += 1,
+        }
+    }
+    braces.sort_unstable();
+    (comments, braces, semicolons)
+}
+
+/// Returns the text of the comments, in source order
+pub fn comment_texts(text: &str) -> Vec<&str> {
+    scan(text).0.iter().map(|c| text[c.start..c.end].trim_end()).collect()
+}
+
+fn is_trailing(text: &str, offset: usize) -> bool {
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    !text[line_start..offset].trim().is_empty()
+}
@@ -0,0 +1,232 @@
+pub mod comments;
+pub mod printer;
+
+use std::fs;
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::compiler::ast::{ArgAST, ParamAST, Span, TypeAST, AST};
+use crate::compiler::epilang::ASTParser;
+use crate::test_runner::discover;
+
+use comments::comment_texts;
+use printer::Printer;
+
+#[derive(Error, Debug)]
+pub enum FormatError {
+    #[error("SyntaxError: {0}")]
+    SyntaxError(String),
+    #[error("formatting would change the program, the file is left unchanged")]
+    ChangedProgram,
+    #[error("formatting would lose some comments, the file is left unchanged")]
+    LostComments,
+}
+
+/// Formats the source files, or checks that they are formatted if check is true.
+/// Paths can be files or directories, where all the .epi files are formatted.
+/// Returns the process exit status, which is non-zero if some file could not be formatted
+/// or, in check mode, if some file is not formatted.
+pub fn run_fmt(args: &[String]) -> i32 {
+    let check = args.iter().any(|arg| arg == "--check");
+    let mut paths: Vec<&str> = args.iter().filter(|arg| *arg != "--check").map(String::as_str).collect();
+    if paths.is_empty() {
+        paths.push(".");
+    }
+
+    let mut status = 0;
+    for path in paths {
+        let files = match discover(Path::new(path)) {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("Unable to read {}: {}", path, e);
+                status = 1;
+                continue
+            }
+        };
+        for file in files {
+            let result = fs::read_to_string(&file).map_err(|e| e.to_string())
+                .and_then(|text| format_source(&text).map(|formatted| (text, formatted)).map_err(|e| e.to_string()));
+            match result {
+                Ok((text, formatted)) if formatted != text => {
+                    if check {
+                        println!("{} is not formatted", file.display());
+                        status = 1;
+                    } else if let Err(e) = fs::write(&file, formatted) {
+                        eprintln!("{}: {}", file.display(), e);
+                        status = 1;
+                    }
+                },
+                Ok(_) => (),
+                Err(e) => {
+                    eprintln!("{}: {}", file.display(), e);
+                    status = 1;
+                },
+            }
+        }
+    }
+    status
+}
+
+/// Formats a source text. The result is parsed again and compared with the original AST,
+/// so that formatting never changes the program.
+pub fn format_source(text: &str) -> Result<String, FormatError> {
+    let mut ast = ASTParser::new().parse(text).map_err(|e| FormatError::SyntaxError(e.to_string()))?;
+    let formatted = Printer::new(text).print(&ast);
+
+    let mut result = ASTParser::new().parse(&formatted).map_err(|_| FormatError::ChangedProgram)?;
+    erase_spans(&mut ast);
+    erase_spans(&mut result);
+    if ast != result {
+        return Err(FormatError::ChangedProgram)
+    }
+    if comment_texts(text) != comment_texts(&formatted) {
+        return Err(FormatError::LostComments)
+    }
+    Ok(formatted)
+}
+
+/// Resets the source positions in an AST, which are not relevant when comparing programs
+fn erase_spans(ast: &mut AST) {
+    fn params(params: &mut [ParamAST]) {
+        for param in params {
+            param.span = Span::default();
+            erase_annotation(&mut param.annotation);
+            if let Some(default) = &mut param.default {
+                erase_spans(default);
+            }
+        }
+    }
+    match ast {
+        AST::Constant(_) => (),
+        AST::Identifier(_, span) => *span = Span::default(),
+        AST::Definition(_, span, annotation) => {
+            *span = Span::default();
+            erase_annotation(annotation);
+        },
+        AST::Concatenation { left, right } | AST::BinaryOp(left, _, right) | AST::Assignment(left, right) => {
+            erase_spans(left);
+            erase_spans(right);
+        },
+        AST::UnaryOp(_, exp) | AST::Block(exp) | AST::Yield(exp) | AST::Implements { exp, .. } => erase_spans(exp),
+        AST::Condition { exp, then_block, else_block, span } => {
+            *span = Span::default();
+            erase_spans(exp);
+            erase_spans(then_block);
+            erase_spans(else_block);
+        },
+        AST::While { guard, exp, span } => {
+            *span = Span::default();
+            erase_spans(guard);
+            erase_spans(exp);
+        },
+        AST::For { span, iterable, exp, .. } => {
+            *span = Span::default();
+            erase_spans(iterable);
+            erase_spans(exp);
+        },
+        AST::List { elements } => elements.iter_mut().for_each(erase_spans),
+        AST::Subscript { element, index } => {
+            erase_spans(element);
+            erase_spans(index);
+        },
+        AST::Slice { element, start, end, step } => {
+            erase_spans(element);
+            for exp in start.iter_mut().chain(end.iter_mut()).chain(step.iter_mut()) {
+                erase_spans(exp);
+            }
+        },
+        AST::Function(function) => {
+            function.span = Span::default();
+            erase_annotation(&mut function.return_type);
+            params(&mut function.params);
+            erase_spans(&mut function.body);
+        },
+        AST::Closure { params: closure_params, exp } => {
+            params(closure_params);
+            erase_spans(exp);
+        },
+        AST::FunctionCall { fun, args } => {
+            erase_spans(fun);
+            for arg in args {
+                match arg {
+                    ArgAST::Positional(exp) | ArgAST::Named(_, exp) => erase_spans(exp),
+                }
+            }
+        },
+        AST::Class(class) => {
+            class.span = Span::default();
+            for field in class.fields.iter_mut().chain(class.static_fields.iter_mut()) {
+                field.span = Span::default();
+                erase_annotation(&mut field.annotation);
+                if let Some(value) = &mut field.value {
+                    erase_spans(value);
+                }
+            }
+            for method in class.methods.iter_mut().chain(class.static_methods.iter_mut()) {
+                method.function.span = Span::default();
+                erase_annotation(&mut method.function.return_type);
+                params(&mut method.function.params);
+                erase_spans(&mut method.function.body);
+            }
+        },
+        AST::PropertyAccess { exp, span, .. } => {
+            *span = Span::default();
+            erase_spans(exp);
+        },
+        AST::Trait(trait_ast) => {
+            trait_ast.span = Span::default();
+            for method in trait_ast.methods.iter_mut() {
+                method.span = Span::default();
+                params(&mut method.params);
+            }
+        },
+        AST::Test { offset, body, .. } => {
+            *offset = 0;
+            erase_spans(body);
+        },
+    }
+}
+
+fn erase_annotation(annotation: &mut Option<TypeAST>) {
+    if let Some(annotation) = annotation {
+        annotation.span = Span::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_source, FormatError};
+
+    /// Checks that a text is formatted as expected, and that formatting it again changes nothing
+    fn assert_formats(text: &str, expected: &str) {
+        let formatted = format_source(text).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn canonical_style() {
+        assert_formats("let x=1;let  y = [1,2 ,3];\nfn f(a,b:Int)->Int{a+b}", "let x = 1;\nlet y = [1, 2, 3];\nfn f(a, b: Int) -> Int { a + b }\n");
+        assert_formats("if x {1} else {2}", "if x { 1 } else { 2 }\n");
+        assert_formats("while i<3 { i = i+1; println(i) }", "while i < 3 {\n    i = i + 1;\n    println(i)\n}\n");
+        assert_formats("class P { let x private fn f() {1} static let n = 0 }", "class P {\n    let x\n    private fn f() { 1 }\n    static let n = 0\n}\n");
+        // Brackets are kept only where precedence requires them
+        assert_formats("(1 + 2) * ((3))", "(1 + 2) * 3\n");
+    }
+
+    #[test]
+    fn comments_are_kept_in_place() {
+        assert_formats("// header\nlet x = 1; // trailing\n\n\n/* block */\nx", "// header\nlet x = 1; // trailing\n\n/* block */\nx\n");
+        assert_formats("if c { // cond\n    1\n} else {\n    // nothing\n    2\n}", "if c { // cond\n    1\n} else {\n    // nothing\n    2\n}\n");
+        assert_formats("let a = 1;\n// before\n1 + 2;\n// last\n3", "let a = 1;\n// before\n1 + 2;\n// last\n3\n");
+        assert_formats("{\n    1;\n    // end\n}", "{\n    1;\n    // end\n}\n");
+        assert_formats("fn f() {\n    // first\n    \"a;b\";\n    // second\n    2\n}", "fn f() {\n    // first\n    \"a;b\";\n    // second\n    2\n}\n");
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(matches!(format_source("let = 1"), Err(FormatError::SyntaxError(_))));
+        assert!(matches!(format_source("class A { pub let x }"), Err(FormatError::SyntaxError(_))));
+    }
+}
@@ -0,0 +1,585 @@
+use crate::compiler::ast::{ArgAST, ClassAST, FieldAST, FunctionAST, MethodAST, ParamAST, TraitAST, TypeAST, AST};
+use crate::intermediate::constant::Constant;
+use crate::intermediate::opcode::BinaryOpcode;
+
+use super::comments::{scan, Comment};
+
+const INDENT: &str = "    ";
+const MAX_WIDTH: usize = 100;
+
+/// Position of the first node of a statement whose position is known
+enum Start {
+    /// Position of a name that comes before any curly brace
+    Name(usize),
+    /// The statement starts with a curly brace, or has one before any name
+    Brace,
+    Unknown,
+}
+
+/// Prints an AST back to source code, restoring the comments of the original text.
+/// Comments are emitted before the first statement or class member that follows them,
+/// or before the closing brace of the block that contains them.
+pub struct Printer<'a> {
+    text: &'a str,
+    comments: Vec<Comment>,
+    next_comment: usize,
+    braces: Vec<(usize, usize)>,
+    semicolons: Vec<usize>,
+    /// Source position reached by the printer, used to match the blocks with their braces
+    cursor: usize,
+    indent: usize,
+    /// True at the beginning of the file and of each block, where blank lines are not kept
+    fresh: bool,
+    out: String,
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(text: &'a str) -> Printer<'a> {
+        let (comments, braces, semicolons) = scan(text);
+        Printer { text, comments, next_comment: 0, braces, semicolons, cursor: 0, indent: 0, fresh: true, out: String::new() }
+    }
+
+    pub fn print(mut self, ast: &AST) -> String {
+        self.statements(ast, None);
+        self.flush(usize::MAX);
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out
+    }
+
+    /// Prints the statements of a file or block, each on its own line
+    fn statements(&mut self, ast: &AST, close: Option<usize>) {
+        let (statements, semicolon) = statements(ast);
+        let count = statements.len();
+        for (i, statement) in statements.into_iter().enumerate() {
+            let start = match start(statement) {
+                Start::Name(position) => Some(position),
+                Start::Brace => self.next_brace().map(|(open, _)| open),
+                Start::Unknown => self.unknown_start(i == 0),
+            };
+            if let Some(start) = start {
+                self.flush(start);
+            }
+            self.begin_line(start);
+            self.exp(statement, 1);
+            if i + 1 < count || semicolon {
+                self.out.push(';');
+            }
+        }
+        if let Some(close) = close {
+            self.flush(close);
+        }
+    }
+
+    /// Prints a block. Blocks with a single statement and no comments are kept on one line if they fit.
+    fn block(&mut self, ast: &AST) {
+        let brace = self.open_brace();
+        let has_comments = brace.is_some_and(|(open, close)| self.has_comments(open, close));
+        let (statements, semicolon) = statements(ast);
+        if statements.is_empty() && !has_comments {
+            self.out.push_str("{}");
+        } else if statements.len() == 1 && !semicolon && !has_comments && !contains_block(statements[0]) && self.inline(statements[0]) {
+            // Printed on one line
+        } else {
+            self.out.push('{');
+            self.indent += 1;
+            self.fresh = true;
+            self.statements(ast, brace.map(|(_, close)| close));
+            self.indent -= 1;
+            self.line();
+            self.out.push('}');
+        }
+        if let Some((_, close)) = brace {
+            self.cursor = self.cursor.max(close + 1);
+        }
+    }
+
+    /// Prints a single statement block on one line, returns false and prints nothing if it does not fit
+    fn inline(&mut self, statement: &AST) -> bool {
+        let (len, cursor) = (self.out.len(), self.cursor);
+        self.out.push_str("{ ");
+        self.exp(statement, 1);
+        self.out.push_str(" }");
+        let line = &self.out[self.out.rfind('\n').map_or(0, |i| i + 1)..];
+        if self.out[len..].contains('\n') || line.chars().count() > MAX_WIDTH {
+            self.out.truncate(len);
+            self.cursor = cursor;
+            return false
+        }
+        true
+    }
+
+    /// Prints an expression, adding round brackets if its precedence is lower than the minimum
+    fn exp(&mut self, ast: &AST, min_precedence: u8) {
+        let brackets = precedence(ast) < min_precedence;
+        if brackets {
+            self.out.push('(');
+        }
+        match ast {
+            AST::Constant(constant) => self.constant(constant),
+            AST::Identifier(name, span) => {
+                self.seen(span.start);
+                self.out.push_str(name);
+            },
+            AST::Concatenation { .. } => {
+                let (statements, semicolon) = statements(ast);
+                for (i, statement) in statements.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str("; ");
+                    }
+                    self.exp(statement, 1);
+                }
+                if semicolon {
+                    self.out.push(';');
+                }
+            },
+            AST::BinaryOp(left, op, right) => {
+                let precedence = precedence(ast);
+                self.exp(left, precedence);
+                self.out.push_str(&format!(" {} ", op));
+                self.exp(right, precedence + 1);
+            },
+            AST::UnaryOp(op, exp) => {
+                self.out.push_str(&op.to_string());
+                self.exp(exp, 8);
+            },
+            AST::Definition(name, span, annotation) => {
+                self.seen(span.start);
+                self.out.push_str("let ");
+                self.out.push_str(name);
+                self.annotation(annotation);
+            },
+            AST::Assignment(left, right) => {
+                self.exp(left, 1);
+                self.out.push_str(" = ");
+                self.exp(right, 2);
+            },
+            AST::Block(exp) => self.block(exp),
+            AST::Condition { exp, then_block, else_block, .. } => {
+                self.out.push_str("if ");
+                self.exp(exp, 1);
+                self.out.push(' ');
+                self.block(then_block);
+                match else_block.as_ref() {
+                    AST::Constant(Constant::Unit) => {
+                        // An empty else block is dropped, unless it contains comments
+                        if self.text[self.cursor.min(self.text.len())..].trim_start().starts_with("else") {
+                            match self.next_brace() {
+                                Some((open, close)) if self.has_comments(open, close) => {
+                                    self.out.push_str(" else ");
+                                    self.block(else_block);
+                                },
+                                Some((_, close)) => self.cursor = close + 1,
+                                None => (),
+                            }
+                        }
+                    },
+                    AST::Condition { .. } => {
+                        self.out.push_str(" else ");
+                        self.exp(else_block, 0);
+                    },
+                    _ => {
+                        self.out.push_str(" else ");
+                        self.block(else_block);
+                    },
+                }
+            },
+            AST::While { guard, exp, .. } => {
+                self.out.push_str("while ");
+                self.exp(guard, 1);
+                self.out.push(' ');
+                self.block(exp);
+            },
+            AST::For { var, span, iterable, exp } => {
+                self.seen(span.start);
+                self.out.push_str(&format!("for {} in ", var));
+                self.exp(iterable, 1);
+                self.out.push(' ');
+                self.block(exp);
+            },
+            AST::List { elements } => {
+                self.out.push('[');
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.exp(element, 2);
+                }
+                self.out.push(']');
+            },
+            AST::Subscript { element, index } => {
+                self.exp(element, 8);
+                self.out.push('[');
+                self.exp(index, 2);
+                self.out.push(']');
+            },
+            AST::Slice { element, start, end, step } => {
+                self.exp(element, 8);
+                self.out.push('[');
+                if let Some(start) = start {
+                    self.exp(start, 2);
+                }
+                self.out.push(':');
+                if let Some(end) = end {
+                    self.exp(end, 2);
+                }
+                if let Some(step) = step {
+                    self.out.push(':');
+                    self.exp(step, 2);
+                }
+                self.out.push(']');
+            },
+            AST::Function(function) => self.function(function),
+            AST::Closure { params, exp } => {
+                if params.is_empty() {
+                    self.out.push_str("||");
+                } else {
+                    self.out.push('|');
+                    self.params(params);
+                    self.out.push('|');
+                }
+                self.out.push(' ');
+                self.block(exp);
+            },
+            AST::FunctionCall { fun, args } => {
+                self.exp(fun, 8);
+                self.out.push('(');
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    match arg {
+                        ArgAST::Positional(exp) => self.exp(exp, 2),
+                        ArgAST::Named(name, exp) => {
+                            self.out.push_str(name);
+                            self.out.push_str(": ");
+                            self.exp(exp, 2);
+                        },
+                    }
+                }
+                self.out.push(')');
+            },
+            AST::Class(class) => self.class(class),
+            AST::PropertyAccess { exp, property, span } => {
+                self.exp(exp, 8);
+                self.seen(span.start);
+                self.out.push('.');
+                self.out.push_str(property);
+            },
+            AST::Trait(trait_ast) => self.trait_def(trait_ast),
+            AST::Implements { exp, trait_name } => {
+                self.exp(exp, 4);
+                self.out.push_str(" implements ");
+                self.out.push_str(trait_name);
+            },
+            AST::Yield(exp) => {
+                self.out.push_str("yield ");
+                self.exp(exp, 2);
+            },
+            AST::Test { name, offset, body } => {
+                self.seen(*offset);
+                self.out.push_str(&format!("test \"{}\" ", name));
+                self.block(body);
+            },
+        }
+        if brackets {
+            self.out.push(')');
+        }
+    }
+
+    fn constant(&mut self, constant: &Constant) {
+        match constant {
+            Constant::Unit => self.out.push_str("()"),
+            Constant::Float(f) => {
+                let mut float = f.to_string();
+                if !float.contains('.') {
+                    float.push_str(".0");
+                }
+                self.out.push_str(&float);
+            },
+            constant => self.out.push_str(&constant.to_string()),
+        }
+    }
+
+    fn function(&mut self, function: &FunctionAST) {
+        self.seen(function.span.start);
+        self.out.push_str(&format!("fn {}(", function.name));
+        self.params(&function.params);
+        self.out.push_str(") ");
+        if let Some(return_type) = &function.return_type {
+            self.out.push_str(&format!("-> {} ", return_type.name));
+        }
+        self.block(&function.body);
+    }
+
+    fn annotation(&mut self, annotation: &Option<TypeAST>) {
+        if let Some(annotation) = annotation {
+            self.out.push_str(": ");
+            self.out.push_str(&annotation.name);
+        }
+    }
+
+    fn params(&mut self, params: &[ParamAST]) {
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.seen(param.span.start);
+            if param.variadic {
+                self.out.push_str("...");
+            }
+            self.out.push_str(&param.name);
+            self.annotation(&param.annotation);
+            if let Some(default) = &param.default {
+                self.out.push_str(" = ");
+                self.exp(default, 2);
+            }
+        }
+    }
+
+    /// Prints a class with its members in source order, each on its own line
+    fn class(&mut self, class: &ClassAST) {
+        self.seen(class.span.start);
+        self.out.push_str(&format!("class {}", class.name));
+        if !class.traits.is_empty() {
+            self.out.push_str(&format!(" implements {}", class.traits.join(", ")));
+        }
+        self.out.push(' ');
+
+        let mut members: Vec<(usize, Member)> = Vec::new();
+        members.extend(class.fields.iter().map(|f| (f.span.start, Member::Field(f, false))));
+        members.extend(class.static_fields.iter().map(|f| (f.span.start, Member::Field(f, true))));
+        members.extend(class.methods.iter().map(|m| (m.function.span.start, Member::Method(m, false))));
+        members.extend(class.static_methods.iter().map(|m| (m.function.span.start, Member::Method(m, true))));
+        members.sort_by_key(|(start, _)| *start);
+
+        let brace = self.open_brace();
+        self.body(brace, members, |printer, member| {
+            let (public, is_static) = match member {
+                Member::Field(field, is_static) => (field.public, is_static),
+                Member::Method(method, is_static) => (method.public, is_static),
+            };
+            if !public {
+                printer.out.push_str("private ");
+            }
+            if is_static {
+                printer.out.push_str("static ");
+            }
+            match member {
+                Member::Field(field, _) => {
+                    printer.seen(field.span.start);
+                    printer.out.push_str("let ");
+                    printer.out.push_str(&field.name);
+                    printer.annotation(&field.annotation);
+                    if let Some(value) = &field.value {
+                        printer.out.push_str(" = ");
+                        printer.exp(value, 2);
+                    }
+                },
+                Member::Method(method, _) => printer.function(&method.function),
+            }
+        });
+    }
+
+    fn trait_def(&mut self, trait_ast: &TraitAST) {
+        self.seen(trait_ast.span.start);
+        self.out.push_str(&format!("trait {} ", trait_ast.name));
+        let brace = self.open_brace();
+        let methods = trait_ast.methods.iter().map(|m| (m.span.start, m)).collect();
+        self.body(brace, methods, |printer, method| {
+            printer.seen(method.span.start);
+            printer.out.push_str(&format!("fn {}(", method.name));
+            printer.params(&method.params);
+            printer.out.push_str(");");
+        });
+    }
+
+    /// Prints the body of a class or trait, with each item on its own line
+    fn body<T>(&mut self, brace: Option<(usize, usize)>, items: Vec<(usize, T)>, mut print: impl FnMut(&mut Self, T)) {
+        let has_comments = brace.is_some_and(|(open, close)| self.has_comments(open, close));
+        if items.is_empty() && !has_comments {
+            self.out.push_str("{}");
+        } else {
+            self.out.push('{');
+            self.indent += 1;
+            self.fresh = true;
+            for (start, item) in items {
+                self.flush(start);
+                self.begin_line(Some(start));
+                print(self, item);
+            }
+            if let Some((_, close)) = brace {
+                self.flush(close);
+            }
+            self.indent -= 1;
+            self.line();
+            self.out.push('}');
+        }
+        if let Some((_, close)) = brace {
+            self.cursor = self.cursor.max(close + 1);
+        }
+    }
+
+    /// Prints the comments that come before a source position
+    fn flush(&mut self, before: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment).filter(|c| c.start < before) {
+            let text = self.text[comment.start..comment.end].trim_end();
+            let last_line = &self.out[self.out.rfind('\n').map_or(0, |i| i + 1)..];
+            if comment.trailing && !last_line.trim().is_empty() {
+                self.out.push(' ');
+            } else {
+                self.begin_line(Some(comment.start));
+            }
+            self.out.push_str(text);
+            self.next_comment += 1;
+        }
+    }
+
+    /// Starts a new line, keeping one blank line if there is one before the position in the source
+    fn begin_line(&mut self, position: Option<usize>) {
+        if !self.fresh && position.is_some_and(|p| self.blank_line_before(p)) {
+            self.out.push('\n');
+        }
+        self.line();
+        self.fresh = false;
+    }
+
+    fn line(&mut self) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn blank_line_before(&self, position: usize) -> bool {
+        let line_start = self.text[..position].rfind('\n').map_or(0, |i| i + 1);
+        if line_start == 0 {
+            return false
+        }
+        let previous_start = self.text[..line_start - 1].rfind('\n').map_or(0, |i| i + 1);
+        self.text[previous_start..line_start].trim().is_empty()
+    }
+
+    fn has_comments(&self, open: usize, close: usize) -> bool {
+        self.comments[self.next_comment..].iter().any(|c| c.start > open && c.start < close)
+    }
+
+    fn seen(&mut self, position: usize) {
+        self.cursor = self.cursor.max(position);
+    }
+
+    /// Returns the position of a statement made of nodes without a position, such as constants.
+    /// It is the first code after the beginning of the block, or after the semicolon that ends
+    /// the previous statement.
+    fn unknown_start(&mut self, first: bool) -> Option<usize> {
+        let mut position = if first {
+            self.cursor
+        } else {
+            let i = self.semicolons.partition_point(|semicolon| *semicolon < self.cursor);
+            self.semicolons.get(i)? + 1
+        };
+        loop {
+            position += self.text[position..].len() - self.text[position..].trim_start().len();
+            match self.comments.iter().find(|c| c.start == position) {
+                Some(comment) => position = comment.end,
+                None => break,
+            }
+        }
+        self.seen(position);
+        Some(position)
+    }
+
+    /// Returns the braces of the next block in the source, without consuming them
+    fn next_brace(&self) -> Option<(usize, usize)> {
+        let i = self.braces.partition_point(|(open, _)| *open < self.cursor);
+        self.braces.get(i).copied()
+    }
+
+    /// Returns the braces of the next block in the source, moving past the opening one
+    fn open_brace(&mut self) -> Option<(usize, usize)> {
+        let brace = self.next_brace();
+        if let Some((open, _)) = brace {
+            self.cursor = open + 1;
+        }
+        brace
+    }
+}
+
+enum Member<'a> {
+    Field(&'a FieldAST, bool),
+    Method(&'a MethodAST, bool),
+}
+
+/// Returns the statements of a file or block, and true if the last one is followed by a semicolon.
+/// Only left nested concatenations are flattened, to preserve the shape of the AST.
+fn statements(ast: &AST) -> (Vec<&AST>, bool) {
+    fn collect<'a>(ast: &'a AST, statements: &mut Vec<&'a AST>) {
+        match ast {
+            AST::Concatenation { left, right } => {
+                collect(left, statements);
+                statements.push(right);
+            },
+            ast => statements.push(ast),
+        }
+    }
+    let mut statements = Vec::new();
+    collect(ast, &mut statements);
+    match statements.last() {
+        Some(AST::Constant(Constant::Unit)) if statements.len() > 1 => {
+            statements.pop();
+            (statements, true)
+        },
+        Some(AST::Constant(Constant::Unit)) => (Vec::new(), false),
+        _ => (statements, false),
+    }
+}
+
+/// Binding strength of an expression, as defined by the grammar rules
+fn precedence(ast: &AST) -> u8 {
+    match ast {
+        AST::Concatenation { .. } => 0,
+        AST::Assignment(..) | AST::Yield(_) | AST::Definition(_, _, Some(_)) => 1,
+        AST::BinaryOp(_, op, _) => match op {
+            BinaryOpcode::Or => 2,
+            BinaryOpcode::And => 3,
+            BinaryOpcode::Equals | BinaryOpcode::NotEquals | BinaryOpcode::Greater
+            | BinaryOpcode::GreaterEquals | BinaryOpcode::Lower | BinaryOpcode::LowerEquals => 4,
+            BinaryOpcode::Add | BinaryOpcode::Sub => 5,
+            BinaryOpcode::Mul | BinaryOpcode::Div => 6,
+        },
+        AST::Implements { .. } => 4,
+        AST::UnaryOp(..) => 7,
+        AST::FunctionCall { .. } | AST::Subscript { .. } | AST::Slice { .. } | AST::PropertyAccess { .. } => 8,
+        _ => 9,
+    }
+}
+
+/// Returns true if printing the AST opens some curly braces
+fn contains_block(ast: &AST) -> bool {
+    match ast {
+        AST::Block(_) | AST::Condition { .. } | AST::While { .. } | AST::For { .. } | AST::Function(_)
+        | AST::Closure { .. } | AST::Class(_) | AST::Trait(_) | AST::Test { .. } => true,
+        ast => ast.children().into_iter().any(contains_block),
+    }
+}
+
+fn start(ast: &AST) -> Start {
+    match ast {
+        AST::Identifier(_, span) | AST::Definition(_, span, _) | AST::For { span, .. } => Start::Name(span.start),
+        AST::Function(function) => Start::Name(function.span.start),
+        AST::Class(class) => Start::Name(class.span.start),
+        AST::Trait(trait_ast) => Start::Name(trait_ast.span.start),
+        AST::Test { offset, .. } => Start::Name(*offset),
+        AST::Closure { params, .. } => params.first().map_or(Start::Brace, |p| Start::Name(p.span.start)),
+        AST::Block(_) => Start::Brace,
+        AST::Condition { span, .. } | AST::While { span, .. } => Start::Name(span.start),
+        AST::PropertyAccess { exp, span, .. } => match start(exp) {
+            Start::Unknown => Start::Name(span.start),
+            start => start,
+        },
+        ast => ast.children().into_iter().map(start).find(|s| !matches!(s, Start::Unknown)).unwrap_or(Start::Unknown),
+    }
+}
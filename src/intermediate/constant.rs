@@ -8,10 +8,39 @@ pub enum Type {
     String,
     Bool,
     List,
+    Map,
     Function,
     Class,
     Object,
     Method,
+    Generator,
+    Iterator,
+    Regex,
+    Done,
+}
+
+impl Type {
+    /// Returns the type with the given name, as printed by type_of
+    pub fn from_name(name: &str) -> Option<Type> {
+        match name {
+            "Unit" => Some(Type::Unit),
+            "Int" => Some(Type::Int),
+            "Float" => Some(Type::Float),
+            "String" => Some(Type::String),
+            "Bool" => Some(Type::Bool),
+            "List" => Some(Type::List),
+            "Map" => Some(Type::Map),
+            "Function" => Some(Type::Function),
+            "Class" => Some(Type::Class),
+            "Object" => Some(Type::Object),
+            "Method" => Some(Type::Method),
+            "Generator" => Some(Type::Generator),
+            "Iterator" => Some(Type::Iterator),
+            "Regex" => Some(Type::Regex),
+            "Done" => Some(Type::Done),
+            _ => None,
+        }
+    }
 }
 
 impl Display for Type {
@@ -23,10 +52,15 @@ impl Display for Type {
             Type::String => write!(f, "String"),
             Type::Bool => write!(f, "Bool"),
             Type::List => write!(f, "List"),
+            Type::Map => write!(f, "Map"),
             Type::Function => write!(f, "Function"),
             Type::Class => write!(f, "Class"),
             Type::Object => write!(f, "Object"),
             Type::Method => write!(f, "Method"),
+            Type::Generator => write!(f, "Generator"),
+            Type::Iterator => write!(f, "Iterator"),
+            Type::Regex => write!(f, "Regex"),
+            Type::Done => write!(f, "Done"),
         }
     }
 }
@@ -38,6 +72,7 @@ pub enum Constant {
     Float(f32),
     String(String),
     Bool(bool),
+    Done,
 }
 
 impl Display for Constant {
@@ -48,6 +83,7 @@ impl Display for Constant {
             Constant::Float(v) => write!(f, "{}", v),
             Constant::String(v) => write!(f, "\"{}\"", v),
             Constant::Bool(v) => write!(f, "{}", v),
+            Constant::Done => write!(f, "done"),
         }
     }
 }
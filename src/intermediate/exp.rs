@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use super::constant::Constant;
 use super::opcode::BinaryOpcode;
@@ -16,15 +17,24 @@ pub enum Exp {
     Block { exp: Box<Exp> },
     Condition { exp: Box<Exp>, then_block: Box<Exp>, else_block: Box<Exp> },
     While { guard: Box<Exp>, exp: Box<Exp> },
+    /// Each value is pushed to the variable stack before evaluating the loop body
+    For { iterable: Box<Exp>, exp: Box<Exp> },
     List { elements: Vec<Exp> },
     Subscript { element: Box<Exp>, index: Box<Exp> },
+    Slice { element: Box<Exp>, start: Option<Box<Exp>>, end: Option<Box<Exp>>, step: Option<Box<Exp>> },
     Function(Box<FunctionExp>),
     BuiltInFunction(BuiltInFunction),
     Closure(Box<FunctionExp>),
-    FunctionCall { fun: Box<Exp>, args: Vec<Exp> },
+    /// Offset is the start of the call in the source text, where failed assertions are reported
+    FunctionCall { fun: Box<Exp>, args: Vec<Exp>, named_args: Vec<(String, Exp)>, offset: usize },
     ClassDef(Box<ClassExp>),
     Class{id: usize},
-    PropertyAccess { exp: Box<Exp>, property: String },
+    /// Private members are accessible only when `from_class` is the class that declares them
+    PropertyAccess { exp: Box<Exp>, property: String, from_class: Option<usize> },
+    Implements { exp: Box<Exp>, trait_id: usize },
+    Yield { exp: Box<Exp> },
+    /// Statement marked for the debugger, which may pause before evaluating it
+    Statement { info: Arc<StatementInfo>, exp: Box<Exp> },
 }
 
 impl Default for Exp {
@@ -33,23 +43,52 @@ impl Default for Exp {
     }
 }
 
+/// Position of a statement and variables in scope before it, recorded when compiling for the debugger
+#[derive(Debug)]
+pub struct StatementInfo {
+    /// Start offset of the statement in the source text
+    pub offset: usize,
+    /// Name of the enclosing function, None at the top level
+    pub function: Option<String>,
+    /// Variables in scope with their scopes relative to the function stack start, sorted by scope
+    pub locals: Vec<(String, usize)>,
+}
+
 #[derive(Clone, Debug)]
 pub struct FunctionExp {
-    pub num_args: usize,
+    pub params: Vec<ParamExp>,
     pub external_vars: Vec<usize>,
     pub body: Exp,
+    /// Functions containing `yield` return a generator when called
+    pub is_generator: bool,
 }
 
 impl FunctionExp {
     pub fn default_constructor() -> FunctionExp {
         FunctionExp {
-            num_args: 1,
+            params: vec![ParamExp::new("self".to_owned())],
             external_vars: Vec::default(),
             body: Exp::default(),
+            is_generator: false,
         }
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct ParamExp {
+    pub name: String,
+    /// Evaluated in the function scope when the argument is not provided
+    pub default: Option<Exp>,
+    /// Variadic parameters collect all the remaining positional arguments in a list
+    pub variadic: bool,
+}
+
+impl ParamExp {
+    pub fn new(name: String) -> ParamExp {
+        ParamExp { name, default: None, variadic: false }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ClassExp {
     pub id: usize,
@@ -57,11 +96,23 @@ pub struct ClassExp {
     pub fields: Vec<FieldExp>,
     pub constructor: FunctionExp,
     pub methods: HashMap<String, FunctionExp>,
+    pub static_fields: Vec<StaticFieldExp>,
+    pub static_methods: HashMap<String, FunctionExp>,
+    pub private_members: HashSet<String>,
+    pub traits: Vec<usize>,
 }
 
 #[derive(Clone, Debug)]
 pub struct FieldExp {
     pub name: String,
+    /// Initializer evaluated for each new object, with `self` as the only variable in scope
+    pub value: Option<Exp>,
+}
+
+#[derive(Clone, Debug)]
+pub struct StaticFieldExp {
+    pub name: String,
+    pub value: Exp,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -69,8 +120,94 @@ pub enum BuiltInFunction {
     Print,
     Println,
     Input,
+    Args,
+    Env,
+    Exit,
+    TypeOf,
+    ToInt,
+    ToFloat,
+    ToStr,
+    ToBool,
+    IsInstance,
+    Assert,
+    AssertEq,
     ListLength,
     ListPush,
     ListPop,
     ListRemove,
+    ListMap,
+    ListFilter,
+    ListReduce,
+    ListFind,
+    ListAny,
+    ListAll,
+    ListSort,
+    ListSortBy,
+    ListReverse,
+    ListIndexOf,
+    ListContains,
+    ListSlice,
+    ListJoin,
+    ListInsert,
+    ListExtend,
+    ListClear,
+    MapLength,
+    MapKeys,
+    MapValues,
+    MapContains,
+    MapGet,
+    MapRemove,
+    Iter,
+    IterNext,
+    IterMap,
+    IterFilter,
+    IterTake,
+    IterZip,
+    IterEnumerate,
+    IterCollect,
+    MathSqrt,
+    MathPow,
+    MathAbs,
+    MathFloor,
+    MathCeil,
+    MathRound,
+    MathMin,
+    MathMax,
+    MathSin,
+    MathCos,
+    MathTan,
+    MathAsin,
+    MathAcos,
+    MathAtan,
+    MathAtan2,
+    MathLog,
+    MathLog2,
+    MathLog10,
+    MathExp,
+    FsReadFile,
+    FsWriteFile,
+    FsAppendFile,
+    FsExists,
+    FsListDir,
+    FsMkdir,
+    FsRemove,
+    FsRemoveDirAll,
+    FsJoin,
+    JsonParse,
+    JsonStringify,
+    TimeNow,
+    TimeClock,
+    TimeSleep,
+    RandomSeed,
+    RandomInt,
+    RandomFloat,
+    RandomChoice,
+    RandomShuffle,
+    RegexCompile,
+    RegexIsMatch,
+    RegexFind,
+    RegexFindAll,
+    RegexCaptures,
+    RegexReplace,
+    RegexSplit,
 }
@@ -0,0 +1,204 @@
+pub mod rules;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::compiler::ast::Span;
+use crate::compiler::compiler::resolve_symbols;
+use crate::compiler::context::CompilerContext;
+use crate::compiler::epilang::ASTParser;
+use crate::formatter::comments::scan;
+use crate::lsp::analysis::parse_error_span;
+use crate::test_runner::discover;
+use crate::ProgramError;
+
+use rules::Linter;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rule {
+    UnusedVariable,
+    ShadowedVariable,
+    DeadAssignment,
+    UnreachableCode,
+    WrongArgumentCount,
+    UnknownMethod,
+    ConstantCondition,
+}
+
+impl Rule {
+    /// Name used in the output and in the suppression comments
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::UnusedVariable => "unused-variable",
+            Rule::ShadowedVariable => "shadowed-variable",
+            Rule::DeadAssignment => "dead-assignment",
+            Rule::UnreachableCode => "unreachable-code",
+            Rule::WrongArgumentCount => "wrong-argument-count",
+            Rule::UnknownMethod => "unknown-method",
+            Rule::ConstantCondition => "constant-condition",
+        }
+    }
+}
+
+pub struct Warning {
+    pub rule: Rule,
+    pub span: Span,
+    pub message: String,
+}
+
+/// Program that cannot be linted because it does not compile
+pub struct LintError {
+    pub span: Span,
+    pub error: ProgramError,
+}
+
+/// Lints the source files, paths can be files or directories, where all the .epi files are linted.
+/// Returns the process exit status, which is non-zero if some warning is found.
+pub fn run_lint(paths: &[String]) -> i32 {
+    let paths = if paths.is_empty() { vec![".".to_owned()] } else { paths.to_vec() };
+    let mut status = 0;
+    let mut count = 0;
+    for path in paths {
+        let files = match discover(Path::new(&path)) {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("Unable to read {}: {}", path, e);
+                status = 1;
+                continue
+            }
+        };
+        for file in files {
+            let text = match fs::read_to_string(&file) {
+                Ok(text) => text,
+                Err(e) => {
+                    eprintln!("Unable to read {}: {}", file.display(), e);
+                    status = 1;
+                    continue
+                }
+            };
+            match lint_source(&text) {
+                Ok(warnings) => {
+                    for warning in warnings.iter() {
+                        let (line, column) = position(&text, warning.span.start);
+                        println!("{}:{}:{}: warning[{}]: {}", file.display(), line, column, warning.rule.name(), warning.message);
+                    }
+                    count += warnings.len();
+                },
+                Err(e) => {
+                    let (line, column) = position(&text, e.span.start);
+                    println!("{}:{}:{}: error: {}", file.display(), line, column, e.error);
+                    status = 1;
+                },
+            }
+        }
+    }
+    if count > 0 {
+        println!("{} {}", count, if count == 1 { "warning" } else { "warnings" });
+        status = 1;
+    }
+    status
+}
+
+/// Returns the warnings in a source text, sorted by position, except the suppressed ones
+pub fn lint_source(text: &str) -> Result<Vec<Warning>, LintError> {
+    let ast = ASTParser::new().parse(text)
+        .map_err(|e| LintError { span: parse_error_span(text, &e), error: ProgramError::SyntaxError(e.to_string()) })?;
+    let mut ctx = CompilerContext::new();
+    ctx.record_symbols();
+    resolve_symbols(&ast, &mut ctx).map_err(|e| LintError { span: ctx.span(), error: ProgramError::CompilerError(e) })?;
+    let symbols = ctx.take_symbols().unwrap_or_default();
+
+    let mut warnings = Linter::new(text, &symbols).lint(&ast);
+    let suppressed = suppressions(text);
+    warnings.retain(|w| match suppressed.get(&position(text, w.span.start).0) {
+        Some(None) => false,
+        Some(Some(rules)) => !rules.iter().any(|r| r == w.rule.name()),
+        None => true,
+    });
+    Ok(warnings)
+}
+
+/// Returns the lines where warnings are suppressed, each with the suppressed rules or None for all the rules.
+/// A `// lint: allow` or `// lint: allow(rule, ...)` comment applies to its own line if it follows
+/// some code, otherwise to the next line.
+fn suppressions(text: &str) -> HashMap<usize, Option<Vec<String>>> {
+    let mut lines = HashMap::new();
+    for comment in scan(text).0 {
+        let content = &text[comment.start + 2..comment.end];
+        let content = content.strip_suffix("*/").unwrap_or(content).trim();
+        let rules = match content.strip_prefix("lint:").map(str::trim).and_then(|c| c.strip_prefix("allow")) {
+            Some(rules) => rules.trim(),
+            None => continue,
+        };
+        let rules = match rules.strip_prefix('(').and_then(|r| r.strip_suffix(')')) {
+            Some(rules) => Some(rules.split(',').map(|r| r.trim().to_owned()).collect()),
+            None if rules.is_empty() => None,
+            None => continue,
+        };
+        let line = if comment.trailing { position(text, comment.start).0 } else { position(text, comment.end).0 + 1 };
+        lines.insert(line, rules);
+    }
+    lines
+}
+
+/// Returns the line and column of an offset, starting from 1
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(text.len());
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    (text[..line_start].matches('\n').count() + 1, text[line_start..offset].chars().count() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint_source, position, Rule};
+
+    /// Returns the rules of the warnings in a text, with the line where they are reported
+    fn warnings(text: &str) -> Vec<(Rule, usize)> {
+        lint_source(text).unwrap_or_else(|e| panic!("{}", e.error)).iter()
+            .map(|w| (w.rule, position(text, w.span.start).0))
+            .collect()
+    }
+
+    #[test]
+    fn unused_and_shadowed_variables() {
+        assert_eq!(warnings("let x = 1;\nlet y = 2;\ny"), [(Rule::UnusedVariable, 1)]);
+        assert_eq!(warnings("fn f(a) {\n    let b = a;\n    1\n};\nf(1)"), [(Rule::UnusedVariable, 2)]);
+        assert_eq!(warnings("let x = 1;\nprintln(x);\nlet x = 2;\nx"), [(Rule::ShadowedVariable, 3)]);
+        // Variables used by tests are not reported
+        assert!(warnings("let x = 1;\ntest \"t\" { assert(x == 1) }").is_empty());
+    }
+
+    #[test]
+    fn dead_assignments_and_unreachable_code() {
+        assert_eq!(warnings("let x = 1;\nx = 2;\nx = 3;\nx"), [(Rule::DeadAssignment, 1), (Rule::DeadAssignment, 2)]);
+        assert!(warnings("let x = 1;\nwhile x < 3 { x = x + 1 };\nx").is_empty());
+        assert_eq!(warnings("fn f() {\n    exit(1);\n    println(2)\n};\nf()"), [(Rule::UnreachableCode, 3)]);
+        assert!(warnings("let ok = args().len() > 0;\nok || exit(1);\nprintln(\"continuing\")").is_empty());
+        assert_eq!(warnings("exit(1) && true;\nprintln(2)"), [(Rule::UnreachableCode, 2)]);
+    }
+
+    #[test]
+    fn calls_and_conditions() {
+        assert_eq!(warnings("fn f(a) { a };\nf(1, 2)"), [(Rule::WrongArgumentCount, 2)]);
+        assert!(warnings("fn f(a, b = 1, ...rest) { [a, b, rest] };\nf(1, 2, 3, 4)").is_empty());
+        assert_eq!(warnings("[1].lenght()"), [(Rule::UnknownMethod, 1)]);
+        assert_eq!(warnings("if true { 1 } else { 2 }"), [(Rule::ConstantCondition, 1)]);
+        assert_eq!(warnings("let x = 1;\nwhile 1 < 2 { x = x + 1 }"), [(Rule::ConstantCondition, 2)]);
+        assert!(warnings("let x = 1;\nif x > 0 { 1 } else { 2 }").is_empty());
+    }
+
+    #[test]
+    fn suppressions() {
+        assert!(warnings("let x = 1; // lint: allow\n2").is_empty());
+        assert!(warnings("// lint: allow(unused-variable)\nlet x = 1;\n2").is_empty());
+        assert_eq!(warnings("let x = 1; // lint: allow(dead-assignment)\n2"), [(Rule::UnusedVariable, 1)]);
+    }
+
+    #[test]
+    fn programs_that_do_not_compile() {
+        assert!(lint_source("let = 1").is_err());
+        let error = lint_source("let x = 1;\ny").err().unwrap();
+        assert_eq!(position("let x = 1;\ny", error.span.start), (2, 1));
+    }
+}
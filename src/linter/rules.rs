@@ -0,0 +1,423 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::compiler::ast::{ArgAST, FunctionAST, ParamAST, Span, AST};
+use crate::compiler::compiler::{BUILTIN_FUNCTIONS, NAMESPACES, NAMESPACE_FUNCTIONS};
+use crate::compiler::context::{SymbolKind, SymbolTable};
+use crate::intermediate::constant::Constant;
+use crate::intermediate::exp::BuiltInFunction;
+use crate::intermediate::opcode::BinaryOpcode;
+use crate::runtime::value::{LIST_METHODS, STRING_METHODS};
+
+use super::{Rule, Warning};
+
+/// Call to a function declared with `fn`, checked once all the assignments are known
+struct Call<'a> {
+    function: usize,
+    span: Span,
+    args: &'a [ArgAST],
+}
+
+/// Walks the AST of a compiled program, using the symbols resolved by the compiler
+pub struct Linter<'a> {
+    text: &'a str,
+    symbols: &'a SymbolTable,
+    /// Maps the position of each reference and declaration to its symbol
+    positions: HashMap<usize, usize>,
+    /// Number of times each symbol is read and assigned
+    reads: Vec<usize>,
+    writes: Vec<usize>,
+    /// Variables declared with let
+    definitions: Vec<usize>,
+    /// Variables used in test blocks, which run after the other statements
+    tested: HashSet<usize>,
+    functions: HashMap<usize, &'a FunctionAST>,
+    calls: Vec<Call<'a>>,
+    /// Assignments whose value is never read, reported unless the variable is never read at all
+    dead_assignments: Vec<(usize, Span)>,
+    warnings: Vec<Warning>,
+}
+
+impl<'a> Linter<'a> {
+    pub fn new(text: &'a str, symbols: &'a SymbolTable) -> Linter<'a> {
+        let mut positions: HashMap<usize, usize> = symbols.symbols.iter().enumerate().map(|(id, s)| (s.span.start, id)).collect();
+        positions.extend(symbols.references.iter().map(|(id, span)| (span.start, *id)));
+        Linter {
+            text,
+            symbols,
+            positions,
+            reads: vec![0; symbols.symbols.len()],
+            writes: vec![0; symbols.symbols.len()],
+            definitions: Vec::new(),
+            tested: HashSet::new(),
+            functions: HashMap::new(),
+            calls: Vec::new(),
+            dead_assignments: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Returns the warnings of a program, sorted by position
+    pub fn lint(mut self, ast: &'a AST) -> Vec<Warning> {
+        for statement in ast.statements() {
+            if let AST::Test { body, .. } = statement {
+                let mentioned = self.mentioned(body);
+                self.tested.extend(mentioned);
+            }
+        }
+        self.block(ast, Vec::new());
+
+        for &id in self.definitions.iter() {
+            if self.reads[id] == 0 {
+                let symbol = &self.symbols.symbols[id];
+                self.warnings.push(Warning {
+                    rule: Rule::UnusedVariable,
+                    span: symbol.span,
+                    message: format!("variable '{}' is never read", symbol.name),
+                });
+            }
+        }
+        for &(id, shadowed) in self.symbols.shadowed.iter() {
+            let (symbol, shadowed) = (&self.symbols.symbols[id], &self.symbols.symbols[shadowed]);
+            let line = self.text[..shadowed.span.start].matches('\n').count() + 1;
+            self.warnings.push(Warning {
+                rule: Rule::ShadowedVariable,
+                span: symbol.span,
+                message: format!("variable '{}' shadows the one declared on line {}", symbol.name, line),
+            });
+        }
+        for &(id, span) in self.dead_assignments.iter() {
+            if self.reads[id] > 0 {
+                self.warnings.push(Warning {
+                    rule: Rule::DeadAssignment,
+                    span,
+                    message: format!("value assigned to '{}' is never read", self.symbols.symbols[id].name),
+                });
+            }
+        }
+        for call in std::mem::take(&mut self.calls) {
+            // Variables holding a function may be reassigned to any other value
+            if self.writes[call.function] > 0 {
+                continue
+            }
+            if let Some(function) = self.functions.get(&call.function) {
+                if let Some(message) = check_arguments(function, call.args) {
+                    self.warnings.push(Warning { rule: Rule::WrongArgumentCount, span: call.span, message });
+                }
+            }
+        }
+        self.warnings.sort_by_key(|w| w.span.start);
+        self.warnings
+    }
+
+    /// Lints a sequence of statements, locals are the variables that cannot be read after it
+    fn block(&mut self, ast: &'a AST, mut locals: Vec<usize>) {
+        let statements = ast.statements();
+        let mentions: Vec<HashSet<usize>> = statements.iter().map(|s| self.mentioned(s)).collect();
+        for (i, statement) in statements.iter().enumerate() {
+            if let AST::Assignment(left, _) = statement {
+                if let AST::Definition(_, span, _) = left.as_ref() {
+                    locals.extend(self.symbol(*span));
+                }
+            }
+            if let Some((id, span)) = self.assigned(statement) {
+                self.check_assignment(id, span, &statements[i + 1..], &mentions[i + 1..], &locals);
+            }
+        }
+        if let Some(i) = statements.iter().position(|s| self.diverges(s)) {
+            let next = statements[i + 1..].iter().find(|s| !matches!(s, AST::Constant(Constant::Unit)));
+            if let Some(next) = next {
+                self.warnings.push(Warning {
+                    rule: Rule::UnreachableCode,
+                    span: next.first_span().unwrap_or_else(|| statements[i].first_span().unwrap_or_default()),
+                    message: "unreachable code".to_owned(),
+                });
+            }
+        }
+        for statement in statements {
+            self.exp(statement);
+        }
+    }
+
+    /// Checks that the value assigned by a statement is read by the following ones
+    fn check_assignment(&mut self, id: usize, span: Span, following: &[&AST], mentions: &[HashSet<usize>], locals: &[usize]) {
+        for (statement, mentioned) in following.iter().zip(mentions) {
+            if mentioned.contains(&id) {
+                let overwritten = match statement {
+                    AST::Assignment(_, right) => self.assigned(statement).is_some_and(|(other, _)| other == id)
+                        && !self.mentioned(right).contains(&id),
+                    _ => false,
+                };
+                if overwritten {
+                    self.dead_assignments.push((id, span));
+                }
+                return
+            }
+        }
+        if locals.contains(&id) && !self.tested.contains(&id) {
+            self.dead_assignments.push((id, span));
+        }
+    }
+
+    fn exp(&mut self, ast: &'a AST) {
+        match ast {
+            AST::Identifier(_, span) => {
+                if let Some(id) = self.symbol(*span) {
+                    self.reads[id] += 1;
+                }
+            },
+            AST::Definition(_, span, _) => self.definitions.extend(self.symbol(*span)),
+            AST::Assignment(left, right) => {
+                match left.as_ref() {
+                    AST::Identifier(_, span) => {
+                        if let Some(id) = self.symbol(*span) {
+                            self.writes[id] += 1;
+                        }
+                    },
+                    left => self.exp(left),
+                }
+                self.exp(right);
+            },
+            AST::Block(exp) => self.block(exp, Vec::new()),
+            AST::Condition { exp, then_block, else_block, span } => {
+                self.check_condition(exp, *span, "if");
+                self.exp(exp);
+                self.block(then_block, Vec::new());
+                self.block(else_block, Vec::new());
+            },
+            AST::While { guard, exp, span } => {
+                self.check_condition(guard, *span, "while");
+                self.exp(guard);
+                self.block(exp, Vec::new());
+            },
+            AST::For { span, iterable, exp, .. } => {
+                self.exp(iterable);
+                let var = self.symbol(*span).into_iter().collect();
+                self.block(exp, var);
+            },
+            AST::Function(function) => {
+                self.functions.extend(self.symbol(function.span).map(|id| (id, function.as_ref())));
+                self.function(&function.params, &function.body);
+            },
+            AST::Closure { params, exp } => self.function(params, exp),
+            AST::Class(class) => {
+                for field in class.fields.iter().chain(class.static_fields.iter()) {
+                    if let Some(value) = &field.value {
+                        self.exp(value);
+                    }
+                }
+                for method in class.methods.iter().chain(class.static_methods.iter()) {
+                    self.function(&method.function.params, &method.function.body);
+                }
+            },
+            AST::FunctionCall { fun, args } => {
+                self.check_call(fun, args);
+                ast.children().into_iter().for_each(|exp| self.exp(exp));
+            },
+            AST::PropertyAccess { exp, property, span } => {
+                self.check_method(exp, property, *span);
+                self.exp(exp);
+            },
+            AST::Test { body, .. } => self.block(body, Vec::new()),
+            _ => ast.children().into_iter().for_each(|exp| self.exp(exp)),
+        }
+    }
+
+    fn function(&mut self, params: &'a [ParamAST], body: &'a AST) {
+        for param in params {
+            if let Some(default) = &param.default {
+                self.exp(default);
+            }
+        }
+        let locals = params.iter().filter_map(|p| self.symbol(p.span)).collect();
+        self.block(body, locals);
+    }
+
+    fn check_condition(&mut self, guard: &AST, span: Span, keyword: &str) {
+        if is_constant(guard) {
+            self.warnings.push(Warning {
+                rule: Rule::ConstantCondition,
+                span,
+                message: format!("the condition of this {} is always the same", keyword),
+            });
+        }
+    }
+
+    fn check_call(&mut self, fun: &AST, args: &'a [ArgAST]) {
+        match fun {
+            AST::Identifier(name, span) => match self.symbol(*span) {
+                Some(id) if self.symbols.symbols[id].kind == SymbolKind::Function => {
+                    self.calls.push(Call { function: id, span: *span, args })
+                },
+                Some(_) => (),
+                None => {
+                    if let Some((_, builtin)) = BUILTIN_FUNCTIONS.iter().find(|(n, _)| n == name) {
+                        self.check_builtin(name, builtin, *span, args);
+                    }
+                },
+            },
+            AST::PropertyAccess { exp, property, span } => {
+                if let AST::Identifier(namespace, namespace_span) = exp.as_ref() {
+                    if self.symbol(*namespace_span).is_some() || !NAMESPACES.contains(&namespace.as_str()) {
+                        return
+                    }
+                    let builtin = NAMESPACE_FUNCTIONS.iter().find(|(ns, n, _)| ns == namespace && n == property);
+                    if let Some((_, _, builtin)) = builtin {
+                        self.check_builtin(&format!("{}.{}", namespace, property), builtin, *span, args);
+                    }
+                }
+            },
+            _ => (),
+        }
+    }
+
+    fn check_builtin(&mut self, name: &str, builtin: &BuiltInFunction, span: Span, args: &[ArgAST]) {
+        let message = if args.iter().any(|arg| matches!(arg, ArgAST::Named(..))) {
+            format!("built-in function '{}' does not accept named arguments", name)
+        } else {
+            let (min, max) = builtin.num_args();
+            if (min..=max).contains(&args.len()) {
+                return
+            }
+            let expected = if min == max {
+                min.to_string()
+            } else if max == usize::MAX {
+                format!("at least {}", min)
+            } else {
+                format!("{} to {}", min, max)
+            };
+            format!("'{}' takes {} {} but {} {} given", name, expected, plural(if max == usize::MAX { min } else { max }, "argument"),
+                args.len(), if args.len() == 1 { "was" } else { "were" })
+        };
+        self.warnings.push(Warning { rule: Rule::WrongArgumentCount, span, message });
+    }
+
+    /// Checks the methods called on list and string literals
+    fn check_method(&mut self, exp: &AST, property: &str, span: Span) {
+        let (type_name, methods) = match exp {
+            AST::List { .. } => ("List", LIST_METHODS),
+            AST::Constant(Constant::String(_)) => ("String", STRING_METHODS),
+            _ => return,
+        };
+        if methods.iter().any(|(name, _)| *name == property) {
+            return
+        }
+        let suggestion = methods.iter()
+            .map(|(name, _)| (edit_distance(name, property), *name))
+            .filter(|(distance, name)| *distance <= 2 && *distance < name.len())
+            .min();
+        let message = match suggestion {
+            Some((_, name)) => format!("{} has no method '{}', did you mean '{}'?", type_name, property, name),
+            None => format!("{} has no method '{}'", type_name, property),
+        };
+        self.warnings.push(Warning { rule: Rule::UnknownMethod, span, message });
+    }
+
+    /// Returns true if the rest of a block is never evaluated after a statement
+    fn diverges(&self, ast: &AST) -> bool {
+        match ast {
+            AST::FunctionCall { fun, args } => match fun.as_ref() {
+                AST::Identifier(name, span) if name == "exit" && self.symbol(*span).is_none() => true,
+                _ => self.diverges(fun) || args.iter().any(|arg| match arg {
+                    ArgAST::Positional(exp) | ArgAST::Named(_, exp) => self.diverges(exp),
+                }),
+            },
+            AST::While { guard, .. } => matches!(guard.as_ref(), AST::Constant(Constant::Bool(true))),
+            AST::Condition { exp, then_block, else_block, .. } => {
+                self.diverges(exp) || self.diverges(then_block) && self.diverges(else_block)
+            },
+            AST::Block(exp) | AST::Yield(exp) | AST::UnaryOp(_, exp) => self.diverges(exp),
+            AST::Concatenation { left, right } => self.diverges(left) || self.diverges(right),
+            // The right operand of && and || is not always evaluated
+            AST::BinaryOp(left, BinaryOpcode::And | BinaryOpcode::Or, _) => self.diverges(left),
+            AST::Assignment(left, right) | AST::BinaryOp(left, _, right) => self.diverges(left) || self.diverges(right),
+            _ => false,
+        }
+    }
+
+    /// Returns the variable assigned by a statement, with the position of the assignment
+    fn assigned(&self, ast: &AST) -> Option<(usize, Span)> {
+        let span = match ast {
+            AST::Assignment(left, _) => match left.as_ref() {
+                AST::Identifier(_, span) | AST::Definition(_, span, _) => *span,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        let id = self.symbol(span)?;
+        match self.symbols.symbols[id].kind {
+            SymbolKind::Variable | SymbolKind::Parameter => Some((id, span)),
+            _ => None,
+        }
+    }
+
+    /// Returns the symbols referenced or declared in an expression
+    fn mentioned(&self, ast: &AST) -> HashSet<usize> {
+        let mut symbols = HashSet::new();
+        let mut stack = vec![ast];
+        while let Some(ast) = stack.pop() {
+            if let AST::Identifier(_, span) | AST::Definition(_, span, _) = ast {
+                symbols.extend(self.symbol(*span));
+            }
+            stack.extend(ast.children());
+        }
+        symbols
+    }
+
+    fn symbol(&self, span: Span) -> Option<usize> {
+        self.positions.get(&span.start).copied()
+    }
+}
+
+/// Returns a message if the arguments of a call do not match the parameters of a function
+fn check_arguments(function: &FunctionAST, args: &[ArgAST]) -> Option<String> {
+    let variadic = function.params.last().is_some_and(|p| p.variadic);
+    let params = &function.params[..function.params.len() - variadic as usize];
+    let positional = args.iter().filter(|arg| matches!(arg, ArgAST::Positional(_))).count();
+    if positional > params.len() && !variadic {
+        return Some(format!("'{}' takes at most {} positional {} but {} {} given", function.name, params.len(),
+            plural(params.len(), "argument"), positional, if positional == 1 { "was" } else { "were" }))
+    }
+    let mut named = Vec::new();
+    for arg in args {
+        if let ArgAST::Named(name, _) = arg {
+            match params.iter().position(|p| &p.name == name) {
+                Some(i) if i < positional => return Some(format!("argument '{}' of '{}' is given twice", name, function.name)),
+                Some(_) => named.push(name),
+                None => return Some(format!("'{}' has no parameter named '{}'", function.name, name)),
+            }
+        }
+    }
+    params.iter().skip(positional)
+        .find(|p| p.default.is_none() && !named.contains(&&p.name))
+        .map(|p| format!("missing argument '{}' in call to '{}'", p.name, function.name))
+}
+
+/// Returns true if an expression only contains constants
+fn is_constant(ast: &AST) -> bool {
+    match ast {
+        AST::Constant(_) => true,
+        AST::UnaryOp(_, exp) => is_constant(exp),
+        AST::BinaryOp(left, _, right) => is_constant(left) && is_constant(right),
+        _ => false,
+    }
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 { word.to_owned() } else { format!("{}s", word) }
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = if ca == *cb { previous } else { 1 + previous.min(row[j]).min(current) };
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
@@ -0,0 +1,365 @@
+use lalrpop_util::ParseError;
+
+use crate::compiler::ast::{Span, AST};
+use crate::compiler::checker::TypeChecker;
+use crate::compiler::compiler::{resolve_symbols, BUILTIN_FUNCTIONS, NAMESPACES, NAMESPACE_CONSTANTS, NAMESPACE_FUNCTIONS};
+use crate::compiler::context::{CompilerContext, Symbol, SymbolKind, SymbolTable};
+use crate::compiler::epilang::ASTParser;
+use crate::runtime::value::{ITERATOR_METHODS, LIST_METHODS, MAP_METHODS, REGEX_METHODS, STRING_METHODS};
+use crate::ProgramError;
+
+/// Built-in methods grouped by the type of value they are called on
+pub const BUILTIN_METHODS: [(&str, &[(&str, crate::intermediate::exp::BuiltInFunction)]); 5] = [
+    ("List", LIST_METHODS),
+    ("Map", MAP_METHODS),
+    ("String", STRING_METHODS),
+    ("Regex", REGEX_METHODS),
+    ("Iterator", ITERATOR_METHODS),
+];
+
+pub const KEYWORDS: [&str; 18] = [
+    "let", "fn", "class", "trait", "private", "static", "implements", "if", "else",
+    "while", "for", "in", "yield", "done", "true", "false", "test", "self",
+];
+
+pub struct Diagnostic {
+    pub span: Span,
+    pub message: String,
+}
+
+/// Completion proposal, kind is the LSP completion item kind
+pub struct Completion {
+    pub label: String,
+    pub kind: u32,
+    pub detail: String,
+}
+
+/// Result of parsing and compiling a document
+pub struct Analysis {
+    pub text: String,
+    pub diagnostics: Vec<Diagnostic>,
+    pub symbols: SymbolTable,
+    /// Symbols of the last version of the document that could be parsed, used for completion
+    /// while the document is being edited and does not parse
+    names: Option<SymbolTable>,
+}
+
+impl Analysis {
+    /// Parses, compiles and type checks a document, recording its declarations and references.
+    /// Test blocks are compiled after the other statements, as done by the test runner.
+    pub fn new(text: String) -> Analysis {
+        let ast = match ASTParser::new().parse(&text) {
+            Ok(ast) => ast,
+            Err(e) => {
+                let diagnostic = Diagnostic { span: parse_error_span(&text, &e), message: ProgramError::SyntaxError(e.to_string()).to_string() };
+                return Analysis { text, diagnostics: vec![diagnostic], symbols: SymbolTable::default(), names: None }
+            }
+        };
+        let mut ctx = CompilerContext::new();
+        ctx.record_symbols();
+        let diagnostics = match resolve_symbols(&ast, &mut ctx) {
+            Ok(_) => check_types(&ast).into_iter().collect(),
+            Err(e) => vec![Diagnostic { span: ctx.span(), message: ProgramError::CompilerError(e).to_string() }],
+        };
+        Analysis { text, diagnostics, symbols: ctx.take_symbols().unwrap_or_default(), names: None }
+    }
+
+    /// Analyzes a new version of a document, keeping the symbols of the previous
+    /// version for completion if the new one does not parse
+    pub fn update(self, text: String) -> Analysis {
+        let mut analysis = Analysis::new(text);
+        if analysis.symbols.symbols.is_empty() && !analysis.diagnostics.is_empty() {
+            analysis.names = self.names.or(Some(self.symbols));
+        }
+        analysis
+    }
+
+    /// Returns the id of the symbol declared or referenced at an offset
+    pub fn symbol_at(&self, offset: usize) -> Option<usize> {
+        self.symbols.symbols.iter().position(|s| s.span.contains(offset))
+            .or_else(|| self.symbols.references.iter().find(|(_, span)| span.contains(offset)).map(|(id, _)| *id))
+    }
+
+    pub fn symbol(&self, id: usize) -> &Symbol {
+        &self.symbols.symbols[id]
+    }
+
+    /// Returns the positions of the references to a symbol
+    pub fn references(&self, id: usize) -> Vec<Span> {
+        self.symbols.references.iter().filter(|(symbol, _)| *symbol == id).map(|(_, span)| *span).collect()
+    }
+
+    /// Returns the declarations of a symbol. Members accessed on values other than
+    /// self and classes are not resolved, so all the members with the same name are returned.
+    pub fn definitions(&self, offset: usize) -> Vec<Span> {
+        if let Some(id) = self.symbol_at(offset) {
+            return vec![self.symbol(id).span]
+        }
+        match self.member_word(offset) {
+            Some((_, word)) => self.symbols.symbols.iter()
+                .filter(|s| is_member(s) && s.name == word)
+                .map(|s| s.span)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the hover text at an offset, in markdown
+    pub fn hover(&self, offset: usize) -> Option<String> {
+        if let Some(id) = self.symbol_at(offset) {
+            let symbol = self.symbol(id);
+            return Some(format!("```epilang\n{}\n```\n{}", symbol.detail, kind_name(symbol.kind)))
+        }
+        let (start, end) = word_bounds(&self.text, offset);
+        let word = &self.text[start..end];
+        if word.is_empty() {
+            return None
+        }
+        if let Some((receiver, _)) = self.member_word(offset) {
+            if NAMESPACES.contains(&receiver) {
+                if NAMESPACE_FUNCTIONS.iter().any(|(ns, name, _)| *ns == receiver && *name == word) {
+                    return Some(format!("```epilang\n{}.{}\n```\nbuilt-in function", receiver, word))
+                }
+                if NAMESPACE_CONSTANTS.iter().any(|(ns, name, _)| *ns == receiver && *name == word) {
+                    return Some(format!("```epilang\n{}.{}\n```\nbuilt-in constant", receiver, word))
+                }
+                return None
+            }
+            let types: Vec<&str> = BUILTIN_METHODS.iter()
+                .filter(|(_, methods)| methods.iter().any(|(name, _)| *name == word))
+                .map(|(t, _)| *t)
+                .collect();
+            if !types.is_empty() {
+                return Some(format!("```epilang\n{}\n```\nbuilt-in method of {}", word, types.join(", ")))
+            }
+            return None
+        }
+        if BUILTIN_FUNCTIONS.iter().any(|(name, _)| *name == word) {
+            return Some(format!("```epilang\n{}\n```\nbuilt-in function", word))
+        }
+        if NAMESPACES.contains(&word) {
+            return Some(format!("```epilang\n{}\n```\nbuilt-in module", word))
+        }
+        None
+    }
+
+    /// Returns the completion proposals at an offset. After a dot, proposes the members of
+    /// a built-in module or the class members and built-in methods, otherwise the declared
+    /// names, the built-in functions and modules and the keywords.
+    pub fn completions(&self, offset: usize) -> Vec<Completion> {
+        let mut completions: Vec<Completion> = Vec::new();
+        let mut add = |label: &str, kind: u32, detail: String| {
+            if !completions.iter().any(|c| c.label == label) {
+                completions.push(Completion { label: label.to_owned(), kind, detail })
+            }
+        };
+        let symbols = &self.names.as_ref().unwrap_or(&self.symbols).symbols;
+        match self.member_word(offset) {
+            Some((receiver, _)) if NAMESPACES.contains(&receiver) => {
+                for (_, name, _) in NAMESPACE_FUNCTIONS.iter().filter(|(ns, _, _)| *ns == receiver) {
+                    add(name, FUNCTION, "built-in function".to_owned());
+                }
+                for (_, name, _) in NAMESPACE_CONSTANTS.iter().filter(|(ns, _, _)| *ns == receiver) {
+                    add(name, CONSTANT, "built-in constant".to_owned());
+                }
+            },
+            Some(_) => {
+                for symbol in symbols.iter().filter(|s| is_member(s)) {
+                    add(&symbol.name, completion_kind(symbol.kind), symbol.detail.clone());
+                }
+                for (t, methods) in BUILTIN_METHODS.iter() {
+                    for (name, _) in methods.iter() {
+                        add(name, METHOD, format!("built-in method of {}", t));
+                    }
+                }
+            },
+            None => {
+                for symbol in symbols.iter().filter(|s| !is_member(s)) {
+                    add(&symbol.name, completion_kind(symbol.kind), symbol.detail.clone());
+                }
+                for (name, _) in BUILTIN_FUNCTIONS.iter() {
+                    add(name, FUNCTION, "built-in function".to_owned());
+                }
+                for name in NAMESPACES.iter() {
+                    add(name, MODULE, "built-in module".to_owned());
+                }
+                for keyword in KEYWORDS.iter() {
+                    add(keyword, KEYWORD, "keyword".to_owned());
+                }
+            },
+        }
+        completions
+    }
+
+    /// When the word at an offset follows a dot, returns the word before the dot and the word itself
+    fn member_word(&self, offset: usize) -> Option<(&str, &str)> {
+        let (start, end) = word_bounds(&self.text, offset);
+        let before = self.text[..start].trim_end();
+        let dot = before.strip_suffix('.')?;
+        let (receiver_start, receiver_end) = word_bounds(dot, dot.len());
+        Some((&dot[receiver_start..receiver_end], &self.text[start..end]))
+    }
+}
+
+// Completion item kinds defined by the LSP specification
+const METHOD: u32 = 2;
+const FUNCTION: u32 = 3;
+const FIELD: u32 = 5;
+const VARIABLE: u32 = 6;
+const CLASS: u32 = 7;
+const INTERFACE: u32 = 8;
+const MODULE: u32 = 9;
+const KEYWORD: u32 = 14;
+const CONSTANT: u32 = 21;
+
+fn completion_kind(kind: SymbolKind) -> u32 {
+    match kind {
+        SymbolKind::Variable | SymbolKind::Parameter => VARIABLE,
+        SymbolKind::Function => FUNCTION,
+        SymbolKind::Class => CLASS,
+        SymbolKind::Trait => INTERFACE,
+        SymbolKind::Field => FIELD,
+        SymbolKind::Method => METHOD,
+    }
+}
+
+fn kind_name(kind: SymbolKind) -> &'static str {
+    match kind {
+        SymbolKind::Variable => "variable",
+        SymbolKind::Parameter => "parameter",
+        SymbolKind::Function => "function",
+        SymbolKind::Class => "class",
+        SymbolKind::Trait => "trait",
+        SymbolKind::Field => "field",
+        SymbolKind::Method => "method",
+    }
+}
+
+fn is_member(symbol: &Symbol) -> bool {
+    matches!(symbol.kind, SymbolKind::Field | SymbolKind::Method)
+}
+
+/// Type checks a document, with its test blocks after the other statements
+fn check_types(ast: &AST) -> Option<Diagnostic> {
+    let mut checker = TypeChecker::new();
+    let tests = ast.statements().into_iter().filter_map(|statement| match statement {
+        AST::Test { body, .. } => Some(body.as_ref()),
+        _ => None,
+    });
+    for ast in std::iter::once(ast).chain(tests) {
+        if let Err(e) = checker.check(ast) {
+            return Some(Diagnostic { span: checker.span(), message: ProgramError::TypeError(e).to_string() })
+        }
+    }
+    None
+}
+
+/// Returns the position of a syntax error in the parsed text
+pub fn parse_error_span<T, E>(text: &str, error: &ParseError<usize, T, E>) -> Span {
+    match error {
+        // Invalid tokens span one character, which can be longer than a byte
+        ParseError::InvalidToken { location } => {
+            let len = text[*location..].chars().next().map_or(0, char::len_utf8);
+            Span::new(*location, *location + len)
+        },
+        ParseError::UnrecognizedEof { location, .. } => Span::new(*location, *location),
+        ParseError::UnrecognizedToken { token: (start, _, end), .. } => Span::new(*start, *end),
+        ParseError::ExtraToken { token: (start, _, end) } => Span::new(*start, *end),
+        ParseError::User { .. } => Span::default(),
+    }
+}
+
+/// Returns the bounds of the identifier containing an offset, empty if there is none
+fn word_bounds(text: &str, offset: usize) -> (usize, usize) {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let offset = offset.min(text.len());
+    let start = text[..offset].rfind(|c: char| !is_word(c)).map_or(0, |i| i + 1);
+    let end = text[offset..].find(|c: char| !is_word(c)).map_or(text.len(), |i| offset + i);
+    (start, end)
+}
+
+/// Converts a byte offset to an LSP position, with characters counted in UTF-16 code units
+pub fn offset_to_position(text: &str, offset: usize) -> (u32, u32) {
+    let offset = offset.min(text.len());
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = text[..line_start].matches('\n').count();
+    let character: usize = text[line_start..offset].chars().map(char::len_utf16).sum();
+    (line as u32, character as u32)
+}
+
+/// Converts an LSP position to a byte offset
+pub fn position_to_offset(text: &str, line: u32, character: u32) -> usize {
+    let line_start = match line {
+        0 => 0,
+        n => match text.match_indices('\n').nth(n as usize - 1) {
+            Some((i, _)) => i + 1,
+            None => return text.len(),
+        },
+    };
+    let mut units = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character as usize || c == '\n' {
+            return line_start + i
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{offset_to_position, position_to_offset, Analysis};
+
+    #[test]
+    fn invalid_tokens_span_a_character() {
+        let analysis = Analysis::new("let é = 1;".to_owned());
+        let span = analysis.diagnostics[0].span;
+        assert_eq!((span.start, span.end), (4, 6));
+        assert_eq!(offset_to_position(&analysis.text, span.end), (0, 5));
+    }
+
+    #[test]
+    fn diagnostics() {
+        assert!(Analysis::new("let x = 1; x".to_owned()).diagnostics.is_empty());
+        let analysis = Analysis::new("let x = 1;\ny".to_owned());
+        assert_eq!(analysis.diagnostics.len(), 1);
+        assert_eq!(offset_to_position(&analysis.text, analysis.diagnostics[0].span.start), (1, 0));
+        assert!(Analysis::new("let s: String = 1".to_owned()).diagnostics[0].message.starts_with("TypeError"));
+    }
+
+    #[test]
+    fn definitions_and_references() {
+        let analysis = Analysis::new("let x = 1;\nfn f(a) { a };\nf(x) + x".to_owned());
+        let use_of_x = analysis.text.rfind('x').unwrap();
+        let spans = analysis.definitions(use_of_x);
+        assert_eq!((spans[0].start, spans[0].end), (4, 5));
+        let id = analysis.symbol_at(use_of_x).unwrap();
+        assert_eq!(analysis.references(id).len(), 2);
+        assert!(analysis.hover(use_of_x).unwrap().contains("let x"));
+        assert!(analysis.hover(analysis.text.find("f(x)").unwrap()).unwrap().contains("fn f(a)"));
+    }
+
+    #[test]
+    fn completions() {
+        // The document does not parse while typing, so the names of its previous version are proposed
+        let analysis = Analysis::new("let value = 1;\n".to_owned()).update("let value = 1;\nmath.".to_owned());
+        let labels = |offset| analysis.completions(offset).into_iter().map(|c| c.label).collect::<Vec<String>>();
+        let members = labels(analysis.text.len());
+        assert!(members.contains(&"sqrt".to_owned()) && members.contains(&"PI".to_owned()));
+        assert!(!members.contains(&"value".to_owned()));
+        let names = labels(0);
+        assert!(names.contains(&"value".to_owned()) && names.contains(&"println".to_owned()) && names.contains(&"while".to_owned()));
+    }
+
+    #[test]
+    fn positions_count_utf16_units() {
+        let text = "let s = \"😀\";\nlet t = 1";
+        let offset = text.find(';').unwrap();
+        assert_eq!(offset_to_position(text, offset), (0, 12));
+        assert_eq!(position_to_offset(text, 0, 12), offset);
+        assert_eq!(position_to_offset(text, 1, 4), text.find("t = ").unwrap());
+        // Positions past the end of a line or of the text are clamped
+        assert_eq!(position_to_offset(text, 0, 100), text.find('\n').unwrap());
+        assert_eq!(position_to_offset(text, 5, 0), text.len());
+    }
+}
@@ -0,0 +1,249 @@
+pub mod analysis;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value as Json};
+
+use crate::compiler::ast::Span;
+
+use analysis::{offset_to_position, position_to_offset, Analysis};
+
+/// Runs the language server, speaking the Language Server Protocol over stdin and stdout.
+/// Returns the exit status of the process.
+pub fn run_server() -> i32 {
+    let stdin = io::stdin();
+    let mut reader = stdin.lock();
+    let stdout = io::stdout();
+    let mut writer = stdout.lock();
+    let mut server = Server { documents: HashMap::new(), shutdown: false };
+    loop {
+        let message = match read_message(&mut reader) {
+            Ok(Some(message)) => message,
+            Ok(None) => return 1,
+            Err(e) => {
+                eprintln!("Language server error: {}", e);
+                return 1
+            }
+        };
+        if message["method"] == "exit" {
+            return if server.shutdown { 0 } else { 1 }
+        }
+        for response in server.handle(&message) {
+            if let Err(e) = write_message(&mut writer, &response) {
+                eprintln!("Language server error: {}", e);
+                return 1
+            }
+        }
+    }
+}
+
+struct Server {
+    documents: HashMap<String, Analysis>,
+    shutdown: bool,
+}
+
+impl Server {
+    /// Handles a request or a notification, returning the messages to send back to the client
+    fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = &message["id"];
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": { "triggerCharacters": ["."] },
+                },
+                "serverInfo": { "name": "epilang" },
+            }),
+            "shutdown" => {
+                self.shutdown = true;
+                Json::Null
+            },
+            "textDocument/didOpen" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                return vec![self.update(uri, text.to_owned())]
+            },
+            "textDocument/didChange" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                // Documents are synchronized in full, so the last change holds the whole text
+                let text = params["contentChanges"].as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                return match text {
+                    Some(text) => vec![self.update(uri, text.to_owned())],
+                    None => Vec::new(),
+                }
+            },
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                self.documents.remove(uri);
+                return vec![notification("textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": [] }))]
+            },
+            "textDocument/definition" => match self.document(params) {
+                Some((uri, doc, offset)) => {
+                    let locations: Vec<Json> = doc.definitions(offset).into_iter()
+                        .map(|span| location(uri, &doc.text, span))
+                        .collect();
+                    json!(locations)
+                },
+                None => Json::Null,
+            },
+            "textDocument/references" => match self.document(params) {
+                Some((uri, doc, offset)) => match doc.symbol_at(offset) {
+                    Some(id) => {
+                        let mut spans = doc.references(id);
+                        if params["context"]["includeDeclaration"].as_bool().unwrap_or(true) {
+                            spans.insert(0, doc.symbol(id).span);
+                        }
+                        let locations: Vec<Json> = spans.into_iter().map(|span| location(uri, &doc.text, span)).collect();
+                        json!(locations)
+                    },
+                    None => json!([]),
+                },
+                None => Json::Null,
+            },
+            "textDocument/hover" => match self.document(params).and_then(|(_, doc, offset)| doc.hover(offset)) {
+                Some(contents) => json!({ "contents": { "kind": "markdown", "value": contents } }),
+                None => Json::Null,
+            },
+            "textDocument/completion" => match self.document(params) {
+                Some((_, doc, offset)) => {
+                    let items: Vec<Json> = doc.completions(offset).into_iter()
+                        .map(|c| json!({ "label": c.label, "kind": c.kind, "detail": c.detail }))
+                        .collect();
+                    json!(items)
+                },
+                None => Json::Null,
+            },
+            _ if id.is_null() => return Vec::new(),
+            _ => return vec![json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": format!("Method not found: {}", method) },
+            })],
+        };
+        // Notifications without a response, such as initialized
+        if id.is_null() {
+            return Vec::new()
+        }
+        vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })]
+    }
+
+    /// Analyzes the new text of a document, returning its diagnostics notification
+    fn update(&mut self, uri: &str, text: String) -> Json {
+        let doc = match self.documents.remove(uri) {
+            Some(previous) => previous.update(text),
+            None => Analysis::new(text),
+        };
+        let diagnostics: Vec<Json> = doc.diagnostics.iter().map(|d| json!({
+            "range": range(&doc.text, d.span),
+            "severity": 1,
+            "source": "epilang",
+            "message": d.message,
+        })).collect();
+        self.documents.insert(uri.to_owned(), doc);
+        notification("textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": diagnostics }))
+    }
+
+    /// Returns the document and the offset of the position referred by the request params
+    fn document<'a>(&'a self, params: &'a Json) -> Option<(&'a str, &'a Analysis, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let doc = self.documents.get(uri)?;
+        let line = params["position"]["line"].as_u64()? as u32;
+        let character = params["position"]["character"].as_u64()? as u32;
+        Some((uri, doc, position_to_offset(&doc.text, line, character)))
+    }
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn location(uri: &str, text: &str, span: Span) -> Json {
+    json!({ "uri": uri, "range": range(text, span) })
+}
+
+fn range(text: &str, span: Span) -> Json {
+    let (start_line, start_character) = offset_to_position(text, span.start);
+    let (end_line, end_character) = offset_to_position(text, span.end);
+    json!({
+        "start": { "line": start_line, "character": start_character },
+        "end": { "line": end_line, "character": end_character },
+    })
+}
+
+/// Reads a message framed by a Content-Length header, returns None at the end of the input
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None)
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    use serde_json::json;
+
+    use super::{read_message, write_message, Server};
+
+    #[test]
+    fn message_framing() {
+        let mut bytes = Vec::new();
+        write_message(&mut bytes, &json!({ "id": 1, "text": "é" })).unwrap();
+        write_message(&mut bytes, &json!({ "id": 2 })).unwrap();
+        let mut reader = Cursor::new(bytes);
+        assert_eq!(read_message(&mut reader).unwrap(), Some(json!({ "id": 1, "text": "é" })));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(json!({ "id": 2 })));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+        assert!(read_message(&mut Cursor::new(b"Other: 1\r\n\r\n{}".to_vec())).is_err());
+    }
+
+    #[test]
+    fn requests() {
+        let mut server = Server { documents: HashMap::new(), shutdown: false };
+        let open = json!({ "method": "textDocument/didOpen", "params": { "textDocument": { "uri": "a.epi", "text": "let é = 1;" } } });
+        let diagnostics = server.handle(&open);
+        assert_eq!(diagnostics[0]["params"]["diagnostics"][0]["range"]["end"], json!({ "line": 0, "character": 5 }));
+
+        let change = json!({ "method": "textDocument/didChange", "params": { "textDocument": { "uri": "a.epi" }, "contentChanges": [{ "text": "let x = 1;\nx" }] } });
+        assert_eq!(server.handle(&change)[0]["params"]["diagnostics"], json!([]));
+        let position = json!({ "textDocument": { "uri": "a.epi" }, "position": { "line": 1, "character": 0 } });
+        let definition = server.handle(&json!({ "id": 1, "method": "textDocument/definition", "params": position }));
+        assert_eq!(definition[0]["result"][0]["range"]["start"], json!({ "line": 0, "character": 4 }));
+
+        let unknown = server.handle(&json!({ "id": 2, "method": "unknown" }));
+        assert_eq!(unknown[0]["error"]["code"], -32601);
+        assert!(server.handle(&json!({ "method": "initialized" })).is_empty());
+        server.handle(&json!({ "id": 3, "method": "shutdown" }));
+        assert!(server.shutdown);
+    }
+}
@@ -2,94 +2,108 @@
 extern crate lalrpop_util;
 
 mod compiler;
+mod debugger;
+mod dump;
+mod formatter;
 mod intermediate;
+mod linter;
+mod lsp;
+mod repl;
 mod runtime;
+mod test_runner;
 
 use std::env;
 use std::fs;
+use std::process;
 use thiserror::Error;
-use rustyline::Editor;
 
 use compiler::epilang::ASTParser;
 use compiler::context::CompilerContext;
 use compiler::compiler::compile;
-use compiler::error::CompilerError;
+use compiler::checker::TypeChecker;
+use compiler::error::{CompilerError, TypeError};
 use runtime::executor::{ExpressionError, evaluate};
-use runtime::value::{Value, V};
+use runtime::value::V;
 use runtime::module::Module;
 
 #[derive(Error, Debug)]
 pub enum ProgramError {
     #[error("SyntaxError: {0}")]
     SyntaxError(String),
+    #[error("TypeError: {0}")]
+    TypeError(TypeError),
     #[error("CompilerError: {0}")]
     CompilerError(CompilerError),
     #[error("{0}")]
     RuntimeError(ExpressionError),
 }
 
+impl ProgramError {
+    /// Exit status of the process when a program terminates with this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ProgramError::RuntimeError(ExpressionError::Exit(code)) => *code,
+            _ => 1,
+        }
+    }
+}
+
 fn main() {
-    let mut args: Vec<String> = env::args().collect();
-    if args.len() > 1 {
-        let file_path: String = args.remove(1);
-        run_file(file_path)
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("test") {
+        let path = args.get(1).map_or(".", String::as_str);
+        process::exit(test_runner::run_tests(path))
+    } else if args.first().map(String::as_str) == Some("fmt") {
+        process::exit(formatter::run_fmt(&args[1..]))
+    } else if args.first().map(String::as_str) == Some("lint") {
+        process::exit(linter::run_lint(&args[1..]))
+    } else if args.first().map(String::as_str) == Some("debug") {
+        process::exit(debugger::run_debug(&args[1..]))
+    } else if args.first().map(String::as_str) == Some("lsp") {
+        process::exit(lsp::run_server())
+    } else if args.first().is_some_and(|arg| arg.starts_with("--dump") || arg.starts_with("--emit")) {
+        process::exit(dump::run_dump(&args))
+    } else if !args.is_empty() {
+        let file_path: String = args.remove(0);
+        run_file(file_path, args)
     } else {
-        repl()
+        repl::repl()
     }
 }
 
-pub fn run_file(file_path: String) {
-    let text = fs::read_to_string(file_path)
-        .expect("Unable to read the program file");
+/// Runs a program file, the process exits with a non-zero status if the program fails
+pub fn run_file(file_path: String, args: Vec<String>) {
+    let text = match fs::read_to_string(&file_path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Unable to read the program file {}: {}", file_path, e);
+            process::exit(1)
+        }
+    };
 
+    let mut checker = TypeChecker::new();
     let mut ctx: CompilerContext = CompilerContext::new();
-    let mut module: Module = Default::default();
+    let mut module: Module = Module { args, ..Default::default() };
 
-    match run_program(text, &mut ctx, &mut module) {
+    match run_program(text, &mut checker, &mut ctx, &mut module) {
         Ok(v) => println!("Result: {}", v.as_ref()),
-        Err(e) => println!("{}", e),
-    }
-}
-
-pub fn repl() {
-    let mut ctx: CompilerContext = CompilerContext::new();
-    let mut module: Module = Default::default();
-
-    let mut rl: Editor<()> = Editor::<()>::new().expect("Error creating editor");
-    loop {
-        match rl.readline("epilang> ") {
-            Ok(mut text) => {
-                if text.trim().is_empty() { continue };
-                rl.add_history_entry(text.as_str());
-                while continue_reading(text.as_str()) {
-                    match rl.readline("... ") {
-                        Ok(next_line) => {
-                            rl.add_history_entry(next_line.as_str());
-                            text.push('\n');
-                            text.push_str(next_line.as_str())
-                        },
-                        Err(_) => break
-                    }
-                }
-                match run_program(text, &mut ctx, &mut module) {
-                    Ok(v) => {
-                        match v.as_ref() {
-                            Value::Unit => (),
-                            value => println!("{}", value.to_string())
-                        }
-                    },
-                    Err(e) => eprintln!("{}", e),
-                }
-            },
-            Err(_) => break
-        };
+        Err(e) => {
+            if !matches!(e, ProgramError::RuntimeError(ExpressionError::Exit(_))) {
+                eprintln!("{}", e);
+            }
+            process::exit(e.exit_code())
+        }
     }
 }
 
-fn run_program(line: String, ctx: &mut CompilerContext, module: &mut Module) -> Result<V, ProgramError> {
+/// Checks, compiles and evaluates a program in a context and module, which may contain previous declarations
+pub fn run_program(line: String, checker: &mut TypeChecker, ctx: &mut CompilerContext, module: &mut Module) -> Result<V, ProgramError> {
     let ast = ASTParser::new().parse(&line)
         .map_err(|e| { ProgramError::SyntaxError(e.to_string()) })?;
 
+    checker.check(&ast)
+        .map_err(|e| { ProgramError::TypeError(e) })?;
+
     let exp = compile(&ast, ctx)
         .map_err(|e| { ProgramError::CompilerError(e) })?;
 
@@ -99,25 +113,9 @@ fn run_program(line: String, ctx: &mut CompilerContext, module: &mut Module) ->
     Ok(v)
 }
 
-/// Used in the REPL to check if some string is ready to be evaluated
-/// or if the REPL must continue reading
-fn continue_reading(text: &str) -> bool {
-    if text.trim_end().ends_with('.') {
-        return true
-    }
-    let mut round_brackets_count = 0;
-    let mut square_brackets_count = 0;
-    let mut curly_brackets_count = 0;
-    for c in text.chars() {
-        match c {
-            '(' => round_brackets_count += 1,
-            '[' => square_brackets_count += 1,
-            '{' => curly_brackets_count += 1,
-            ')' => round_brackets_count -= 1,
-            ']' => square_brackets_count -= 1,
-            '}' => curly_brackets_count -= 1,
-            _ => ()
-        }
-    }
-    round_brackets_count > 0 || square_brackets_count > 0 || curly_brackets_count > 0
+/// Runs a program in a new module, returning the representation of its result
+#[cfg(test)]
+pub fn eval(text: &str) -> Result<String, ProgramError> {
+    let mut module = Module::default();
+    run_program(text.to_owned(), &mut TypeChecker::new(), &mut CompilerContext::new(), &mut module).map(|v| v.as_ref().repr())
 }
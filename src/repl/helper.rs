@@ -0,0 +1,163 @@
+use std::borrow::Cow;
+
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use crate::compiler::compiler::{BUILTIN_FUNCTIONS, NAMESPACES, NAMESPACE_CONSTANTS, NAMESPACE_FUNCTIONS};
+use crate::lsp::analysis::{BUILTIN_METHODS, KEYWORDS};
+
+use super::COMMANDS;
+
+const KEYWORD_COLOR: &str = "\x1b[35m";
+const BUILTIN_COLOR: &str = "\x1b[36m";
+const STRING_COLOR: &str = "\x1b[32m";
+const NUMBER_COLOR: &str = "\x1b[33m";
+const COMMENT_COLOR: &str = "\x1b[90m";
+const RESET: &str = "\x1b[0m";
+
+/// Completes and highlights the REPL input
+pub struct ReplHelper {
+    /// Variables, classes and traits defined in the session
+    pub names: Vec<String>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        if line.starts_with(':') && !line[..pos].contains(char::is_whitespace) {
+            let commands = COMMANDS.iter().filter_map(|(usage, _)| usage.split(' ').next());
+            return Ok((0, commands.filter(|c| c.starts_with(&line[..pos])).map(str::to_owned).collect()))
+        }
+        let start = line[..pos].rfind(|c: char| !is_word(c)).map_or(0, |i| i + 1);
+        let prefix = &line[start..pos];
+        let candidates: Vec<&str> = if start > 0 && line[..start].ends_with('.') {
+            let receiver_start = line[..start - 1].rfind(|c: char| !is_word(c)).map_or(0, |i| i + 1);
+            let receiver = &line[receiver_start..start - 1];
+            if NAMESPACES.contains(&receiver) && !self.names.iter().any(|n| n == receiver) {
+                NAMESPACE_FUNCTIONS.iter().filter(|(ns, _, _)| *ns == receiver).map(|(_, name, _)| *name)
+                    .chain(NAMESPACE_CONSTANTS.iter().filter(|(ns, _, _)| *ns == receiver).map(|(_, name, _)| *name))
+                    .collect()
+            } else {
+                BUILTIN_METHODS.iter().flat_map(|(_, methods)| methods.iter().map(|(name, _)| *name)).collect()
+            }
+        } else {
+            self.names.iter().map(String::as_str)
+                .chain(BUILTIN_FUNCTIONS.iter().map(|(name, _)| *name))
+                .chain(NAMESPACES.iter().copied())
+                .chain(KEYWORDS.iter().copied())
+                .collect()
+        };
+        let mut candidates: Vec<String> = candidates.into_iter()
+            .filter(|c| c.starts_with(prefix))
+            .map(str::to_owned)
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+        Ok((start, candidates))
+    }
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        Cow::Owned(highlight(line))
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        true
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+/// Colors keywords, built-in functions, literals and comments with ANSI escape codes
+fn highlight(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() * 2);
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        let (len, color) = if rest.starts_with("//") {
+            (rest.len(), Some(COMMENT_COLOR))
+        } else if c == '"' {
+            (rest[1..].find('"').map_or(rest.len(), |i| i + 2), Some(STRING_COLOR))
+        } else if c.is_ascii_digit() {
+            (rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len()), Some(NUMBER_COLOR))
+        } else if is_word(c) {
+            let len = rest.find(|c: char| !is_word(c)).unwrap_or(rest.len());
+            let word = &rest[..len];
+            let color = if KEYWORDS.contains(&word) {
+                Some(KEYWORD_COLOR)
+            } else if BUILTIN_FUNCTIONS.iter().any(|(name, _)| *name == word) || NAMESPACES.contains(&word) {
+                Some(BUILTIN_COLOR)
+            } else {
+                None
+            };
+            (len, color)
+        } else {
+            (c.len_utf8(), None)
+        };
+        match color {
+            Some(color) => {
+                out.push_str(color);
+                out.push_str(&rest[..len]);
+                out.push_str(RESET);
+            },
+            None => out.push_str(&rest[..len]),
+        }
+        rest = &rest[len..];
+    }
+    out
+}
+
+fn is_word(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use rustyline::history::History;
+
+    use super::*;
+
+    fn complete(names: &[&str], line: &str) -> (usize, Vec<String>) {
+        let helper = ReplHelper { names: names.iter().map(|n| n.to_string()).collect() };
+        let history = History::new();
+        helper.complete(line, line.len(), &Context::new(&history)).unwrap()
+    }
+
+    #[test]
+    fn completes_names_and_commands() {
+        assert_eq!(complete(&["printer"], "let y = prin"), (8, vec!["print".to_owned(), "printer".to_owned(), "println".to_owned()]));
+        assert_eq!(complete(&[], "wh"), (0, vec!["while".to_owned()]));
+        assert_eq!(complete(&[], ":l"), (0, vec![":load".to_owned()]));
+        // Arguments of meta-commands are completed as code
+        assert_eq!(complete(&[], ":type pri"), (6, vec!["print".to_owned(), "println".to_owned(), "private".to_owned()]));
+    }
+
+    #[test]
+    fn completes_members() {
+        assert_eq!(complete(&[], "math.p"), (5, vec!["pow".to_owned()]));
+        assert_eq!(complete(&[], "math.P"), (5, vec!["PI".to_owned()]));
+        assert!(complete(&[], "[1].pu").1.contains(&"push".to_owned()));
+        // A variable shadowing a namespace has methods, not namespace members
+        assert!(!complete(&["math"], "math.p").1.contains(&"pow".to_owned()));
+    }
+
+    #[test]
+    fn highlights_tokens() {
+        assert_eq!(highlight("let x"), format!("{}let{} x", KEYWORD_COLOR, RESET));
+        assert_eq!(highlight("println(12.5)"), format!("{}println{}({}12.5{})", BUILTIN_COLOR, RESET, NUMBER_COLOR, RESET));
+        assert_eq!(highlight("\"if\" // é"), format!("{}\"if\"{} {}// é{}", STRING_COLOR, RESET, COMMENT_COLOR, RESET));
+        // Unterminated strings are colored up to the end of the line
+        assert_eq!(highlight("\"ab"), format!("{}\"ab{}", STRING_COLOR, RESET));
+        assert_eq!(highlight("letter"), "letter");
+    }
+}
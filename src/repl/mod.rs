@@ -0,0 +1,318 @@
+mod helper;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use lalrpop_util::ParseError;
+use rustyline::Editor;
+
+use crate::compiler::checker::TypeChecker;
+use crate::compiler::compiler::compile;
+use crate::compiler::context::CompilerContext;
+use crate::compiler::epilang::ASTParser;
+use crate::dump::{ast_tree, exp_tree};
+use crate::runtime::executor::ExpressionError;
+use crate::runtime::module::Module;
+use crate::runtime::value::{Value, V};
+use crate::{run_program, ProgramError};
+
+use helper::ReplHelper;
+
+/// Meta-commands with their description, shown by `:help`
+pub const COMMANDS: [(&str, &str); 7] = [
+    (":help", "Shows this message"),
+    (":vars", "Lists the variables with their values"),
+    (":type <expr>", "Evaluates an expression and shows the type of its value"),
+    (":load <file>", "Runs a source file in the current session"),
+    (":reset", "Discards all the variables, classes and traits"),
+    (":ast <code>", "Shows the syntax tree of some code"),
+    (":exp <code>", "Shows the compiled expression of some code, without evaluating it"),
+];
+
+/// Compiler and runtime state of a REPL session
+struct Session {
+    checker: TypeChecker,
+    ctx: CompilerContext,
+    module: Module,
+}
+
+impl Session {
+    fn new() -> Session {
+        Session { checker: TypeChecker::new(), ctx: CompilerContext::new(), module: Module::default() }
+    }
+
+    /// Runs some code, restoring the state preceding it if it fails. Otherwise the context
+    /// would keep the definitions compiled before the error, pointing to variables never pushed.
+    /// Only the bindings are restored, not the mutations to existing values: after
+    /// `let l = [1]` a failing `l.push(2); [][0]` leaves `l` as `[1, 2]`.
+    fn run(&mut self, text: String) -> Result<V, ProgramError> {
+        let checker = self.checker.clone();
+        let ctx = self.ctx.clone();
+        let variables = self.module.variables.clone();
+        let classes = self.module.classes.clone();
+        let result = run_program(text, &mut self.checker, &mut self.ctx, &mut self.module);
+        if result.is_err() {
+            self.checker = checker;
+            self.ctx = ctx;
+            self.module.variables = variables;
+            self.module.classes = classes;
+        }
+        result
+    }
+
+    /// Variables, classes and traits defined in the session
+    fn names(&self) -> Vec<String> {
+        self.ctx.global_variables().into_iter().map(|(name, _)| name)
+            .chain(self.ctx.global_types())
+            .map(str::to_owned)
+            .collect()
+    }
+}
+
+pub fn repl() {
+    let mut session = Session::new();
+
+    let mut rl: Editor<ReplHelper> = Editor::new().expect("Error creating editor");
+    rl.set_helper(Some(ReplHelper { names: Vec::new() }));
+    let history = history_path();
+    if let Some(path) = &history {
+        // The history file does not exist in the first session
+        let _ = rl.load_history(path);
+    }
+
+    while let Ok(mut text) = rl.readline("epilang> ") {
+        if text.trim().is_empty() { continue };
+        rl.add_history_entry(text.as_str());
+        while continue_reading(text.as_str()) {
+            match rl.readline("... ") {
+                Ok(next_line) => {
+                    rl.add_history_entry(next_line.as_str());
+                    text.push('\n');
+                    text.push_str(next_line.as_str())
+                },
+                Err(_) => break
+            }
+        }
+        let exit_code = if text.trim_start().starts_with(':') {
+            command(&mut session, text.trim())
+        } else {
+            report(session.run(text))
+        };
+        if let Some(code) = exit_code {
+            save_history(&mut rl, &history);
+            process::exit(code)
+        }
+        if let Some(helper) = rl.helper_mut() {
+            helper.names = session.names();
+        }
+    }
+    save_history(&mut rl, &history);
+}
+
+/// Runs a meta-command, returns the exit status if the evaluated code called exit
+fn command(session: &mut Session, line: &str) -> Option<i32> {
+    let (name, arg) = line.split_once(char::is_whitespace).map_or((line, ""), |(name, arg)| (name, arg.trim()));
+    let usage = COMMANDS.iter().map(|(usage, _)| *usage).find(|usage| usage.split(' ').next() == Some(name));
+    if arg.is_empty() && usage.is_some_and(|usage| usage.contains('<')) {
+        eprintln!("Usage: {}", usage.unwrap_or_default());
+        return None
+    }
+    match name {
+        ":help" => {
+            for (usage, description) in COMMANDS.iter() {
+                println!("{:<14} {}", usage, description);
+            }
+        },
+        ":vars" => {
+            for (name, scope) in session.ctx.global_variables() {
+                if let Some(value) = session.module.variables.get(scope) {
+                    println!("{} = {}", name, value.as_ref().repr());
+                }
+            }
+        },
+        ":type" => match session.run(arg.to_owned()) {
+            Ok(v) => println!("{}", v.as_ref().get_type()),
+            Err(ProgramError::RuntimeError(ExpressionError::Exit(code))) => return Some(code),
+            Err(e) => eprintln!("{}", e),
+        },
+        ":load" => match fs::read_to_string(arg) {
+            Ok(text) => return report(session.run(text)),
+            Err(e) => eprintln!("Unable to read {}: {}", arg, e),
+        },
+        ":reset" => *session = Session::new(),
+        ":ast" => match ASTParser::new().parse(arg) {
+            Ok(ast) => print!("{}", ast_tree(&ast).to_text()),
+            Err(e) => eprintln!("{}", ProgramError::SyntaxError(e.to_string())),
+        },
+        ":exp" => match ASTParser::new().parse(arg) {
+            // Compiled in a copy of the context, as the code is not evaluated
+            Ok(ast) => match compile(&ast, &mut session.ctx.clone()) {
+                Ok(exp) => print!("{}", exp_tree(&exp).to_text()),
+                Err(e) => eprintln!("{}", ProgramError::CompilerError(e)),
+            },
+            Err(e) => eprintln!("{}", ProgramError::SyntaxError(e.to_string())),
+        },
+        _ => eprintln!("Unknown command '{}', type :help to list the commands", name),
+    }
+    None
+}
+
+/// Prints the result of an evaluation, returns the exit status if the evaluated code called exit
+fn report(result: Result<V, ProgramError>) -> Option<i32> {
+    match result {
+        Ok(v) => {
+            match v.as_ref() {
+                Value::Unit => (),
+                value => println!("{}", value.repr())
+            }
+            None
+        },
+        Err(ProgramError::RuntimeError(ExpressionError::Exit(code))) => Some(code),
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        },
+    }
+}
+
+/// History file in the user's home directory
+fn history_path() -> Option<PathBuf> {
+    env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")).map(|home| PathBuf::from(home).join(".epilang_history"))
+}
+
+fn save_history(rl: &mut Editor<ReplHelper>, path: &Option<PathBuf>) {
+    if let Some(path) = path {
+        if let Err(e) = rl.save_history(path) {
+            eprintln!("Unable to save the history to {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// Used in the REPL to check if some string is ready to be evaluated or if the REPL must
+/// continue reading, which is when the parser reaches the end of the input expecting more tokens
+fn continue_reading(text: &str) -> bool {
+    let code = match text.trim_start().strip_prefix(':') {
+        // Only the commands taking some code as argument can span multiple lines
+        Some(command) => match command.split_once(char::is_whitespace) {
+            Some(("type" | "ast" | "exp", code)) => code,
+            _ => return false,
+        },
+        None => text,
+    };
+    match ASTParser::new().parse(code) {
+        Err(ParseError::UnrecognizedEof { .. }) => true,
+        // String literals can span multiple lines
+        Err(ParseError::InvalidToken { location }) => code[location..].starts_with('"'),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(session: &mut Session, text: &str) -> Result<String, ProgramError> {
+        session.run(text.to_owned()).map(|v| v.as_ref().repr())
+    }
+
+    #[test]
+    fn runtime_error_discards_definitions() {
+        let mut session = Session::new();
+        assert!(matches!(eval(&mut session, "let a = 1; [][0]"), Err(ProgramError::RuntimeError(_))));
+        assert!(matches!(eval(&mut session, "a"), Err(ProgramError::CompilerError(_))));
+        assert_eq!(eval(&mut session, "let b = 2; b").unwrap(), "2");
+    }
+
+    #[test]
+    fn runtime_error_in_block_keeps_slots_aligned() {
+        let mut session = Session::new();
+        assert!(eval(&mut session, "{ let y = 3; [][0] }").is_err());
+        assert!(eval(&mut session, "[][0]; let w = 5;").is_err());
+        eval(&mut session, "let z = 4;").unwrap();
+        assert_eq!(eval(&mut session, "z").unwrap(), "4");
+    }
+
+    #[test]
+    fn runtime_error_restores_assigned_variables() {
+        let mut session = Session::new();
+        eval(&mut session, "let x = 1;").unwrap();
+        assert!(eval(&mut session, "x = 2; [][0]").is_err());
+        assert_eq!(eval(&mut session, "x").unwrap(), "1");
+    }
+
+    #[test]
+    fn runtime_error_keeps_mutated_values() {
+        let mut session = Session::new();
+        eval(&mut session, "let l = [1];").unwrap();
+        assert!(eval(&mut session, "l.push(2); [][0]").is_err());
+        assert_eq!(eval(&mut session, "l").unwrap(), "[1, 2]");
+    }
+
+    #[test]
+    fn compiler_error_discards_classes() {
+        let mut session = Session::new();
+        assert!(matches!(eval(&mut session, "class A { }; undefined"), Err(ProgramError::CompilerError(_))));
+        assert!(matches!(eval(&mut session, "A"), Err(ProgramError::CompilerError(_))));
+        eval(&mut session, "class A { }").unwrap();
+        assert!(eval(&mut session, "A()").is_ok());
+    }
+
+    #[test]
+    fn type_error_discards_annotations() {
+        let mut session = Session::new();
+        assert!(matches!(eval(&mut session, "fn f(x: Int) { x }; let s: String = 2"), Err(ProgramError::TypeError(_))));
+        assert!(matches!(eval(&mut session, "f(\"text\")"), Err(ProgramError::CompilerError(_))));
+    }
+
+    #[test]
+    fn incomplete_input_continues_reading() {
+        assert!(continue_reading("fn f(x) {"));
+        assert!(continue_reading("if x { 1 } else"));
+        assert!(continue_reading("\"multi"));
+        assert!(continue_reading(":ast 1 +"));
+        assert!(!continue_reading("let s = \"{\";"));
+        assert!(!continue_reading("1 + )"));
+        assert!(!continue_reading(":load file"));
+    }
+
+    #[test]
+    fn names_lists_definitions() {
+        let mut session = Session::new();
+        eval(&mut session, "let a = 1; class B { }; trait C { }").unwrap();
+        let names = session.names();
+        assert!(["a", "B", "C"].iter().all(|name| names.contains(&name.to_string())));
+    }
+
+    #[test]
+    fn commands_update_the_session() {
+        let path = env::temp_dir().join(format!("epilang_repl_load_{}.epi", process::id()));
+        fs::write(&path, "let loaded = 40 + 2;").unwrap();
+        let mut session = Session::new();
+        assert_eq!(command(&mut session, &format!(":load {}", path.display())), None);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(eval(&mut session, "loaded").unwrap(), "42");
+        assert_eq!(command(&mut session, ":load"), None);
+        assert_eq!(command(&mut session, &format!(":load {}", path.display())), None);
+        // Code shown by :exp is compiled but not defined
+        assert_eq!(command(&mut session, ":exp let shown = 1"), None);
+        assert!(eval(&mut session, "shown").is_err());
+        assert_eq!(command(&mut session, ":type let typed = 1"), None);
+        assert_eq!(eval(&mut session, "typed").unwrap(), "1");
+        assert_eq!(command(&mut session, ":reset"), None);
+        assert!(eval(&mut session, "loaded").is_err());
+        assert!(session.names().is_empty());
+    }
+
+    #[test]
+    fn commands_return_exit_codes() {
+        let mut session = Session::new();
+        assert_eq!(command(&mut session, ":type exit(3)"), Some(3));
+        assert_eq!(command(&mut session, ":type"), None);
+        assert_eq!(command(&mut session, ":unknown"), None);
+        assert_eq!(command(&mut session, ":help"), None);
+        assert_eq!(report(session.run("exit(4)".to_owned())), Some(4));
+        assert_eq!(report(session.run("[][0]".to_owned())), None);
+    }
+}
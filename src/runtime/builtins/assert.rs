@@ -0,0 +1,45 @@
+use crate::runtime::executor::ExpressionError;
+use crate::runtime::pointer::Ptr;
+use crate::runtime::value::{Value, V};
+
+/// Fails with the given message, or a default one, when the condition is false
+pub fn assert(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    if args[0].as_ref().as_bool() {
+        return Ok(V::Ptr(Ptr::unit()))
+    }
+    let message = match args.get(1) {
+        Some(message) => message.as_ref().to_string(),
+        None => "assertion failed".to_owned(),
+    };
+    Err(ExpressionError::AssertionFailed(message, None))
+}
+
+/// Fails when two values are not equal. Lists and maps are compared by content.
+pub fn assert_eq(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let (left, right) = (args[0].as_ref(), args[1].as_ref());
+    if equal(left, right) {
+        return Ok(V::Ptr(Ptr::unit()))
+    }
+    let mut message = format!("{} != {}", left.repr(), right.repr());
+    if let Some(m) = args.get(2) {
+        message = format!("{}: {}", m.as_ref(), message);
+    }
+    Err(ExpressionError::AssertionFailed(message, None))
+}
+
+fn equal(v1: &Value, v2: &Value) -> bool {
+    if std::ptr::eq(v1, v2) {
+        return true
+    }
+    match (v1, v2) {
+        (Value::List(l1), Value::List(l2)) => {
+            l1.len() == l2.len() && l1.iter().zip(l2.iter()).all(|(e1, e2)| equal(e1.as_ref(), e2.as_ref()))
+        },
+        (Value::Map(m1), Value::Map(m2)) => {
+            m1.len() == m2.len() && m1.entries().iter().all(|(key, e1)| {
+                m2.get(key).is_some_and(|e2| equal(e1.as_ref(), e2.as_ref()))
+            })
+        },
+        (v1, v2) => v1 == v2,
+    }
+}
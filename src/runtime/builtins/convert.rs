@@ -0,0 +1,93 @@
+use crate::intermediate::constant::Type;
+
+use crate::runtime::executor::ExpressionError;
+use crate::runtime::pointer::Ptr;
+use crate::runtime::value::{Value, V};
+
+/// Returns the name of the type of a value
+pub fn type_of(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    Ok(V::Val(Value::String(args[0].as_ref().get_type().to_string())))
+}
+
+/// Converts numbers, booleans and strings to Int. Floats are truncated towards zero.
+pub fn int(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let value = args[0].as_ref();
+    let result = match value {
+        Value::Int(i) => Some(*i),
+        Value::Float(f) if f.is_finite() && *f >= i32::MIN as f32 && *f < i32::MAX as f32 => Some(*f as i32),
+        Value::Float(_) => None,
+        Value::Bool(b) => Some(*b as i32),
+        Value::String(s) => s.trim().parse().ok(),
+        v => return Err(ExpressionError::UnsupportedConversion(v.get_type(), Type::Int)),
+    };
+    result.map(|i| V::Val(Value::Int(i)))
+        .ok_or_else(|| ExpressionError::InvalidConversion(value.to_string(), Type::Int))
+}
+
+/// Converts numbers, booleans and strings to Float
+pub fn float(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let value = args[0].as_ref();
+    let result = match value {
+        Value::Int(i) => Some(*i as f32),
+        Value::Float(f) => Some(*f),
+        Value::Bool(b) => Some(*b as i32 as f32),
+        Value::String(s) => s.trim().parse().ok(),
+        v => return Err(ExpressionError::UnsupportedConversion(v.get_type(), Type::Float)),
+    };
+    result.map(|f| V::Val(Value::Float(f)))
+        .ok_or_else(|| ExpressionError::InvalidConversion(value.to_string(), Type::Float))
+}
+
+/// Converts any value to String, as printed by println
+pub fn str(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    match args[0].as_ref() {
+        Value::String(_) => Ok(V::Ptr(args[0])),
+        v => Ok(V::Val(Value::String(v.to_string()))),
+    }
+}
+
+/// Converts any value to Bool, following the truthiness rules of conditions
+pub fn bool(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    Ok(V::Val(Value::Bool(args[0].as_ref().as_bool())))
+}
+
+/// Checks whether a value is an object created by a class
+pub fn is_instance(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    match (args[0].as_ref(), args[1].as_ref()) {
+        (Value::Object(obj), Value::Class(class)) => Ok(V::Val(Value::Bool(obj.class == *class))),
+        (_, Value::Class(_)) => Ok(V::Val(Value::Bool(false))),
+        (_, v) => Err(ExpressionError::UnexpectedType(Type::Class, v.get_type())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval, ProgramError};
+    use crate::intermediate::constant::Type;
+    use crate::runtime::executor::ExpressionError;
+
+    #[test]
+    fn types() {
+        assert_eq!(eval("[type_of(1), type_of(1.5), type_of(\"a\"), type_of([]), type_of(()), type_of(true)]").unwrap(), "[Int, Float, String, List, Unit, Bool]");
+        assert_eq!(eval("class A {}; fn f() {}; [type_of(A), type_of(A()), type_of(f)]").unwrap(), "[Class, Object, Function]");
+        assert_eq!(eval("class A {}; class B {}; [is_instance(A(), A), is_instance(A(), B), is_instance(1, A)]").unwrap(), "[true, false, false]");
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(eval("[int(2.9), int(-2.9), int(true), int(\" 42 \"), int(7)]").unwrap(), "[2, -2, 1, 42, 7]");
+        assert_eq!(eval("[float(2), float(\"1.5\"), float(false)]").unwrap(), "[2, 1.5, 0]");
+        assert_eq!(eval("[str(1), str([1, \"a\"]), str(\"s\")]").unwrap(), "[1, [1, a], s]");
+        assert_eq!(eval("[bool(0), bool(\"\"), bool([1]), bool(\"false\")]").unwrap(), "[false, false, true, true]");
+    }
+
+    #[test]
+    fn conversion_errors() {
+        assert!(matches!(eval("int(\"1.5\")"), Err(ProgramError::RuntimeError(ExpressionError::InvalidConversion(s, Type::Int))) if s == "1.5"));
+        assert!(matches!(eval("int(10000000000.0)"), Err(ProgramError::RuntimeError(ExpressionError::InvalidConversion(_, Type::Int)))));
+        assert!(matches!(eval("int(0.0 / 0.0)"), Err(ProgramError::RuntimeError(ExpressionError::InvalidConversion(_, Type::Int)))));
+        assert!(matches!(eval("float(\"x\")"), Err(ProgramError::RuntimeError(ExpressionError::InvalidConversion(_, Type::Float)))));
+        assert!(matches!(eval("int([])"), Err(ProgramError::RuntimeError(ExpressionError::UnsupportedConversion(Type::List, Type::Int)))));
+        assert!(matches!(eval("is_instance(1, 2)"), Err(ProgramError::RuntimeError(ExpressionError::UnexpectedType(Type::Class, Type::Int)))));
+    }
+}
@@ -0,0 +1,147 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::runtime::executor::ExpressionError;
+use crate::runtime::pointer::Ptr;
+use crate::runtime::value::{Value, V};
+
+use super::as_str;
+
+pub fn read_file(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let path = as_str(&args[0])?;
+    let content = fs::read_to_string(path).map_err(|e| io_error(path, e))?;
+    Ok(V::Val(Value::String(content)))
+}
+
+/// Writes a string to a file, creating the file or replacing its content
+pub fn write_file(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let path = as_str(&args[0])?;
+    fs::write(path, as_str(&args[1])?).map_err(|e| io_error(path, e))?;
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+/// Writes a string at the end of a file, creating the file if it does not exist
+pub fn append_file(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let path = as_str(&args[0])?;
+    let content = as_str(&args[1])?;
+    OpenOptions::new().create(true).append(true).open(path)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .map_err(|e| io_error(path, e))?;
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+pub fn exists(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let path = as_str(&args[0])?;
+    Ok(V::Val(Value::Bool(PathBuf::from(path).exists())))
+}
+
+/// Returns the names of the entries in a directory, in alphabetical order
+pub fn list_dir(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let path = as_str(&args[0])?;
+    let mut names = fs::read_dir(path)
+        .and_then(|entries| entries.map(|e| e.map(|e| e.file_name().to_string_lossy().into_owned())).collect::<io::Result<Vec<String>>>())
+        .map_err(|e| io_error(path, e))?;
+    names.sort();
+    let list = names.into_iter().map(|name| Ptr::from(Value::String(name))).collect();
+    Ok(V::Val(Value::List(list)))
+}
+
+/// Creates a directory along with its missing parents
+pub fn mkdir(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let path = as_str(&args[0])?;
+    fs::create_dir_all(path).map_err(|e| io_error(path, e))?;
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+/// Removes a file or an empty directory
+pub fn remove(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let path = as_str(&args[0])?;
+    let result = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) => Err(e),
+    };
+    result.map_err(|e| io_error(path, e))?;
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+/// Removes a directory with all its content
+pub fn remove_dir_all(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let path = as_str(&args[0])?;
+    fs::remove_dir_all(path).map_err(|e| io_error(path, e))?;
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+/// Joins path components with the platform separator
+pub fn join(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut path = PathBuf::new();
+    for arg in args.iter() {
+        path.push(as_str(arg)?);
+    }
+    Ok(V::Val(Value::String(path.to_string_lossy().into_owned())))
+}
+
+fn io_error(path: &str, error: io::Error) -> ExpressionError {
+    ExpressionError::IOError(format!("{}: {}", path, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use crate::{eval, ProgramError};
+    use crate::runtime::executor::ExpressionError;
+
+    /// Runs a program with `dir` bound to a new empty directory
+    fn eval_in_dir(name: &str, text: &str) -> Result<String, ProgramError> {
+        let dir = env::temp_dir().join(format!("epilang-fs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let result = eval(&format!("let dir = \"{}\"; {}", dir.display(), text));
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+
+    #[test]
+    fn files() {
+        let program = "let path = fs.join(dir, \"a.txt\");
+            let before = fs.exists(path);
+            fs.write_file(path, \"one\");
+            fs.append_file(path, \" two\");
+            fs.append_file(fs.join(dir, \"b.txt\"), \"new\");
+            [before, fs.read_file(path), fs.list_dir(dir)]";
+        assert_eq!(eval_in_dir("files", program).unwrap(), "[false, one two, [a.txt, b.txt]]");
+    }
+
+    #[test]
+    fn directories() {
+        let program = "let sub = fs.join(dir, \"x\", \"y\");
+            fs.mkdir(sub);
+            let created = fs.exists(sub);
+            fs.remove(sub);
+            fs.write_file(fs.join(dir, \"x\", \"f\"), \"\");
+            fs.remove_dir_all(fs.join(dir, \"x\"));
+            [created, fs.exists(sub), fs.list_dir(dir)]";
+        assert_eq!(eval_in_dir("directories", program).unwrap(), "[true, false, []]");
+    }
+
+    #[test]
+    fn remove_is_not_recursive() {
+        let dir = env::temp_dir().join(format!("epilang-fs-not-empty-{}", std::process::id()));
+        fs::create_dir_all(dir.join("x")).unwrap();
+        let result = eval(&format!("fs.remove(\"{}\")", dir.display()));
+        let kept = dir.join("x").exists();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(result, Err(ProgramError::RuntimeError(ExpressionError::IOError(_)))));
+        assert!(kept);
+    }
+
+    #[test]
+    fn io_errors() {
+        assert!(matches!(eval_in_dir("missing", "fs.read_file(fs.join(dir, \"missing\"))"), Err(ProgramError::RuntimeError(ExpressionError::IOError(_)))));
+        assert!(matches!(eval_in_dir("remove-missing", "fs.remove(fs.join(dir, \"missing\"))"), Err(ProgramError::RuntimeError(ExpressionError::IOError(_)))));
+        assert!(matches!(eval_in_dir("list-file", "fs.write_file(fs.join(dir, \"f\"), \"\"); fs.list_dir(fs.join(dir, \"f\"))"), Err(ProgramError::RuntimeError(ExpressionError::IOError(_)))));
+    }
+}
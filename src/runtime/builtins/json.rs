@@ -0,0 +1,160 @@
+use std::convert::TryFrom;
+
+use serde::Serialize;
+use serde_json::ser::{PrettyFormatter, Serializer};
+use serde_json::{Number, Value as Json};
+
+use crate::runtime::executor::ExpressionError;
+use crate::runtime::pointer::Ptr;
+use crate::runtime::value::{Map, Value, V};
+
+use super::{as_int, as_str};
+
+/// Parses a JSON string. Objects are converted to maps, integers that fit
+/// in an Int are converted to Int and all the other numbers to Float.
+pub fn parse(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let json: Json = serde_json::from_str(as_str(&args[0])?).map_err(|e| {
+        let message = e.to_string();
+        // Line and column are reported by the error variant
+        let message = message.rsplit_once(" at line ").map_or(message.as_str(), |(m, _)| m);
+        ExpressionError::JsonParseError(message.to_owned(), e.line(), e.column())
+    })?;
+    Ok(V::Val(from_json(json)))
+}
+
+/// Serializes a value to a JSON string, indenting nested values by the given number of spaces
+pub fn stringify(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let json = to_json(args[0], &mut Vec::new())?;
+    let indent = match args.get(1) {
+        Some(indent) => as_int(indent)?.max(0) as usize,
+        None => 0,
+    };
+    if indent == 0 {
+        return Ok(V::Val(Value::String(json.to_string())))
+    }
+    let indent = " ".repeat(indent);
+    let mut bytes = Vec::new();
+    let mut serializer = Serializer::with_formatter(&mut bytes, PrettyFormatter::with_indent(indent.as_bytes()));
+    json.serialize(&mut serializer).map_err(|e| ExpressionError::JsonError(e.to_string()))?;
+    Ok(V::Val(Value::String(String::from_utf8_lossy(&bytes).into_owned())))
+}
+
+fn from_json(json: Json) -> Value {
+    match json {
+        Json::Null => Value::Unit,
+        Json::Bool(b) => Value::Bool(b),
+        Json::Number(n) => match n.as_i64().and_then(|i| i32::try_from(i).ok()) {
+            Some(i) => Value::Int(i),
+            None => Value::Float(n.as_f64().unwrap_or(f64::NAN) as f32),
+        },
+        Json::String(s) => Value::String(s),
+        Json::Array(values) => Value::List(values.into_iter().map(|v| Ptr::from(from_json(v))).collect()),
+        Json::Object(entries) => {
+            let mut map = Map::default();
+            for (key, value) in entries {
+                map.insert(key, Ptr::from(from_json(value)));
+            }
+            Value::Map(map)
+        },
+    }
+}
+
+/// Converts a value to JSON. Visiting contains the lists, maps and objects
+/// being converted, which are used to detect cycles.
+fn to_json(ptr: Ptr<Value>, visiting: &mut Vec<*const Value>) -> Result<Json, ExpressionError> {
+    let address = ptr.as_ref() as *const Value;
+    let nested = matches!(ptr.as_ref(), Value::List(_) | Value::Map(_) | Value::Object(_));
+    if nested {
+        if visiting.contains(&address) {
+            return Err(ExpressionError::JsonError("cyclic structure cannot be serialized".to_owned()))
+        }
+        visiting.push(address);
+    }
+    let json = match ptr.as_ref() {
+        Value::Unit => Json::Null,
+        Value::Bool(b) => Json::Bool(*b),
+        Value::Int(i) => Json::from(*i),
+        // Converted through the shortest representation, so that 0.1 is not serialized as 0.10000000149011612
+        Value::Float(f) => match f.to_string().parse::<f64>().ok().and_then(Number::from_f64) {
+            Some(n) => Json::Number(n),
+            None => return Err(ExpressionError::JsonError(format!("{} is not a valid JSON number", f))),
+        },
+        Value::String(s) => Json::String(s.clone()),
+        Value::List(values) => {
+            Json::Array(values.iter().map(|v| to_json(*v, visiting)).collect::<Result<_, _>>()?)
+        },
+        Value::Map(map) => {
+            let mut entries = serde_json::Map::new();
+            for (key, value) in map.entries() {
+                entries.insert(key.clone(), to_json(*value, visiting)?);
+            }
+            Json::Object(entries)
+        },
+        // Objects are serialized by field, in declaration order
+        Value::Object(obj) => {
+            let mut entries = serde_json::Map::new();
+            for field in obj.class.as_ref().fields.iter() {
+                if let Some(value) = obj.get_field(&field.name) {
+                    entries.insert(field.name.clone(), to_json(value, visiting)?);
+                }
+            }
+            Json::Object(entries)
+        },
+        v => return Err(ExpressionError::NotSerializable(v.get_type())),
+    };
+    if nested {
+        visiting.pop();
+    }
+    Ok(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval, ProgramError};
+    use crate::intermediate::constant::Type;
+    use crate::runtime::executor::ExpressionError;
+    use crate::runtime::pointer::Ptr;
+    use crate::runtime::value::Value;
+
+    use super::{parse, stringify};
+
+    fn parse_str(text: &str) -> Result<String, ExpressionError> {
+        parse(vec![Ptr::from(Value::String(text.to_owned()))]).map(|v| v.as_ref().to_string())
+    }
+
+    #[test]
+    fn parse_values() {
+        assert_eq!(parse_str(r#"{"a": [1, 2.5, "x"], "b": {"c": null, "d": true}}"#).unwrap(), "{a: [1, 2.5, x], b: {c: unit, d: true}}");
+        // Integers that do not fit in an Int are parsed as floats
+        assert_eq!(parse_str("[3000000000]").unwrap(), "[3000000000]");
+        assert!(matches!(parse(vec![Ptr::from(Value::String("[3000000000]".to_owned()))]).unwrap().as_ref(), Value::List(l) if matches!(l[0].as_ref(), Value::Float(_))));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(parse_str("[1,\n 2,"), Err(ExpressionError::JsonParseError(_, 2, 3))));
+        assert!(matches!(parse_str("{\"a\" 1}"), Err(ExpressionError::JsonParseError(_, 1, 6))));
+        assert!(matches!(eval("json.parse(1)"), Err(ProgramError::RuntimeError(ExpressionError::UnexpectedType(Type::String, Type::Int)))));
+    }
+
+    #[test]
+    fn stringify_values() {
+        assert_eq!(eval("json.stringify([1, 0.1, \"a\", true, (), [], [[]]])").unwrap(), r#""[1,0.1,"a",true,null,[],[[]]]""#);
+        assert_eq!(eval("class P { let x = 1 let y = [2] }; json.stringify(P())").unwrap(), r#""{"x":1,"y":[2]}""#);
+        assert_eq!(eval("json.stringify([1, [2]], 2)").unwrap(), "\"[\n  1,\n  [\n    2\n  ]\n]\"");
+        // Shared values are not cycles
+        assert_eq!(eval("let a = [1]; json.stringify([a, a])").unwrap(), "\"[[1],[1]]\"");
+        // Maps keep the order of their keys
+        let map = parse(vec![Ptr::from(Value::String(r#"{"b": 1, "a": {}}"#.to_owned()))]).unwrap().into_ptr();
+        let map = stringify(vec![map]);
+        assert_eq!(map.unwrap().as_ref().to_string(), r#"{"b":1,"a":{}}"#);
+    }
+
+    #[test]
+    fn stringify_errors() {
+        assert!(matches!(eval("let l = [1]; l.push(l); json.stringify(l)"), Err(ProgramError::RuntimeError(ExpressionError::JsonError(_)))));
+        assert!(matches!(eval("class N { let next }; let n = N(); n.next = n; json.stringify(n)"), Err(ProgramError::RuntimeError(ExpressionError::JsonError(_)))));
+        assert!(matches!(eval("json.stringify(0.0 / 0.0)"), Err(ProgramError::RuntimeError(ExpressionError::JsonError(_)))));
+        assert!(matches!(eval("fn f() { 1 }; json.stringify([f])"), Err(ProgramError::RuntimeError(ExpressionError::NotSerializable(Type::Function)))));
+    }
+}
@@ -0,0 +1,338 @@
+use std::cmp::Ordering;
+use std::convert::TryInto;
+
+use crate::intermediate::constant::Type;
+
+use crate::runtime::executor::{ExpressionError, call_value};
+use crate::runtime::iterator::Iter;
+use crate::runtime::module::Module;
+use crate::runtime::pointer::Ptr;
+use crate::runtime::value::{Value, V};
+
+use super::{as_int, as_list};
+
+pub fn len(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut list = args[0];
+    let list = as_list(&mut list)?;
+    Ok(V::Val(Value::Int(list.len().try_into().unwrap())))
+}
+
+pub fn push(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut list = args[0];
+    as_list(&mut list)?.push(args[1]);
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+pub fn pop(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut list = args[0];
+    match as_list(&mut list)?.pop() {
+        Some(v) => Ok(V::Ptr(v)),
+        None => Err(ExpressionError::ListIndexOutofRange),
+    }
+}
+
+pub fn remove(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut list = args[0];
+    let list = as_list(&mut list)?;
+    match args[1].as_ref() {
+        Value::Int(i) => {
+            let index = (*i).try_into().map_err(|_| ExpressionError::ListIndexOutofRange)?;
+            if list.len() > index {
+                Ok(V::Ptr(list.remove(index)))
+            } else {
+                Err(ExpressionError::ListIndexOutofRange)
+            }
+        },
+        i => Err(ExpressionError::IndexTypeError(Type::List, i.get_type())),
+    }
+}
+
+/// Returns a new list with the results of calling a function on each element
+pub fn map(args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+    let values = elements(args[0])?;
+    let mut result = Vec::with_capacity(values.len());
+    for value in values {
+        result.push(call_value(args[1].as_ref(), vec![value], Vec::new(), module)?.into_ptr());
+    }
+    Ok(V::Val(Value::List(result)))
+}
+
+/// Returns a new list with the elements for which a function returns true
+pub fn filter(args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+    let mut result = Vec::new();
+    for value in elements(args[0])? {
+        if call_value(args[1].as_ref(), vec![value], Vec::new(), module)?.as_bool() {
+            result.push(value);
+        }
+    }
+    Ok(V::Val(Value::List(result)))
+}
+
+/// Combines the elements calling a function with the accumulated value and each element.
+/// When the initial value is missing the first element is used.
+pub fn reduce(args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+    let mut values = elements(args[0])?.into_iter();
+    let mut acc = match args.get(2) {
+        Some(initial) => *initial,
+        None => values.next().ok_or(ExpressionError::ListIndexOutofRange)?,
+    };
+    for value in values {
+        acc = call_value(args[1].as_ref(), vec![acc, value], Vec::new(), module)?.into_ptr();
+    }
+    Ok(V::Ptr(acc))
+}
+
+/// Returns the first element for which a function returns true, or unit if none is found
+pub fn find(args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+    for value in elements(args[0])? {
+        if call_value(args[1].as_ref(), vec![value], Vec::new(), module)?.as_bool() {
+            return Ok(V::Ptr(value))
+        }
+    }
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+pub fn any(args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+    for value in elements(args[0])? {
+        if call_value(args[1].as_ref(), vec![value], Vec::new(), module)?.as_bool() {
+            return Ok(V::Val(Value::Bool(true)))
+        }
+    }
+    Ok(V::Val(Value::Bool(false)))
+}
+
+pub fn all(args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+    for value in elements(args[0])? {
+        if !call_value(args[1].as_ref(), vec![value], Vec::new(), module)?.as_bool() {
+            return Ok(V::Val(Value::Bool(false)))
+        }
+    }
+    Ok(V::Val(Value::Bool(true)))
+}
+
+/// Sorts the list in place in ascending order.
+/// The elements must be all integers, all floats other than NaN, all strings or all booleans.
+pub fn sort(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut values = elements(args[0])?;
+    check_comparable(&values)?;
+    values.sort_by(|v1, v2| v1.as_ref().partial_cmp(v2.as_ref()).unwrap_or(Ordering::Equal));
+    replace_elements(args[0], values)
+}
+
+/// Sorts the list in place using a comparator function, which returns a negative
+/// number, zero or a positive number when the first argument is lower, equal
+/// or greater than the second
+pub fn sort_by(args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+    let values = elements(args[0])?;
+    let values = merge_sort(values, &mut |v1, v2| {
+        let result = call_value(args[1].as_ref(), vec![v1, v2], Vec::new(), module)?.into_ptr();
+        Ok(as_int(&result)?.cmp(&0))
+    })?;
+    replace_elements(args[0], values)
+}
+
+/// Reverses the list in place
+pub fn reverse(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut list = args[0];
+    as_list(&mut list)?.reverse();
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+/// Returns the index of the first element equal to a value, or -1 if none is found
+pub fn index_of(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let index = elements(args[0])?.iter().position(|v| v.as_ref() == args[1].as_ref());
+    Ok(V::Val(Value::Int(index.map_or(-1, |i| i as i32))))
+}
+
+pub fn contains(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let found = elements(args[0])?.iter().any(|v| v.as_ref() == args[1].as_ref());
+    Ok(V::Val(Value::Bool(found)))
+}
+
+/// Returns a new list with the elements from start (included) to end (excluded).
+/// Negative indices count from the end of the list, out of range indices are clamped.
+pub fn slice(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let values = elements(args[0])?;
+    let len = values.len() as i32;
+    let clamp = |i: i32| if i < 0 { (len + i).max(0) } else { i.min(len) } as usize;
+    let start = clamp(as_int(&args[1])?);
+    let end = match args.get(2) {
+        Some(end) => clamp(as_int(end)?),
+        None => values.len(),
+    };
+    let result = if start < end { values[start..end].to_vec() } else { Vec::new() };
+    Ok(V::Val(Value::List(result)))
+}
+
+/// Concatenates the elements in a string, with an optional separator between them
+pub fn join(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let separator = match args.get(1).map(|s| s.as_ref()) {
+        Some(Value::String(s)) => s.as_str(),
+        Some(v) => return Err(ExpressionError::UnexpectedType(Type::String, v.get_type())),
+        None => "",
+    };
+    let strings: Vec<String> = elements(args[0])?.iter().map(|v| v.as_ref().to_string()).collect();
+    Ok(V::Val(Value::String(strings.join(separator))))
+}
+
+/// Inserts a value at the given index, shifting the following elements
+pub fn insert(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut list = args[0];
+    let list = as_list(&mut list)?;
+    let index = as_int(&args[1])?;
+    let index = if index < 0 { index + list.len() as i32 } else { index };
+    if index < 0 || index as usize > list.len() {
+        return Err(ExpressionError::ListIndexOutofRange)
+    }
+    list.insert(index as usize, args[2]);
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+/// Appends all the values of an iterable to the list
+pub fn extend(args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+    let values = Iter::new(args[1], module)?.collect(module)?;
+    let mut list = args[0];
+    as_list(&mut list)?.extend(values);
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+pub fn clear(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut list = args[0];
+    as_list(&mut list)?.clear();
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+/// Copies the list elements, so that callbacks can safely modify the list
+fn elements(mut list: Ptr<Value>) -> Result<Vec<Ptr<Value>>, ExpressionError> {
+    Ok(as_list(&mut list)?.clone())
+}
+
+fn replace_elements(mut list: Ptr<Value>, values: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    *as_list(&mut list)? = values;
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+/// Checks that every two elements can be compared, so that they are totally ordered
+fn check_comparable(values: &[Ptr<Value>]) -> Result<(), ExpressionError> {
+    let first = match values {
+        [first, _, ..] => first.as_ref(),
+        _ => return Ok(()),
+    };
+    for value in values {
+        let comparable = match (first, value.as_ref()) {
+            (Value::Float(_), Value::Float(f)) => !f.is_nan(),
+            (Value::Int(_), Value::Int(_))
+            | (Value::String(_), Value::String(_))
+            | (Value::Bool(_), Value::Bool(_))
+            | (Value::Unit, Value::Unit) => true,
+            _ => false,
+        };
+        if !comparable {
+            return Err(ExpressionError::NotComparable(first.get_type(), value.as_ref().get_type()))
+        }
+    }
+    Ok(())
+}
+
+/// Stable merge sort with a comparator that can fail. Unlike the sort of the standard library,
+/// it does not panic when the comparator is not a total order, the elements are just left in
+/// an unspecified order.
+fn merge_sort<F>(mut values: Vec<Ptr<Value>>, compare: &mut F) -> Result<Vec<Ptr<Value>>, ExpressionError>
+where F: FnMut(Ptr<Value>, Ptr<Value>) -> Result<Ordering, ExpressionError> {
+    if values.len() < 2 {
+        return Ok(values)
+    }
+    let right = values.split_off(values.len() / 2);
+    let mut left = merge_sort(values, compare)?.into_iter().peekable();
+    let mut right = merge_sort(right, compare)?.into_iter().peekable();
+    let mut result = Vec::with_capacity(left.len() + right.len());
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        // Equal elements are taken from the left first to keep the sort stable
+        let next = if compare(*l, *r)? == Ordering::Greater { right.next() } else { left.next() };
+        result.extend(next);
+    }
+    result.extend(left);
+    result.extend(right);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval, ProgramError};
+    use crate::intermediate::constant::Type;
+    use crate::runtime::executor::ExpressionError;
+
+    fn runtime_error(text: &str) -> ExpressionError {
+        match eval(text) {
+            Err(ProgramError::RuntimeError(e)) => e,
+            result => panic!("expected a runtime error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn callback_methods() {
+        assert_eq!(eval("[1, 2, 3].map(|x| { x * 2 })").unwrap(), "[2, 4, 6]");
+        assert_eq!(eval("[1, 2, 3, 4].filter(|x| { x > 2 })").unwrap(), "[3, 4]");
+        assert_eq!(eval("[1, 2, 3].reduce(|a, b| { a + b })").unwrap(), "6");
+        assert_eq!(eval("[1, 2, 3].reduce(|a, b| { a + b }, 10)").unwrap(), "16");
+        assert_eq!(eval("[1, 2, 3].find(|x| { x > 1 })").unwrap(), "2");
+        assert_eq!(eval("[1, 2, 3].find(|x| { x > 5 })").unwrap(), "unit");
+        assert_eq!(eval("[[1, 2].any(|x| { x > 1 }), [1, 2].all(|x| { x > 1 }), [].all(|x| { false })]").unwrap(), "[true, false, true]");
+        // Functions and methods can be passed as callbacks too
+        assert_eq!(eval("fn double(x) { x * 2 }; [1, 2].map(double)").unwrap(), "[2, 4]");
+    }
+
+    #[test]
+    fn callback_errors() {
+        assert!(matches!(runtime_error("[].reduce(|a, b| { a + b })"), ExpressionError::ListIndexOutofRange));
+        assert!(matches!(runtime_error("[1].map(1)"), ExpressionError::ValueNotCallable(Type::Int)));
+        assert!(matches!(runtime_error("[1].map(|a, b| { a })"), ExpressionError::MissingArgument(_)));
+        assert!(matches!(runtime_error("[1].filter(|x| { [][0] })"), ExpressionError::IndexOutOfRange(..)));
+    }
+
+    #[test]
+    fn sort() {
+        assert_eq!(eval("let l = [3, 1, 2]; l.sort(); l").unwrap(), "[1, 2, 3]");
+        assert_eq!(eval("let l = [\"b\", \"c\", \"a\"]; l.sort(); l").unwrap(), "[a, b, c]");
+        assert_eq!(eval("let l = [2.5, -1.0]; l.sort(); l").unwrap(), "[-1, 2.5]");
+        assert_eq!(eval("let l = [3, 1, 2]; l.sort_by(|a, b| { b - a }); l").unwrap(), "[3, 2, 1]");
+        // Elements comparing equal keep their order
+        assert_eq!(eval("let l = [[1, 0], [0, 1], [1, 2], [0, 3]]; l.sort_by(|a, b| { a[0] - b[0] }); l").unwrap(), "[[0, 1], [0, 3], [1, 0], [1, 2]]");
+    }
+
+    #[test]
+    fn sort_errors() {
+        let mixed = "let l = [3, \"a\", 1, \"b\", 2.5, 0, \"c\", 7, true, 4, \"d\", 9, 1.5, \"e\", 8, 2, \"f\", 6, 5, \"g\", 11, 10, \"h\", 12];";
+        assert!(matches!(runtime_error(&format!("{} l.sort()", mixed)), ExpressionError::NotComparable(Type::Int, Type::String)));
+        assert!(matches!(runtime_error("let l = [1.0, 0.0 / 0.0]; l.sort()"), ExpressionError::NotComparable(Type::Float, Type::Float)));
+        assert!(matches!(runtime_error("let l = [1, 1.0]; l.sort()"), ExpressionError::NotComparable(Type::Int, Type::Float)));
+        assert!(matches!(runtime_error("let l = [1, 2]; l.sort_by(|a, b| { \"x\" })"), ExpressionError::UnexpectedType(Type::Int, Type::String)));
+    }
+
+    #[test]
+    fn inconsistent_comparators_do_not_panic() {
+        let program = "let l = []; let i = 0; while i < 40 { l.push(i * 7 - i * 7 / 40 * 40); i = i + 1 };
+            l.sort_by(|a, b| { if a > 20 { -1 } else { 1 } }); l.len()";
+        assert_eq!(eval(program).unwrap(), "40");
+    }
+
+    #[test]
+    fn list_methods() {
+        assert_eq!(eval("let l = [1, 2, 3]; l.reverse(); l").unwrap(), "[3, 2, 1]");
+        assert_eq!(eval("[[1, 2].index_of(2), [1, 2].index_of(5), [1, 2].contains(1), [1, 2].contains(3)]").unwrap(), "[1, -1, true, false]");
+        assert_eq!(eval("[[1, 2, 3, 4].slice(1, 3), [1, 2, 3, 4].slice(-2), [1, 2].slice(5), [1, 2, 3].slice(2, 1)]").unwrap(), "[[2, 3], [3, 4], [], []]");
+        assert_eq!(eval("[[1, \"a\", 2.5].join(\", \"), [1, 2].join()]").unwrap(), "[1, a, 2.5, 12]");
+        assert_eq!(eval("let l = [1, 3]; l.insert(1, 2); l.insert(-1, 9); l.insert(4, 4); l").unwrap(), "[1, 2, 9, 3, 4]");
+        assert_eq!(eval("let l = [1]; l.extend([2, 3]); l.extend(\"ab\"); l").unwrap(), "[1, 2, 3, a, b]");
+        assert_eq!(eval("let l = [1, 2]; l.clear(); l").unwrap(), "[]");
+    }
+
+    #[test]
+    fn list_method_errors() {
+        assert!(matches!(runtime_error("[1].insert(2, 0)"), ExpressionError::ListIndexOutofRange));
+        assert!(matches!(runtime_error("[1].insert(-2, 0)"), ExpressionError::ListIndexOutofRange));
+        assert!(matches!(runtime_error("[1].slice(\"a\")"), ExpressionError::UnexpectedType(Type::Int, Type::String)));
+        assert!(matches!(runtime_error("[1].join(1)"), ExpressionError::UnexpectedType(Type::String, Type::Int)));
+        assert!(matches!(runtime_error("[1].extend(1)"), ExpressionError::NotIterable(Type::Int)));
+    }
+}
@@ -0,0 +1,62 @@
+use std::convert::TryInto;
+
+use crate::intermediate::constant::Type;
+
+use crate::runtime::executor::ExpressionError;
+use crate::runtime::pointer::Ptr;
+use crate::runtime::value::{Map, Value, V};
+
+use super::as_str;
+
+/// Returns the map a built-in map function is called on
+fn as_map(ptr: &mut Ptr<Value>) -> Result<&mut Map, ExpressionError> {
+    match ptr.as_mut_ref() {
+        Value::Map(map) => Ok(map),
+        v => Err(ExpressionError::UnexpectedType(Type::Map, v.get_type())),
+    }
+}
+
+pub fn len(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut map = args[0];
+    let map = as_map(&mut map)?;
+    Ok(V::Val(Value::Int(map.len().try_into().unwrap())))
+}
+
+pub fn keys(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut map = args[0];
+    let keys = as_map(&mut map)?.entries().iter()
+        .map(|(key, _)| Ptr::from(Value::String(key.clone())))
+        .collect();
+    Ok(V::Val(Value::List(keys)))
+}
+
+pub fn values(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut map = args[0];
+    let values = as_map(&mut map)?.entries().iter().map(|(_, value)| *value).collect();
+    Ok(V::Val(Value::List(values)))
+}
+
+pub fn contains(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut map = args[0];
+    let found = as_map(&mut map)?.get(as_str(&args[1])?).is_some();
+    Ok(V::Val(Value::Bool(found)))
+}
+
+/// Returns the value of a key, or the default value (unit if missing) when the key is not found
+pub fn get(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut map = args[0];
+    match as_map(&mut map)?.get(as_str(&args[1])?) {
+        Some(value) => Ok(V::Ptr(value)),
+        None => Ok(V::Ptr(args.get(2).copied().unwrap_or_else(Ptr::unit))),
+    }
+}
+
+/// Removes a key and returns its value
+pub fn remove(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let mut map = args[0];
+    let key = as_str(&args[1])?;
+    match as_map(&mut map)?.remove(key) {
+        Some(value) => Ok(V::Ptr(value)),
+        None => Err(ExpressionError::KeyNotFound(key.to_owned())),
+    }
+}
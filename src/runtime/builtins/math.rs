@@ -0,0 +1,120 @@
+use std::convert::TryFrom;
+
+use crate::intermediate::constant::Type;
+
+use crate::runtime::executor::ExpressionError;
+use crate::runtime::pointer::Ptr;
+use crate::runtime::value::{Value, V};
+
+fn as_number(ptr: &Ptr<Value>) -> Result<f32, ExpressionError> {
+    match ptr.as_ref() {
+        Value::Int(i) => Ok(*i as f32),
+        Value::Float(f) => Ok(*f),
+        v => Err(ExpressionError::UnexpectedType(Type::Float, v.get_type())),
+    }
+}
+
+/// Applies a function to a numeric argument, returning a float
+pub fn float_fn(args: Vec<Ptr<Value>>, f: fn(f32) -> f32) -> Result<V, ExpressionError> {
+    Ok(V::Val(Value::Float(f(as_number(&args[0])?))))
+}
+
+/// Rounds a numeric argument to an integer
+pub fn int_fn(args: Vec<Ptr<Value>>, f: fn(f32) -> f32) -> Result<V, ExpressionError> {
+    match args[0].as_ref() {
+        Value::Int(i) => Ok(V::Val(Value::Int(*i))),
+        _ => Ok(V::Val(Value::Int(f(as_number(&args[0])?) as i32))),
+    }
+}
+
+pub fn abs(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    match args[0].as_ref() {
+        Value::Int(i) => Ok(V::Val(Value::Int(i.abs()))),
+        _ => float_fn(args, f32::abs),
+    }
+}
+
+/// Integer powers with non negative exponents are integers, other powers are floats
+pub fn pow(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    if let (Value::Int(base), Value::Int(exp)) = (args[0].as_ref(), args[1].as_ref()) {
+        if let Some(result) = u32::try_from(*exp).ok().and_then(|exp| base.checked_pow(exp)) {
+            return Ok(V::Val(Value::Int(result)))
+        }
+    }
+    Ok(V::Val(Value::Float(as_number(&args[0])?.powf(as_number(&args[1])?))))
+}
+
+pub fn atan2(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    Ok(V::Val(Value::Float(as_number(&args[0])?.atan2(as_number(&args[1])?))))
+}
+
+/// Natural logarithm, or logarithm in the given base
+pub fn log(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let x = as_number(&args[0])?;
+    match args.get(1) {
+        Some(base) => Ok(V::Val(Value::Float(x.log(as_number(base)?)))),
+        None => Ok(V::Val(Value::Float(x.ln()))),
+    }
+}
+
+/// Returns the minimum of the arguments, or of the elements of a single list argument
+pub fn min(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    extreme(args, |x, y| x < y)
+}
+
+/// Returns the maximum of the arguments, or of the elements of a single list argument
+pub fn max(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    extreme(args, |x, y| x > y)
+}
+
+fn extreme(args: Vec<Ptr<Value>>, better: fn(f32, f32) -> bool) -> Result<V, ExpressionError> {
+    let values = match (args.len(), args[0].as_ref()) {
+        (1, Value::List(list)) => list.clone(),
+        _ => args,
+    };
+    let mut result: Option<(Ptr<Value>, f32)> = None;
+    for value in values {
+        let number = as_number(&value)?;
+        match result {
+            Some((_, best)) if !better(number, best) => (),
+            _ => result = Some((value, number)),
+        }
+    }
+    match result {
+        Some((value, _)) => Ok(V::Ptr(value)),
+        None => Err(ExpressionError::ListIndexOutofRange),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval, ProgramError};
+    use crate::compiler::error::CompilerError;
+    use crate::intermediate::constant::Type;
+    use crate::runtime::executor::ExpressionError;
+
+    #[test]
+    fn integer_results() {
+        assert_eq!(eval("[math.abs(-3), math.pow(2, 10), math.floor(2.7), math.ceil(2.1), math.round(-2.5), math.floor(4)]").unwrap(), "[3, 1024, 2, 3, -3, 4]");
+        assert_eq!(eval("[math.min(3, 1, 2), math.max([1, 5, 2]), math.max(1, 2.5)]").unwrap(), "[1, 5, 2.5]");
+    }
+
+    #[test]
+    fn float_results() {
+        assert_eq!(eval("[math.sqrt(16), math.pow(2, -1), math.pow(2.0, 2), math.abs(-1.5)]").unwrap(), "[4, 0.5, 4, 1.5]");
+        assert_eq!(eval("[math.round(math.log(math.E)), math.log(8, 2), math.log2(8), math.log10(1000), math.exp(0)]").unwrap(), "[1, 3, 3, 3, 1]");
+        assert_eq!(eval("[math.sin(0), math.cos(0), math.atan2(0, 1), math.round(math.PI * 100)]").unwrap(), "[0, 1, 0, 314]");
+        // Integer powers that overflow are computed as floats
+        assert_eq!(eval("math.pow(10, 10) > 1.0").unwrap(), "true");
+    }
+
+    #[test]
+    fn math_errors() {
+        assert!(matches!(eval("math.sqrt(\"4\")"), Err(ProgramError::RuntimeError(ExpressionError::UnexpectedType(Type::Float, Type::String)))));
+        assert!(matches!(eval("math.max([1, true])"), Err(ProgramError::RuntimeError(ExpressionError::UnexpectedType(Type::Float, Type::Bool)))));
+        assert!(matches!(eval("math.min([])"), Err(ProgramError::RuntimeError(ExpressionError::ListIndexOutofRange))));
+        assert!(matches!(eval("math.cube(2)"), Err(ProgramError::CompilerError(CompilerError::UnknownNamespaceMember(..)))));
+        // The namespace can be shadowed by a variable
+        assert_eq!(eval("let math = [1]; math.len()").unwrap(), "1");
+    }
+}
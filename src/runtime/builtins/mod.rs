@@ -0,0 +1,38 @@
+pub mod assert;
+pub mod convert;
+pub mod fs;
+pub mod json;
+pub mod list;
+pub mod map;
+pub mod math;
+pub mod random;
+pub mod regex;
+pub mod time;
+
+use crate::intermediate::constant::Type;
+
+use super::executor::ExpressionError;
+use super::pointer::Ptr;
+use super::value::Value;
+
+/// Returns the list a built-in list function is called on
+fn as_list(ptr: &mut Ptr<Value>) -> Result<&mut Vec<Ptr<Value>>, ExpressionError> {
+    match ptr.as_mut_ref() {
+        Value::List(list) => Ok(list),
+        v => Err(ExpressionError::UnexpectedType(Type::List, v.get_type())),
+    }
+}
+
+fn as_int(ptr: &Ptr<Value>) -> Result<i32, ExpressionError> {
+    match ptr.as_ref() {
+        Value::Int(i) => Ok(*i),
+        v => Err(ExpressionError::UnexpectedType(Type::Int, v.get_type())),
+    }
+}
+
+fn as_str(ptr: &Ptr<Value>) -> Result<&str, ExpressionError> {
+    match ptr.as_ref() {
+        Value::String(s) => Ok(s),
+        v => Err(ExpressionError::UnexpectedType(Type::String, v.get_type())),
+    }
+}
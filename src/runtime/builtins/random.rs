@@ -0,0 +1,95 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::runtime::executor::ExpressionError;
+use crate::runtime::module::Module;
+use crate::runtime::pointer::Ptr;
+use crate::runtime::value::{Value, V};
+
+use super::{as_int, as_list};
+
+/// Advances the SplitMix64 generator of the module,
+/// seeded from the system time unless random.seed is called
+fn next_u64(module: &mut Module) -> u64 {
+    let seed = module.random.get_or_insert_with(|| {
+        SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)
+    });
+    *seed = seed.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = *seed;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// Returns a random number lower than bound
+fn next_below(bound: u64, module: &mut Module) -> u64 {
+    ((next_u64(module) as u128 * bound as u128) >> 64) as u64
+}
+
+/// Seeds the generator, so that the following random values are reproducible
+pub fn seed(args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+    let seed = as_int(&args[0])?;
+    module.random = Some(seed as u64);
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+/// Returns a random integer between a and b, both included
+pub fn int(args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+    let (a, b) = (as_int(&args[0])?, as_int(&args[1])?);
+    if a > b {
+        return Err(ExpressionError::EmptyRange(a, b))
+    }
+    let offset = next_below((b as i64 - a as i64 + 1) as u64, module);
+    Ok(V::Val(Value::Int((a as i64 + offset as i64) as i32)))
+}
+
+/// Returns a random float between 0 (included) and 1 (excluded)
+pub fn float(_args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+    let value = (next_u64(module) >> 40) as f32 / (1u32 << 24) as f32;
+    Ok(V::Val(Value::Float(value)))
+}
+
+/// Returns a random element of a list
+pub fn choice(args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+    let mut list = args[0];
+    let list = as_list(&mut list)?;
+    if list.is_empty() {
+        return Err(ExpressionError::ListIndexOutofRange)
+    }
+    Ok(V::Ptr(list[next_below(list.len() as u64, module) as usize]))
+}
+
+/// Shuffles a list in place
+pub fn shuffle(args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+    let mut list = args[0];
+    let list = as_list(&mut list)?;
+    for i in (1..list.len()).rev() {
+        list.swap(i, next_below(i as u64 + 1, module) as usize);
+    }
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval, ProgramError};
+    use crate::runtime::executor::ExpressionError;
+
+    #[test]
+    fn seeded_values_are_reproducible() {
+        let values = "[random.int(0, 1000), random.float(), random.choice([1, 2, 3])]";
+        assert_eq!(eval(&format!("random.seed(7); let a = {0}; random.seed(7); a.join() == {0}.join()", values)).unwrap(), "true");
+    }
+
+    #[test]
+    fn values_are_in_range() {
+        assert_eq!(eval("let ok = true; let i = 0; while i < 200 { let n = random.int(-2, 2); let f = random.float(); ok = ok && n >= -2 && n <= 2 && f >= 0.0 && f < 1.0; i = i + 1 }; ok").unwrap(), "true");
+        assert_eq!(eval("random.int(5, 5)").unwrap(), "5");
+        assert!(matches!(eval("random.int(2, 1)"), Err(ProgramError::RuntimeError(ExpressionError::EmptyRange(2, 1)))));
+    }
+
+    #[test]
+    fn choice_and_shuffle() {
+        assert_eq!(eval("let l = [1, 2, 3, 4, 5]; random.shuffle(l); l.sort(); l").unwrap(), "[1, 2, 3, 4, 5]");
+        assert_eq!(eval("[random.choice([4])]").unwrap(), "[4]");
+        assert!(matches!(eval("random.choice([])"), Err(ProgramError::RuntimeError(ExpressionError::ListIndexOutofRange))));
+    }
+}
@@ -0,0 +1,114 @@
+use regex::Regex;
+
+use crate::intermediate::constant::Type;
+
+use crate::runtime::executor::ExpressionError;
+use crate::runtime::pointer::Ptr;
+use crate::runtime::value::{Value, V};
+
+use super::as_str;
+
+/// Compiles a regular expression. Syntax errors report the column of the offending pattern part.
+pub fn compile(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let pattern = as_str(&args[0])?;
+    // The syntax is checked first because regex::Error does not expose the error position
+    if let Err(e) = regex_syntax::Parser::new().parse(pattern) {
+        let (message, column) = match &e {
+            regex_syntax::Error::Parse(e) => (e.kind().to_string(), e.span().start.column),
+            regex_syntax::Error::Translate(e) => (e.kind().to_string(), e.span().start.column),
+            e => (e.to_string(), 1),
+        };
+        return Err(ExpressionError::RegexSyntaxError(message, column))
+    }
+    let regex = Regex::new(pattern).map_err(|e| ExpressionError::RegexSyntaxError(e.to_string(), 1))?;
+    Ok(V::Val(Value::Regex(regex)))
+}
+
+fn as_regex(ptr: &Ptr<Value>) -> Result<&Regex, ExpressionError> {
+    match ptr.as_ref() {
+        Value::Regex(regex) => Ok(regex),
+        v => Err(ExpressionError::UnexpectedType(Type::Regex, v.get_type())),
+    }
+}
+
+fn string(s: &str) -> Ptr<Value> {
+    Ptr::from(Value::String(s.to_owned()))
+}
+
+pub fn is_match(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let found = as_regex(&args[0])?.is_match(as_str(&args[1])?);
+    Ok(V::Val(Value::Bool(found)))
+}
+
+/// Returns the first match, or unit if the pattern does not match
+pub fn find(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    match as_regex(&args[0])?.find(as_str(&args[1])?) {
+        Some(m) => Ok(V::Ptr(string(m.as_str()))),
+        None => Ok(V::Ptr(Ptr::unit())),
+    }
+}
+
+/// Returns the list of all the non-overlapping matches
+pub fn find_all(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let matches = as_regex(&args[0])?.find_iter(as_str(&args[1])?)
+        .map(|m| string(m.as_str()))
+        .collect();
+    Ok(V::Val(Value::List(matches)))
+}
+
+/// Returns the groups of the first match in a list, starting with the whole match,
+/// or unit if the pattern does not match. Groups not taking part in the match are unit.
+pub fn captures(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    match as_regex(&args[0])?.captures(as_str(&args[1])?) {
+        Some(captures) => {
+            let groups = captures.iter()
+                .map(|group| group.map_or_else(Ptr::unit, |m| string(m.as_str())))
+                .collect();
+            Ok(V::Val(Value::List(groups)))
+        },
+        None => Ok(V::Ptr(Ptr::unit())),
+    }
+}
+
+/// Replaces all the matches. The replacement can refer to groups with $1 or $name.
+pub fn replace(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let result = as_regex(&args[0])?.replace_all(as_str(&args[1])?, as_str(&args[2])?);
+    Ok(V::Val(Value::String(result.into_owned())))
+}
+
+/// Splits a string at each match
+pub fn split(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let parts = as_regex(&args[0])?.split(as_str(&args[1])?).map(string).collect();
+    Ok(V::Val(Value::List(parts)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval, ProgramError};
+    use crate::intermediate::constant::Type;
+    use crate::runtime::executor::ExpressionError;
+
+    #[test]
+    fn matching() {
+        let program = "let r = regex.compile(\"(\\d+)-(?P<b>\\w+)?\");";
+        assert_eq!(eval(&format!("{} [r.is_match(\"a 12-x\"), r.is_match(\"none\")]", program)).unwrap(), "[true, false]");
+        assert_eq!(eval(&format!("{} [r.find(\"a 12-x 3-\"), r.find(\"none\")]", program)).unwrap(), "[12-x, unit]");
+        assert_eq!(eval(&format!("{} r.find_all(\"1-a 2-b 3-\")", program)).unwrap(), "[1-a, 2-b, 3-]");
+        // Groups not taking part in the match are unit
+        assert_eq!(eval(&format!("{} [r.captures(\"1-a\"), r.captures(\"3-\"), r.captures(\"x\")]", program)).unwrap(), "[[1-a, 1, a], [3-, 3, unit], unit]");
+    }
+
+    #[test]
+    fn replace_and_split() {
+        assert_eq!(eval("regex.compile(\"(\\w+)@(?P<host>\\w+)\").replace(\"a@b, c@d\", \"$host:$1\")").unwrap(), "\"b:a, d:c\"");
+        assert_eq!(eval("regex.compile(\",\\s*\").split(\"a, b,c\")").unwrap(), "[a, b, c]");
+    }
+
+    #[test]
+    fn regex_errors() {
+        assert!(matches!(eval("regex.compile(\"a(b\")"), Err(ProgramError::RuntimeError(ExpressionError::RegexSyntaxError(_, 2)))));
+        assert!(matches!(eval("regex.compile(\"ab)\")"), Err(ProgramError::RuntimeError(ExpressionError::RegexSyntaxError(_, 3)))));
+        assert!(matches!(eval("regex.compile(1)"), Err(ProgramError::RuntimeError(ExpressionError::UnexpectedType(Type::String, Type::Int)))));
+        assert!(matches!(eval("regex.compile(\"a\").find(1)"), Err(ProgramError::RuntimeError(ExpressionError::UnexpectedType(Type::String, Type::Int)))));
+    }
+}
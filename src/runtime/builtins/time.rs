@@ -0,0 +1,60 @@
+use std::convert::TryFrom;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::intermediate::constant::Type;
+
+use crate::runtime::executor::ExpressionError;
+use crate::runtime::pointer::Ptr;
+use crate::runtime::value::{Value, V};
+
+/// Reference instant of the monotonic clock, set at the first call
+static CLOCK_START: OnceLock<Instant> = OnceLock::new();
+
+/// Returns the number of seconds elapsed since the Unix epoch
+pub fn now(_args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    Ok(V::Val(Value::Int(i32::try_from(seconds).unwrap_or(i32::MAX))))
+}
+
+/// Returns the seconds elapsed on a monotonic clock, only the difference
+/// between two calls is meaningful
+pub fn clock(_args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let start = CLOCK_START.get_or_init(Instant::now);
+    Ok(V::Val(Value::Float(start.elapsed().as_secs_f32())))
+}
+
+/// Suspends the program for the given number of milliseconds
+pub fn sleep(args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
+    let millis = match args[0].as_ref() {
+        Value::Int(i) => (*i).max(0) as f32,
+        Value::Float(f) => f.max(0.0),
+        v => return Err(ExpressionError::UnexpectedType(Type::Int, v.get_type())),
+    };
+    let duration = Duration::try_from_secs_f32(millis / 1000.0).map_err(|_| ExpressionError::InvalidDuration(millis))?;
+    thread::sleep(duration);
+    Ok(V::Ptr(Ptr::unit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval, ProgramError};
+    use crate::intermediate::constant::Type;
+    use crate::runtime::executor::ExpressionError;
+
+    #[test]
+    fn clocks() {
+        assert_eq!(eval("time.now() > 1700000000").unwrap(), "true");
+        assert_eq!(eval("let start = time.clock(); time.sleep(5); time.clock() - start >= 0.004").unwrap(), "true");
+        // Negative durations do not sleep
+        assert_eq!(eval("time.sleep(-1); time.sleep(0.5)").unwrap(), "unit");
+    }
+
+    #[test]
+    fn invalid_durations() {
+        assert!(matches!(eval("time.sleep(1.0 / 0.0)"), Err(ProgramError::RuntimeError(ExpressionError::InvalidDuration(_)))));
+        assert!(matches!(eval("time.sleep(100000000000000000000000000000000000.0)"), Err(ProgramError::RuntimeError(ExpressionError::InvalidDuration(_)))));
+        assert!(matches!(eval("time.sleep(\"1\")"), Err(ProgramError::RuntimeError(ExpressionError::UnexpectedType(Type::Int, Type::String)))));
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::intermediate::exp::StatementInfo;
+
+use super::executor::ExpressionError;
+use super::module::Module;
+
+/// Function call being evaluated, as seen by a debugger
+#[derive(Debug)]
+pub struct Frame {
+    /// Last statement reached in the function, None before its first statement
+    pub statement: Option<Arc<StatementInfo>>,
+    /// Position of the first variable of the function in the variable stack
+    pub stack_start: usize,
+}
+
+/// Pauses the evaluation of a module before the statements marked by the compiler
+pub trait Debugger {
+    /// Called before each marked statement with the call stack, whose last frame is evaluating the statement.
+    /// Returns an error to stop the evaluation.
+    fn statement(&mut self, frames: &[Frame], module: &mut Module) -> Result<(), ExpressionError>;
+}
+
+/// Call stack of a module being debugged
+pub struct DebugState {
+    frames: Vec<Frame>,
+    debugger: Box<dyn Debugger>,
+}
+
+impl DebugState {
+    pub fn new(debugger: Box<dyn Debugger>) -> DebugState {
+        DebugState { frames: vec![Frame { statement: None, stack_start: 0 }], debugger }
+    }
+
+    pub fn enter_function(&mut self, stack_start: usize) {
+        self.frames.push(Frame { statement: None, stack_start })
+    }
+
+    pub fn exit_function(&mut self) {
+        self.frames.pop();
+    }
+
+    /// Updates the current frame and notifies the debugger
+    pub fn statement(&mut self, info: &Arc<StatementInfo>, stack_start: usize, module: &mut Module) -> Result<(), ExpressionError> {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.statement = Some(info.clone());
+            // Field initializers are evaluated with their own stack start, without entering a function
+            frame.stack_start = stack_start;
+        }
+        self.debugger.statement(&self.frames, module)
+    }
+}
+
+impl fmt::Debug for DebugState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebugState").field("frames", &self.frames).finish()
+    }
+}
@@ -1,15 +1,18 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use thiserror::Error;
 
 use crate::intermediate::constant::Type;
-use crate::intermediate::exp::{Exp, FunctionExp};
+use crate::intermediate::exp::{Exp, FunctionExp, ParamExp, StatementInfo};
 use crate::intermediate::opcode::{BinaryOpcode, UnaryOpcode};
 use crate::runtime::operations::OperationError;
 
 use super::module::Module;
 use super::value::{V, Value, Class, Object, Field};
 use super::function::{Function};
+use super::generator::Generator;
+use super::iterator::Iter;
 use super::pointer::Ptr;
 
 #[derive(Error, Debug)]
@@ -18,10 +21,30 @@ pub enum ExpressionError {
     OperationError(OperationError),
     #[error("RuntimeError: list index out of range")]
     ListIndexOutofRange,
+    #[error("RuntimeError: {0} index {1} out of range for length {2}")]
+    IndexOutOfRange(Type, i32, usize),
+    #[error("RuntimeError: empty range from {0} to {1}")]
+    EmptyRange(i32, i32),
+    #[error("ValueError: cannot sleep for {0} milliseconds")]
+    InvalidDuration(f32),
+    #[error("RuntimeError: slice step cannot be zero")]
+    ZeroSliceStep,
+    #[error("TypeError: {0} does not support item assignment")]
+    ItemAssignment(Type),
     #[error("TypeError: {0} is not callable")]
     ValueNotCallable(Type),
     #[error("TypeError: function requires {0} positional argument(s) but {1} was given")]
     WrongArgumentsNumber(usize, usize),
+    #[error("TypeError: function takes at most {0} positional argument(s) but {1} were given")]
+    TooManyArguments(usize, usize),
+    #[error("TypeError: missing required argument '{0}'")]
+    MissingArgument(String),
+    #[error("TypeError: got an unexpected named argument '{0}'")]
+    UnexpectedNamedArgument(String),
+    #[error("TypeError: got multiple values for argument '{0}'")]
+    DuplicateArgument(String),
+    #[error("KeyError: key '{0}' not found")]
+    KeyNotFound(String),
     #[error("TypeError: {0} is not subscriptable")]
     NotSubscriptable(Type),
     #[error("TypeError: {0} indices must be integers, not {1}")]
@@ -30,8 +53,38 @@ pub enum ExpressionError {
     NoSuchFieldOrMethod(String),
     #[error("TypeError: no such field {0}")]
     NoSuchField(String),
+    #[error("TypeError: {0} object has no field '{1}', fields must be declared in the class body")]
+    UndeclaredField(String, String),
+    #[error("TypeError: '{1}' is a private member of class {0}")]
+    PrivateMember(String, String),
     #[error("TypeError: expected value of type {0}, found {1}")]
     UnexpectedType(Type, Type),
+    #[error("TypeError: cannot convert {0} to {1}")]
+    UnsupportedConversion(Type, Type),
+    #[error("ValueError: cannot convert '{0}' to {1}")]
+    InvalidConversion(String, Type),
+    #[error("TypeError: cannot compare {0} with {1}")]
+    NotComparable(Type, Type),
+    #[error("TypeError: {0} is not iterable")]
+    NotIterable(Type),
+    #[error("RuntimeError: generator already running")]
+    GeneratorAlreadyRunning,
+    #[error("JSONError: {0} at line {1}, column {2}")]
+    JsonParseError(String, usize, usize),
+    #[error("JSONError: {0}")]
+    JsonError(String),
+    #[error("TypeError: {0} is not JSON serializable")]
+    NotSerializable(Type),
+    #[error("RegexError: {0} at column {1}")]
+    RegexSyntaxError(String, usize),
+    /// Message and offset of the failed call in the source text
+    #[error("AssertionError: {0}")]
+    AssertionFailed(String, Option<usize>),
+    #[error("IOError: {0}")]
+    IOError(String),
+    /// Raised by the exit built-in function to terminate the program
+    #[error("Exit: program exited with status {0}")]
+    Exit(i32),
 }
 
 pub fn evaluate(exp: &Exp, module: &mut Module, stack_start: usize) -> Result<V, ExpressionError> {
@@ -59,25 +112,25 @@ pub fn evaluate(exp: &Exp, module: &mut Module, stack_start: usize) -> Result<V,
                 BinaryOpcode::Mul => {
                     let val2 = evaluate(arg2, module, stack_start)?;
                     let result = val1.as_ref() * val2.as_ref();
-                    let value = result.map_err(|e| ExpressionError::OperationError(e))?;
+                    let value = result.map_err(ExpressionError::OperationError)?;
                     Ok(V::Val(value))
                 },
                 BinaryOpcode::Div => {
                     let val2 = evaluate(arg2, module, stack_start)?;
                     let result = val1.as_ref() / val2.as_ref();
-                    let value = result.map_err(|e| ExpressionError::OperationError(e))?;
+                    let value = result.map_err(ExpressionError::OperationError)?;
                     Ok(V::Val(value))
                 },
                 BinaryOpcode::Add => {
                     let val2 = evaluate(arg2, module, stack_start)?;
                     let result = val1.as_ref() + val2.as_ref();
-                    let value = result.map_err(|e| ExpressionError::OperationError(e))?;
+                    let value = result.map_err(ExpressionError::OperationError)?;
                     Ok(V::Val(value))
                 },
                 BinaryOpcode::Sub => {
                     let val2 = evaluate(arg2, module, stack_start)?;
                     let result = val1.as_ref() - val2.as_ref();
-                    let value = result.map_err(|e| ExpressionError::OperationError(e))?;
+                    let value = result.map_err(ExpressionError::OperationError)?;
                     Ok(V::Val(value))
                 },
                 BinaryOpcode::And => {
@@ -147,15 +200,27 @@ pub fn evaluate(exp: &Exp, module: &mut Module, stack_start: usize) -> Result<V,
                 Exp::Subscript { element, index } => {
                     let mut e = evaluate(element, module, stack_start)?;
                     let i = evaluate(index, module, stack_start)?;
+                    // Assigning a missing key adds it to the map
+                    if let (Value::Map(map), Value::String(key)) = (e.as_mut_ref(), i.as_ref()) {
+                        map.insert(key.clone(), ptr);
+                        return Ok(V::Val(Value::Unit))
+                    }
                     let value_ptr = subscript(e.as_mut_ref(), i.as_ref())?;
                     *value_ptr = ptr;
                 },
 
-                Exp::PropertyAccess { exp, property } => {
+                Exp::PropertyAccess { exp, property, from_class } => {
                     let mut v = evaluate(exp, module, stack_start)?;
+                    check_visibility(v.as_ref(), property, *from_class)?;
                     match v.as_mut_ref() {
                         Value::Object(obj) => {
                             match obj.get_mut_field(property) {
+                                Some(field) => *field = ptr,
+                                None => return Err(ExpressionError::UndeclaredField(obj.class.as_ref().name.clone(), property.clone())),
+                            }
+                        },
+                        Value::Class(class) => {
+                            match class.as_mut_ref().static_fields.get_mut(property) {
                                 Some(field) => *field = ptr,
                                 None => return Err(ExpressionError::NoSuchField(property.clone())),
                             }
@@ -196,6 +261,19 @@ pub fn evaluate(exp: &Exp, module: &mut Module, stack_start: usize) -> Result<V,
             Ok(V::Val(Value::Unit))
         }
 
+        Exp::For { iterable, exp } => {
+            let iterable = evaluate(iterable, module, stack_start)?.into_ptr();
+            let mut iter = Iter::new(iterable, module)?;
+            while let Some(value) = iter.next(module)? {
+                let scope = module.variables.len();
+                module.variables.push(value);
+                let result = evaluate(exp, module, stack_start);
+                module.variables.truncate(scope);
+                result?;
+            }
+            Ok(V::Val(Value::Unit))
+        },
+
         Exp::List { elements } => {
             let list = evaluate_list(elements, module, stack_start)?;
             Ok(V::Val(Value::List(list)))
@@ -204,18 +282,53 @@ pub fn evaluate(exp: &Exp, module: &mut Module, stack_start: usize) -> Result<V,
         Exp::Subscript { element, index } => {
             let mut e = evaluate(element, module, stack_start)?;
             let i = evaluate(index, module, stack_start)?;
+            // Strings are indexed by character and cannot be assigned
+            if let (Value::String(s), Value::Int(i)) = (e.as_ref(), i.as_ref()) {
+                let chars: Vec<char> = s.chars().collect();
+                let index = resolve_index(*i, chars.len(), Type::String)?;
+                return Ok(V::Val(Value::String(chars[index].to_string())))
+            }
             let value_ptr = subscript(e.as_mut_ref(), i.as_ref())?;
             Ok(V::Ptr(*value_ptr))
         }
 
+        Exp::Slice { element, start, end, step } => {
+            let e = evaluate(element, module, stack_start)?;
+            let mut evaluate_bound = |bound: &Option<Box<Exp>>| -> Result<Option<i32>, ExpressionError> {
+                match bound {
+                    Some(exp) => match evaluate(exp, module, stack_start)?.as_ref() {
+                        Value::Int(i) => Ok(Some(*i)),
+                        v => Err(ExpressionError::IndexTypeError(e.as_ref().get_type(), v.get_type())),
+                    },
+                    None => Ok(None),
+                }
+            };
+            let start = evaluate_bound(start)?;
+            let end = evaluate_bound(end)?;
+            let step = evaluate_bound(step)?;
+            match e.as_ref() {
+                Value::List(values) => {
+                    let indices = slice_indices(values.len(), start, end, step)?;
+                    Ok(V::Val(Value::List(indices.into_iter().map(|i| values[i]).collect())))
+                },
+                Value::String(s) => {
+                    let chars: Vec<char> = s.chars().collect();
+                    let indices = slice_indices(chars.len(), start, end, step)?;
+                    Ok(V::Val(Value::String(indices.into_iter().map(|i| chars[i]).collect())))
+                },
+                v => Err(ExpressionError::NotSubscriptable(v.get_type())),
+            }
+        }
+
         Exp::Function(function_exp) => {
             let FunctionExp {
-                num_args, external_vars, body
+                params, external_vars, body, is_generator
             } = function_exp.as_ref();
             let function = Function {
-                num_args: *num_args,
+                params: params.clone(),
                 external_values: Vec::new(),
                 body: body.clone(),
+                is_generator: *is_generator,
             };
             let function_ptr = Ptr::from(Value::Function(function));
             match function_ptr.clone().as_mut_ref() {
@@ -237,85 +350,82 @@ pub fn evaluate(exp: &Exp, module: &mut Module, stack_start: usize) -> Result<V,
 
         Exp::Closure(function_exp) => {
             let FunctionExp {
-                num_args, external_vars, body
+                params, external_vars, body, is_generator
             } = function_exp.as_ref();
             let external_values = external_vars.iter().map(|var| {module.variables[*var + stack_start]}).collect();
             let function = Function {
-                num_args: *num_args,
-                external_values: external_values,
+                params: params.clone(),
+                external_values,
                 body: body.clone(),
+                is_generator: *is_generator,
             };
             Ok(V::Val(Value::Function(function)))
         },
 
-        Exp::FunctionCall { fun, args } => {
+        Exp::FunctionCall { fun, args, named_args, offset } => {
             let fun = evaluate(fun, module, stack_start)?;
-            match fun.as_ref() {
-                // Function call
-                Value::Function(fun) => {
-                    let args_v = evaluate_list(args, module, stack_start)?;
-                    call_function(fun, args_v, module)
-                },
-                // Method call
-                Value::Method(method) => {
-                    call_method(method.function.as_ref(), method.self_value, args, module, stack_start)
-                },
-                // Built-in function call
-                Value::BuiltInFunction(fun) => {
-                    let args_v = evaluate_list(args, module, stack_start)?;
-                    fun.call(args_v)
-                },
-                // Built-in method call
-                Value::BuiltInMethod(method) => {
-                    let mut args_v = Vec::with_capacity(args.len() + 1);
-                    args_v.push(method.self_value);
-                    args_v.extend(evaluate_list(args, module, stack_start)?);
-                    method.function.call(args_v)
-                }
-                // Class constructor call
-                Value::Class(class) => {
-                    // Create object
-                    let mut fields = HashMap::with_capacity(class.as_ref().fields.len());
-                    for field in &class.as_ref().fields {
-                        fields.insert(field.name.clone(), Ptr::unit());
-                    }
-                    let object = Value::Object(Object { class: *class, fields});
-                    // Call constructor
-                    call_method(&class.as_ref().constructor, Ptr::from(&object), args, module, stack_start)?;
-                    Ok(V::Val(object))
-                },
-                _ => Err(ExpressionError::ValueNotCallable(fun.as_ref().get_type()))
-            }
+            let args_v = evaluate_list(args, module, stack_start)?;
+            let named_args_v = evaluate_named_list(named_args, module, stack_start)?;
+            // Failed assertions are located at the innermost call, which is the assertion itself
+            call_value(fun.as_ref(), args_v, named_args_v, module).map_err(|e| match e {
+                ExpressionError::AssertionFailed(message, None) => ExpressionError::AssertionFailed(message, Some(*offset)),
+                e => e,
+            })
         },
 
         Exp::ClassDef(class_exp) => {
             // Create class
             let class = Class {
+                id: class_exp.id,
                 name: class_exp.name.clone(),
                 fields: class_exp.fields.iter().map(|f| {
-                    Field { name: f.name.clone() }
+                    Field { name: f.name.clone(), value: f.value.clone() }
                 }).collect(),
                 constructor: Function {
-                    num_args: class_exp.constructor.num_args,
+                    params: class_exp.constructor.params.clone(),
                     external_values: Vec::new(),
-                    body: class_exp.constructor.body.clone()
+                    body: class_exp.constructor.body.clone(),
+                    is_generator: class_exp.constructor.is_generator,
                 },
                 methods: class_exp.methods.iter().map(|(k, v)| {
                     let function = Function {
-                        num_args: v.num_args,
+                        params: v.params.clone(),
                         external_values: Vec::new(),// Class methods never have external values
                         body: v.body.clone(),
+                        is_generator: v.is_generator,
                     };
                     (k.clone(), Ptr::from(function))
                 }).collect(),
+                static_fields: HashMap::with_capacity(class_exp.static_fields.len()),
+                static_methods: class_exp.static_methods.iter().map(|(k, v)| {
+                    let function = Function {
+                        params: v.params.clone(),
+                        external_values: Vec::new(),
+                        body: v.body.clone(),
+                        is_generator: v.is_generator,
+                    };
+                    (k.clone(), Ptr::from(Value::Function(function)))
+                }).collect(),
+                private_members: class_exp.private_members.clone(),
+                traits: class_exp.traits.clone(),
             };
             // Load class in module
-            module.classes.insert(class_exp.id, Ptr::from(class));
+            let mut class_ptr = Ptr::from(class);
+            module.classes.insert(class_exp.id, class_ptr);
+            // Initialize static fields after loading the class, so initializers can refer to it
+            for field in &class_exp.static_fields {
+                let scope = module.variables.len();
+                let result = evaluate(&field.value, module, stack_start);
+                module.variables.truncate(scope);
+                class_ptr.as_mut_ref().static_fields.insert(field.name.clone(), result?.into_ptr());
+            }
             Ok(V::Val(Value::Unit))
         },
 
-        Exp::PropertyAccess { exp, property } => {
-            let v = evaluate(exp, module, stack_start)?;
+        Exp::PropertyAccess { exp, property, from_class } => {
+            // Methods keep a reference to their self value, so it must outlive the expression
+            let v = evaluate(exp, module, stack_start)?.into_ptr();
+            check_visibility(v.as_ref(), property, *from_class)?;
             match v.as_ref().get_field(property) {
                 // Check if a field with property name exists
                 Some(ptr) => Ok(V::Ptr(ptr)),
@@ -330,50 +440,403 @@ pub fn evaluate(exp: &Exp, module: &mut Module, stack_start: usize) -> Result<V,
                 },
             }
         },
+
+        // Yield statements are evaluated by the generator, yield in other positions is rejected at compile time
+        Exp::Yield { .. } => unreachable!(),
+
+        Exp::Statement { info, exp } => {
+            debug_statement(info, module, stack_start)?;
+            evaluate(exp, module, stack_start)
+        },
+
+        Exp::Implements { exp, trait_id } => {
+            let v = evaluate(exp, module, stack_start)?;
+            let implements = match v.as_ref() {
+                Value::Object(obj) => obj.class.as_ref().implements(*trait_id),
+                Value::Class(class) => class.as_ref().implements(*trait_id),
+                _ => false,
+            };
+            Ok(V::Val(Value::Bool(implements)))
+        },
     }
 }
 
-fn subscript<'a, 'b>(element: &'a mut Value, index: &'b Value) -> Result<&'a mut Ptr<Value>, ExpressionError> {
+/// Notifies the debugger, if any, before evaluating a statement
+pub fn debug_statement(info: &Arc<StatementInfo>, module: &mut Module, stack_start: usize) -> Result<(), ExpressionError> {
+    // Debugger is moved out of the module while paused, so the expressions it evaluates are not debugged
+    if let Some(mut debugger) = module.debugger.take() {
+        let result = debugger.statement(info, stack_start, module);
+        module.debugger = Some(debugger);
+        result?;
+    }
+    Ok(())
+}
+
+fn subscript<'a>(element: &'a mut Value, index: &Value) -> Result<&'a mut Ptr<Value>, ExpressionError> {
     match (element, index) {
         (Value::List(values), Value::Int(i)) => {
-            values.get_mut(*i as usize).ok_or(ExpressionError::ListIndexOutofRange)
+            let index = resolve_index(*i, values.len(), Type::List)?;
+            Ok(&mut values[index])
+        },
+        (Value::Map(map), Value::String(key)) => {
+            map.get_mut(key).ok_or_else(|| ExpressionError::KeyNotFound(key.clone()))
         },
+        (Value::String(_), Value::Int(_)) => Err(ExpressionError::ItemAssignment(Type::String)),
         (v, Value::Int(_)) => Err(ExpressionError::NotSubscriptable(v.get_type())),
         (v, i) => Err(ExpressionError::IndexTypeError(v.get_type(), i.get_type()))
     }
 }
 
-fn call_function(fun: &Function, args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
-    if fun.num_args == args.len() {
-        let function_stack_start = module.variables.len();
-        // Push external values to variable stack
-        module.variables.extend_from_slice(&fun.external_values);
-        // Push function args to variable stack
-        module.variables.extend(args);
-        let result = evaluate(&fun.body, module, function_stack_start);
-        module.variables.truncate(function_stack_start);
-        Ok(result?)
+/// Negative indices count from the end
+fn resolve_index(index: i32, len: usize, t: Type) -> Result<usize, ExpressionError> {
+    let resolved = if index < 0 { index as i64 + len as i64 } else { index as i64 };
+    if resolved < 0 || resolved >= len as i64 {
+        Err(ExpressionError::IndexOutOfRange(t, index, len))
+    } else {
+        Ok(resolved as usize)
+    }
+}
+
+/// Computes the indices selected by a slice, following Python semantics:
+/// negative bounds count from the end, out of range bounds are clamped
+/// and a negative step traverses the sequence backwards
+fn slice_indices(len: usize, start: Option<i32>, end: Option<i32>, step: Option<i32>) -> Result<Vec<usize>, ExpressionError> {
+    let len = len as i64;
+    let step = step.unwrap_or(1) as i64;
+    if step == 0 {
+        return Err(ExpressionError::ZeroSliceStep)
+    }
+    // Bounds are clamped between lower and upper
+    let (lower, upper) = if step > 0 { (0, len) } else { (-1, len - 1) };
+    let clamp = |bound: i32| {
+        let bound = bound as i64;
+        let bound = if bound < 0 { bound + len } else { bound };
+        bound.clamp(lower, upper)
+    };
+    let start = start.map_or(if step > 0 { lower } else { upper }, clamp);
+    let end = end.map_or(if step > 0 { upper } else { lower }, clamp);
+    let mut indices = Vec::new();
+    let mut i = start;
+    while (step > 0 && i < end) || (step < 0 && i > end) {
+        indices.push(i as usize);
+        i += step;
+    }
+    Ok(indices)
+}
+
+/// Creates a new object, evaluating the field initializers with `self` as the only variable in scope
+fn create_object(class: Ptr<Class>, module: &mut Module) -> Result<Ptr<Value>, ExpressionError> {
+    let mut fields = HashMap::with_capacity(class.as_ref().fields.len());
+    for field in &class.as_ref().fields {
+        fields.insert(field.name.clone(), Ptr::unit());
+    }
+    let mut object = Ptr::from(Value::Object(Object { class, fields }));
+    for field in class.as_ref().fields.iter() {
+        if let Some(value) = &field.value {
+            let object_stack_start = module.variables.len();
+            module.variables.push(object);
+            let result = evaluate(value, module, object_stack_start);
+            module.variables.truncate(object_stack_start);
+            let ptr = result?.into_ptr();
+            if let Value::Object(obj) = object.as_mut_ref() {
+                obj.fields.insert(field.name.clone(), ptr);
+            }
+        }
+    }
+    Ok(object)
+}
+
+/// Checks that a private field or method is accessed from the class body
+fn check_visibility(value: &Value, property: &str, from_class: Option<usize>) -> Result<(), ExpressionError> {
+    let class = match value {
+        Value::Object(obj) => obj.class,
+        Value::Class(class) => *class,
+        _ => return Ok(()),
+    };
+    if class.as_ref().is_accessible(property, from_class) {
+        Ok(())
     } else {
-        Err(ExpressionError::WrongArgumentsNumber(fun.num_args, args.len()))
+        Err(ExpressionError::PrivateMember(class.as_ref().name.clone(), property.to_owned()))
     }
 }
 
-fn call_method(fun: &Function, self_ptr: Ptr<Value>, args: &Vec<Exp>,  module: &mut Module, stack_start: usize) -> Result<V, ExpressionError> {
+/// Calls a callable value. Allows built-in code to call functions, methods and classes.
+pub fn call_value(fun: &Value, args: Vec<Ptr<Value>>, named_args: Vec<(String, Ptr<Value>)>, module: &mut Module) -> Result<V, ExpressionError> {
+    match fun {
+        // Function call
+        Value::Function(fun) => {
+            call_function(fun, args, named_args, module)
+        },
+        // Method call
+        Value::Method(method) => {
+            call_method(method.function.as_ref(), method.self_value, args, named_args, module)
+        },
+        // Built-in function call
+        Value::BuiltInFunction(fun) => {
+            check_no_named_args(&named_args)?;
+            fun.call(args, module)
+        },
+        // Built-in method call
+        Value::BuiltInMethod(method) => {
+            check_no_named_args(&named_args)?;
+            let mut builtin_args = Vec::with_capacity(args.len() + 1);
+            builtin_args.push(method.self_value);
+            builtin_args.extend(args);
+            method.function.call(builtin_args, module)
+        }
+        // Class constructor call
+        Value::Class(class) => {
+            let object = create_object(*class, module)?;
+            // Call constructor
+            call_method(&class.as_ref().constructor, object, args, named_args, module)?;
+            Ok(V::Ptr(object))
+        },
+        _ => Err(ExpressionError::ValueNotCallable(fun.get_type()))
+    }
+}
+
+fn call_function(fun: &Function, args: Vec<Ptr<Value>>, named_args: Vec<(String, Ptr<Value>)>, module: &mut Module) -> Result<V, ExpressionError> {
+    let bound_args = bind_arguments(&fun.params, args, named_args)?;
+    if fun.is_generator {
+        // Generator body is evaluated lazily
+        let generator = Generator::new(fun.clone(), bound_args);
+        return Ok(V::Val(Value::Generator(generator)))
+    }
+    call_bound_function(fun, bound_args, module)
+}
+
+/// Evaluates a function body with arguments already bound to its parameters
+fn call_bound_function(fun: &Function, bound_args: Vec<Option<Ptr<Value>>>, module: &mut Module) -> Result<V, ExpressionError> {
+    let function_stack_start = module.variables.len();
+    if let Some(debugger) = module.debugger.as_mut() {
+        debugger.enter_function(function_stack_start)
+    }
+    // Push external values to variable stack
+    module.variables.extend_from_slice(&fun.external_values);
+    // Push function args to variable stack, then evaluate function body
+    let result = push_arguments(&fun.params, bound_args, module, function_stack_start)
+        .and_then(|_| evaluate(&fun.body, module, function_stack_start));
+    module.variables.truncate(function_stack_start);
+    if let Some(debugger) = module.debugger.as_mut() {
+        debugger.exit_function()
+    }
+    result
+}
+
+fn call_method(fun: &Function, self_ptr: Ptr<Value>, args: Vec<Ptr<Value>>, named_args: Vec<(String, Ptr<Value>)>, module: &mut Module) -> Result<V, ExpressionError> {
     let mut args_v = Vec::with_capacity(args.len() + 1);
     // Push self reference as first method argument
     args_v.push(self_ptr);
-    for arg in args {
-        let v = evaluate(&arg, module, stack_start)?;
-        args_v.push(v.into_ptr())
+    args_v.extend(args);
+    call_function(fun, args_v, named_args, module)
+}
+
+/// Assigns positional and named arguments to function parameters.
+/// Parameters with no argument are left empty and must have a default value.
+fn bind_arguments(params: &[ParamExp], args: Vec<Ptr<Value>>, named_args: Vec<(String, Ptr<Value>)>) -> Result<Vec<Option<Ptr<Value>>>, ExpressionError> {
+    let variadic = params.last().is_some_and(|p| p.variadic);
+    let num_positional = if variadic { params.len() - 1 } else { params.len() };
+    if args.len() > num_positional && !variadic {
+        return Err(ExpressionError::TooManyArguments(num_positional, args.len()))
+    }
+    let mut bound_args: Vec<Option<Ptr<Value>>> = vec![None; params.len()];
+    let mut rest = Vec::new();
+    for (i, arg) in args.into_iter().enumerate() {
+        if i < num_positional {
+            bound_args[i] = Some(arg)
+        } else {
+            rest.push(arg)
+        }
+    }
+    for (name, arg) in named_args {
+        match params[..num_positional].iter().position(|p| p.name == name) {
+            Some(i) if bound_args[i].is_some() => return Err(ExpressionError::DuplicateArgument(name)),
+            Some(i) => bound_args[i] = Some(arg),
+            None => return Err(ExpressionError::UnexpectedNamedArgument(name)),
+        }
     }
-    call_function(fun, args_v, module)
+    if variadic {
+        bound_args[num_positional] = Some(Ptr::from(Value::List(rest)))
+    }
+    Ok(bound_args)
+}
+
+/// Pushes bound arguments to the variable stack, evaluating default values for missing ones
+pub fn push_arguments(params: &[ParamExp], bound_args: Vec<Option<Ptr<Value>>>, module: &mut Module, stack_start: usize) -> Result<(), ExpressionError> {
+    for (param, arg) in params.iter().zip(bound_args) {
+        let ptr = match (arg, &param.default) {
+            (Some(ptr), _) => ptr,
+            (None, Some(default)) => {
+                let scope = module.variables.len();
+                let result = evaluate(default, module, stack_start);
+                module.variables.truncate(scope);
+                result?.into_ptr()
+            },
+            (None, None) => return Err(ExpressionError::MissingArgument(param.name.clone())),
+        };
+        module.variables.push(ptr);
+    }
+    Ok(())
 }
 
-fn evaluate_list(exps: &Vec<Exp>,  module: &mut Module, stack_start: usize) -> Result<Vec<Ptr<Value>>, ExpressionError> {
+fn check_no_named_args(named_args: &[(String, Ptr<Value>)]) -> Result<(), ExpressionError> {
+    match named_args.first() {
+        Some((name, _)) => Err(ExpressionError::UnexpectedNamedArgument(name.clone())),
+        None => Ok(()),
+    }
+}
+
+fn evaluate_list(exps: &[Exp], module: &mut Module, stack_start: usize) -> Result<Vec<Ptr<Value>>, ExpressionError> {
     let mut list = Vec::with_capacity(exps.len());
     for arg in exps {
-        let v = evaluate(&arg, module, stack_start)?;
+        let v = evaluate(arg, module, stack_start)?;
         list.push(v.into_ptr())
     }
     Ok(list)
 }
+
+fn evaluate_named_list(exps: &[(String, Exp)], module: &mut Module, stack_start: usize) -> Result<Vec<(String, Ptr<Value>)>, ExpressionError> {
+    let mut list = Vec::with_capacity(exps.len());
+    for (name, arg) in exps {
+        let v = evaluate(arg, module, stack_start)?;
+        list.push((name.clone(), v.into_ptr()))
+    }
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval, ProgramError};
+    use crate::intermediate::constant::Type;
+    use super::ExpressionError;
+
+    fn runtime_error(text: &str) -> ExpressionError {
+        match eval(text) {
+            Err(ProgramError::RuntimeError(e)) => e,
+            result => panic!("expected a runtime error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn default_parameters() {
+        assert_eq!(eval("fn f(a, b = 2) { a + b }; f(1)").unwrap(), "3");
+        assert_eq!(eval("fn f(a, b = 2) { a + b }; f(1, 5)").unwrap(), "6");
+        // Defaults can refer to the previous parameters
+        assert_eq!(eval("fn f(a, b = a * 10) { b }; f(4)").unwrap(), "40");
+    }
+
+    #[test]
+    fn named_arguments() {
+        assert_eq!(eval("fn f(a, b = 2, c = 3) { [a, b, c] }; f(1, c: 30)").unwrap(), "[1, 2, 30]");
+        assert_eq!(eval("fn f(a, b) { a - b }; f(b: 1, a: 5)").unwrap(), "4");
+        assert_eq!(eval("class P { let x let y fn self(x, y = 0) { self.x = x; self.y = y } }; let p = P(y: 2, x: 1); p.y").unwrap(), "2");
+    }
+
+    #[test]
+    fn variadic_parameters() {
+        assert_eq!(eval("fn f(a, ...rest) { rest }; f(1, 2, 3)").unwrap(), "[2, 3]");
+        assert_eq!(eval("fn f(a, ...rest) { rest }; f(1)").unwrap(), "[]");
+        assert_eq!(eval("let g = |...xs| { xs }; g(1, 2)").unwrap(), "[1, 2]");
+    }
+
+    #[test]
+    fn wrong_arguments() {
+        assert!(matches!(runtime_error("fn f(a) { a }; f(1, 2)"), ExpressionError::TooManyArguments(1, 2)));
+        assert!(matches!(runtime_error("fn f(a, b) { a }; f(1)"), ExpressionError::MissingArgument(name) if name == "b"));
+        assert!(matches!(runtime_error("fn f(a) { a }; f(b: 1)"), ExpressionError::UnexpectedNamedArgument(name) if name == "b"));
+        assert!(matches!(runtime_error("fn f(a) { a }; f(1, a: 2)"), ExpressionError::DuplicateArgument(name) if name == "a"));
+        // Named arguments cannot bind the variadic parameter
+        assert!(matches!(runtime_error("fn f(...a) { a }; f(a: 1)"), ExpressionError::UnexpectedNamedArgument(_)));
+        assert!(matches!(runtime_error("[1].len(l: 1)"), ExpressionError::UnexpectedNamedArgument(_)));
+    }
+
+    #[test]
+    fn static_members() {
+        let counter = "class Counter {
+            static let created = 0
+            let count
+            static fn make(start) { Counter.created = Counter.created + 1; let c = Counter(); c.count = start; c }
+        };";
+        assert_eq!(eval(&format!("{} let c = Counter.make(5); c.count", counter)).unwrap(), "5");
+        assert_eq!(eval(&format!("{} Counter.make(1); Counter.make(2); Counter.created", counter)).unwrap(), "2");
+        // Static fields are initialized once, when the class is defined
+        assert_eq!(eval("let n = 1; class A { static let v = n * 2 }; n = 5; A.v").unwrap(), "2");
+    }
+
+    #[test]
+    fn static_and_instance_members_are_separate() {
+        assert!(matches!(runtime_error("class A { static fn f() { 1 } }; let a = A(); a.f()"), ExpressionError::NoSuchFieldOrMethod(_)));
+        assert!(matches!(runtime_error("class A { fn f() { 1 } }; A.f()"), ExpressionError::NoSuchFieldOrMethod(_)));
+        assert!(matches!(runtime_error("class A { static let n = 1 }; A.m"), ExpressionError::NoSuchFieldOrMethod(_)));
+    }
+
+    #[test]
+    fn field_initializers() {
+        assert_eq!(eval("class A { let x = 1 let y }; let a = A(); [a.x, a.y]").unwrap(), "[1, unit]");
+        // Initializers are evaluated for each object, before the constructor
+        assert_eq!(eval("class A { let l = [] fn self() { self.l.push(1) } }; let a = A(); a.l.push(2); let b = A(); [a.l, b.l]").unwrap(), "[[1, 2], [1]]");
+        assert_eq!(eval("class A { let x = 2 let y = self.x * 3 }; let a = A(); a.y").unwrap(), "6");
+    }
+
+    #[test]
+    fn members_are_public_by_default() {
+        assert_eq!(eval("class A { let x = 1 fn f() { 2 } }; let a = A(); a.x = 3; a.x + a.f()").unwrap(), "5");
+        assert!(matches!(eval("class A { pub let x = 1 }"), Err(ProgramError::SyntaxError(_))));
+    }
+
+    #[test]
+    fn private_members() {
+        let class = "class A {
+            private let x = 1
+            private static let n = 2
+            private fn f() { self.x * 10 }
+            fn g() { self.f() + A.n }
+        };";
+        assert_eq!(eval(&format!("{} let a = A(); a.g()", class)).unwrap(), "12");
+        assert!(matches!(runtime_error(&format!("{} let a = A(); a.x", class)), ExpressionError::PrivateMember(_, name) if name == "x"));
+        assert!(matches!(runtime_error(&format!("{} let a = A(); a.x = 2", class)), ExpressionError::PrivateMember(_, name) if name == "x"));
+        assert!(matches!(runtime_error(&format!("{} let a = A(); a.f()", class)), ExpressionError::PrivateMember(_, name) if name == "f"));
+        assert!(matches!(runtime_error(&format!("{} A.n", class)), ExpressionError::PrivateMember(_, name) if name == "n"));
+    }
+
+    #[test]
+    fn undeclared_fields() {
+        assert!(matches!(runtime_error("class A { let x }; let a = A(); a.y = 3"), ExpressionError::UndeclaredField(_, name) if name == "y"));
+    }
+
+    #[test]
+    fn implements_operator() {
+        let program = "trait Drawable { fn draw(self); }; class C implements Drawable { fn draw() { 1 } }; class D { fn draw() { 2 } };";
+        assert_eq!(eval(&format!("{} [C() implements Drawable, C implements Drawable]", program)).unwrap(), "[true, true]");
+        // Classes implement a trait only when they declare it
+        assert_eq!(eval(&format!("{} [D() implements Drawable, 1 implements Drawable]", program)).unwrap(), "[false, false]");
+    }
+
+    #[test]
+    fn negative_indices() {
+        assert_eq!(eval("let l = [1, 2, 3]; [l[0], l[-1], l[-3]]").unwrap(), "[1, 3, 1]");
+        assert_eq!(eval("let s = \"héllo\"; [s[1], s[-1]]").unwrap(), "[é, o]");
+        assert_eq!(eval("let l = [1, 2, 3]; l[-1] = 9; l[-3] = 7; l").unwrap(), "[7, 2, 9]");
+    }
+
+    #[test]
+    fn slices() {
+        assert_eq!(eval("let l = [0, 1, 2, 3, 4]; [l[1:3], l[:-1], l[3:], l[:]]").unwrap(), "[[1, 2], [0, 1, 2, 3], [3, 4], [0, 1, 2, 3, 4]]");
+        assert_eq!(eval("let l = [0, 1, 2, 3, 4]; [l[::2], l[::-1], l[3:0:-2], l[-2::-1]]").unwrap(), "[[0, 2, 4], [4, 3, 2, 1, 0], [3, 1], [3, 2, 1, 0]]");
+        // Out of range bounds are clamped
+        assert_eq!(eval("let l = [0, 1, 2]; [l[-10:10], l[5:], l[2:1]]").unwrap(), "[[0, 1, 2], [], []]");
+        assert_eq!(eval("let s = \"héllo\"; [s[1:3], s[::-1], s[::2]]").unwrap(), "[él, olléh, hlo]");
+    }
+
+    #[test]
+    fn subscript_errors() {
+        assert!(matches!(runtime_error("[1, 2][2]"), ExpressionError::IndexOutOfRange(Type::List, 2, 2)));
+        assert!(matches!(runtime_error("[1, 2][-3]"), ExpressionError::IndexOutOfRange(Type::List, -3, 2)));
+        assert!(matches!(runtime_error("let l = [1]; l[-2] = 0"), ExpressionError::IndexOutOfRange(Type::List, -2, 1)));
+        assert!(matches!(runtime_error("\"ab\"[2]"), ExpressionError::IndexOutOfRange(Type::String, 2, 2)));
+        assert!(matches!(runtime_error("let s = \"ab\"; s[0] = \"c\""), ExpressionError::ItemAssignment(Type::String)));
+        assert!(matches!(runtime_error("[1, 2][::0]"), ExpressionError::ZeroSliceStep));
+        assert!(matches!(runtime_error("[1, 2][\"a\":]"), ExpressionError::IndexTypeError(Type::List, Type::String)));
+        assert!(matches!(runtime_error("1[0:1]"), ExpressionError::NotSubscriptable(Type::Int)));
+    }
+}
@@ -1,18 +1,22 @@
-use std::convert::TryInto;
+use std::env;
 use std::io::{self, Write};
 
 use crate::intermediate::constant::Type;
-use crate::intermediate::exp::{Exp, BuiltInFunction};
+use crate::intermediate::exp::{Exp, BuiltInFunction, ParamExp};
 
+use super::builtins::{assert, convert, fs, json, list, map, math, random, regex, time};
 use super::pointer::Ptr;
 use super::executor::ExpressionError;
+use super::iterator::Iter;
+use super::module::Module;
 use super::value::{Value, V};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Function {
-    pub num_args: usize,
+    pub params: Vec<ParamExp>,
     pub external_values: Vec<Ptr<Value>>,
-    pub body: Exp
+    pub body: Exp,
+    pub is_generator: bool,
 }
 
 #[derive(Debug)]
@@ -28,95 +32,338 @@ pub struct BuiltInMethod {
 }
 
 impl BuiltInFunction {
-    pub fn call(&self, args: Vec<Ptr<Value>>) -> Result<V, ExpressionError> {
-        if args.len() != self.num_args() {
-            return Err(ExpressionError::WrongArgumentsNumber(self.num_args(), args.len()))
+    pub fn call(&self, args: Vec<Ptr<Value>>, module: &mut Module) -> Result<V, ExpressionError> {
+        let (min_args, max_args) = self.num_args();
+        if args.len() < min_args {
+            return Err(ExpressionError::WrongArgumentsNumber(min_args, args.len()))
+        }
+        if args.len() > max_args {
+            return Err(ExpressionError::TooManyArguments(max_args, args.len()))
         }
         match self {
             BuiltInFunction::Print => {
-                print!("{}", args[0]);
+                module.print(args[0].to_string());
                 Ok(V::Ptr(Ptr::unit()))
             }
 
             BuiltInFunction::Println => {
                 match args[0].as_ref() {
-                    Value::String(s) => println!("{}", s),
-                    v => println!("{}", v),
+                    Value::String(s) => module.print(format!("{}\n", s)),
+                    v => module.print(format!("{}\n", v)),
                 }
                 Ok(V::Ptr(Ptr::unit()))
             }
 
             BuiltInFunction::Input => {
-                print!("{}", args[0]);
-                io::stdout().flush().unwrap();
+                module.print(args[0].to_string());
                 let mut input = String::new();
-                io::stdin().read_line(&mut input).expect("error: unable to read user input");
+                io::stdout().flush()
+                    .and_then(|_| io::stdin().read_line(&mut input))
+                    .map_err(|e| ExpressionError::IOError(e.to_string()))?;
                 let len = input.trim_end_matches(&['\r', '\n'][..]).len(); // Remove final end of line
                 input.truncate(len);
                 Ok(V::Val(Value::String(input)))
             }
 
-            BuiltInFunction::ListLength => {
+            BuiltInFunction::Args => {
+                let args = module.args.iter().map(|arg| Ptr::from(Value::String(arg.clone()))).collect();
+                Ok(V::Val(Value::List(args)))
+            }
+
+            BuiltInFunction::Env => {
                 match args[0].as_ref() {
-                    Value::List(list) => {
-                        Ok(V::Val(Value::Int(list.len().try_into().unwrap())))
+                    // Returns unit when the variable is not set
+                    Value::String(name) => match env::var(name) {
+                        Ok(value) => Ok(V::Val(Value::String(value))),
+                        Err(_) => Ok(V::Ptr(Ptr::unit())),
                     },
-                    v => Err(ExpressionError::UnexpectedType(Type::List, v.get_type()))
+                    v => Err(ExpressionError::UnexpectedType(Type::String, v.get_type())),
                 }
             }
 
-            BuiltInFunction::ListPush => {
-                match args[0].clone().as_mut_ref() {
-                    Value::List(list) => {
-                        list.push(args[1]);
-                        Ok(V::Ptr(Ptr::unit()))
-                    },
-                    v => Err(ExpressionError::UnexpectedType(Type::List, v.get_type()))
+            BuiltInFunction::Exit => {
+                match args.first().map(|code| code.as_ref()) {
+                    Some(Value::Int(code)) => Err(ExpressionError::Exit(*code)),
+                    Some(v) => Err(ExpressionError::UnexpectedType(Type::Int, v.get_type())),
+                    None => Err(ExpressionError::Exit(0)),
                 }
             }
 
-            BuiltInFunction::ListPop => {
-                match args[0].clone().as_mut_ref() {
-                    Value::List(list) => {
-                        match list.pop() {
-                            Some(v) => Ok(V::Ptr(v)),
-                            None => Err(ExpressionError::ListIndexOutofRange),
-                        }
-                    },
-                    v => Err(ExpressionError::UnexpectedType(Type::List, v.get_type()))
+            BuiltInFunction::TypeOf => convert::type_of(args),
+            BuiltInFunction::ToInt => convert::int(args),
+            BuiltInFunction::ToFloat => convert::float(args),
+            BuiltInFunction::ToStr => convert::str(args),
+            BuiltInFunction::ToBool => convert::bool(args),
+            BuiltInFunction::IsInstance => convert::is_instance(args),
+            BuiltInFunction::Assert => assert::assert(args),
+            BuiltInFunction::AssertEq => assert::assert_eq(args),
+
+            BuiltInFunction::ListLength => list::len(args),
+            BuiltInFunction::ListPush => list::push(args),
+            BuiltInFunction::ListPop => list::pop(args),
+            BuiltInFunction::ListRemove => list::remove(args),
+            BuiltInFunction::ListMap => list::map(args, module),
+            BuiltInFunction::ListFilter => list::filter(args, module),
+            BuiltInFunction::ListReduce => list::reduce(args, module),
+            BuiltInFunction::ListFind => list::find(args, module),
+            BuiltInFunction::ListAny => list::any(args, module),
+            BuiltInFunction::ListAll => list::all(args, module),
+            BuiltInFunction::ListSort => list::sort(args),
+            BuiltInFunction::ListSortBy => list::sort_by(args, module),
+            BuiltInFunction::ListReverse => list::reverse(args),
+            BuiltInFunction::ListIndexOf => list::index_of(args),
+            BuiltInFunction::ListContains => list::contains(args),
+            BuiltInFunction::ListSlice => list::slice(args),
+            BuiltInFunction::ListJoin => list::join(args),
+            BuiltInFunction::ListInsert => list::insert(args),
+            BuiltInFunction::ListExtend => list::extend(args, module),
+            BuiltInFunction::ListClear => list::clear(args),
+
+            BuiltInFunction::MapLength => map::len(args),
+            BuiltInFunction::MapKeys => map::keys(args),
+            BuiltInFunction::MapValues => map::values(args),
+            BuiltInFunction::MapContains => map::contains(args),
+            BuiltInFunction::MapGet => map::get(args),
+            BuiltInFunction::MapRemove => map::remove(args),
+
+            BuiltInFunction::Iter => {
+                match args[0].as_ref() {
+                    // Iterators are returned as they are
+                    Value::Iterator(_) => Ok(V::Ptr(args[0])),
+                    _ => Ok(V::Val(Value::Iterator(Iter::new(args[0], module)?))),
                 }
             }
 
-            BuiltInFunction::ListRemove => {
-                match args[0].clone().as_mut_ref() {
-                    Value::List(list) => {
-                        match args[1].as_ref() {
-                            Value::Int(i) => {
-                                let index = (*i).try_into().unwrap();
-                                if list.len() > (*i).try_into().unwrap() {
-                                    Ok(V::Ptr(list.remove(index)))
-                                } else {
-                                    Err(ExpressionError::ListIndexOutofRange)
-                                }
-                            },
-                            i => Err(ExpressionError::IndexTypeError(Type::List, i.get_type())),
-                        }
+            BuiltInFunction::IterNext => {
+                let next = Iter::new(args[0], module)?.next(module)?;
+                Ok(next.map_or(V::Val(Value::Done), V::Ptr))
+            }
+
+            BuiltInFunction::IterMap => {
+                let iter = Box::new(Iter::new(args[0], module)?);
+                Ok(V::Val(Value::Iterator(Iter::Map { iter, fun: args[1] })))
+            }
+
+            BuiltInFunction::IterFilter => {
+                let iter = Box::new(Iter::new(args[0], module)?);
+                Ok(V::Val(Value::Iterator(Iter::Filter { iter, fun: args[1] })))
+            }
+
+            BuiltInFunction::IterTake => {
+                match args[1].as_ref() {
+                    Value::Int(n) => {
+                        let iter = Box::new(Iter::new(args[0], module)?);
+                        let remaining = (*n).max(0) as usize;
+                        Ok(V::Val(Value::Iterator(Iter::Take { iter, remaining })))
                     },
-                    v => Err(ExpressionError::UnexpectedType(Type::List, v.get_type()))
+                    v => Err(ExpressionError::UnexpectedType(Type::Int, v.get_type()))
                 }
             }
+
+            BuiltInFunction::IterZip => {
+                let first = Box::new(Iter::new(args[0], module)?);
+                let second = Box::new(Iter::new(args[1], module)?);
+                Ok(V::Val(Value::Iterator(Iter::Zip { first, second })))
+            }
+
+            BuiltInFunction::IterEnumerate => {
+                let iter = Box::new(Iter::new(args[0], module)?);
+                Ok(V::Val(Value::Iterator(Iter::Enumerate { iter, index: 0 })))
+            }
+
+            BuiltInFunction::IterCollect => {
+                let list = Iter::new(args[0], module)?.collect(module)?;
+                Ok(V::Val(Value::List(list)))
+            }
+
+            BuiltInFunction::MathSqrt => math::float_fn(args, f32::sqrt),
+            BuiltInFunction::MathPow => math::pow(args),
+            BuiltInFunction::MathAbs => math::abs(args),
+            BuiltInFunction::MathFloor => math::int_fn(args, f32::floor),
+            BuiltInFunction::MathCeil => math::int_fn(args, f32::ceil),
+            BuiltInFunction::MathRound => math::int_fn(args, f32::round),
+            BuiltInFunction::MathMin => math::min(args),
+            BuiltInFunction::MathMax => math::max(args),
+            BuiltInFunction::MathSin => math::float_fn(args, f32::sin),
+            BuiltInFunction::MathCos => math::float_fn(args, f32::cos),
+            BuiltInFunction::MathTan => math::float_fn(args, f32::tan),
+            BuiltInFunction::MathAsin => math::float_fn(args, f32::asin),
+            BuiltInFunction::MathAcos => math::float_fn(args, f32::acos),
+            BuiltInFunction::MathAtan => math::float_fn(args, f32::atan),
+            BuiltInFunction::MathAtan2 => math::atan2(args),
+            BuiltInFunction::MathLog => math::log(args),
+            BuiltInFunction::MathLog2 => math::float_fn(args, f32::log2),
+            BuiltInFunction::MathLog10 => math::float_fn(args, f32::log10),
+            BuiltInFunction::MathExp => math::float_fn(args, f32::exp),
+
+            BuiltInFunction::FsReadFile => fs::read_file(args),
+            BuiltInFunction::FsWriteFile => fs::write_file(args),
+            BuiltInFunction::FsAppendFile => fs::append_file(args),
+            BuiltInFunction::FsExists => fs::exists(args),
+            BuiltInFunction::FsListDir => fs::list_dir(args),
+            BuiltInFunction::FsMkdir => fs::mkdir(args),
+            BuiltInFunction::FsRemove => fs::remove(args),
+            BuiltInFunction::FsRemoveDirAll => fs::remove_dir_all(args),
+            BuiltInFunction::FsJoin => fs::join(args),
+
+            BuiltInFunction::JsonParse => json::parse(args),
+            BuiltInFunction::JsonStringify => json::stringify(args),
+
+            BuiltInFunction::TimeNow => time::now(args),
+            BuiltInFunction::TimeClock => time::clock(args),
+            BuiltInFunction::TimeSleep => time::sleep(args),
+            BuiltInFunction::RandomSeed => random::seed(args, module),
+            BuiltInFunction::RandomInt => random::int(args, module),
+            BuiltInFunction::RandomFloat => random::float(args, module),
+            BuiltInFunction::RandomChoice => random::choice(args, module),
+            BuiltInFunction::RandomShuffle => random::shuffle(args, module),
+
+            BuiltInFunction::RegexCompile => regex::compile(args),
+            BuiltInFunction::RegexIsMatch => regex::is_match(args),
+            BuiltInFunction::RegexFind => regex::find(args),
+            BuiltInFunction::RegexFindAll => regex::find_all(args),
+            BuiltInFunction::RegexCaptures => regex::captures(args),
+            BuiltInFunction::RegexReplace => regex::replace(args),
+            BuiltInFunction::RegexSplit => regex::split(args),
         }
     }
 
-    fn num_args(&self) -> usize {
+    /// Minimum and maximum number of arguments, trailing arguments may be optional
+    pub fn num_args(&self) -> (usize, usize) {
         match self {
-            BuiltInFunction::Print => 1,
-            BuiltInFunction::Println => 1,
-            BuiltInFunction::Input => 1,
-            BuiltInFunction::ListLength => 1,
-            BuiltInFunction::ListPush => 2,
-            BuiltInFunction::ListPop => 1,
-            BuiltInFunction::ListRemove => 2,
+            BuiltInFunction::Print => (1, 1),
+            BuiltInFunction::Println => (1, 1),
+            BuiltInFunction::Input => (1, 1),
+            BuiltInFunction::Args => (0, 0),
+            BuiltInFunction::Env => (1, 1),
+            BuiltInFunction::Exit => (0, 1),
+            BuiltInFunction::TypeOf => (1, 1),
+            BuiltInFunction::ToInt => (1, 1),
+            BuiltInFunction::ToFloat => (1, 1),
+            BuiltInFunction::ToStr => (1, 1),
+            BuiltInFunction::ToBool => (1, 1),
+            BuiltInFunction::IsInstance => (2, 2),
+            BuiltInFunction::Assert => (1, 2),
+            BuiltInFunction::AssertEq => (2, 3),
+            BuiltInFunction::ListLength => (1, 1),
+            BuiltInFunction::ListPush => (2, 2),
+            BuiltInFunction::ListPop => (1, 1),
+            BuiltInFunction::ListRemove => (2, 2),
+            BuiltInFunction::ListMap => (2, 2),
+            BuiltInFunction::ListFilter => (2, 2),
+            BuiltInFunction::ListReduce => (2, 3),
+            BuiltInFunction::ListFind => (2, 2),
+            BuiltInFunction::ListAny => (2, 2),
+            BuiltInFunction::ListAll => (2, 2),
+            BuiltInFunction::ListSort => (1, 1),
+            BuiltInFunction::ListSortBy => (2, 2),
+            BuiltInFunction::ListReverse => (1, 1),
+            BuiltInFunction::ListIndexOf => (2, 2),
+            BuiltInFunction::ListContains => (2, 2),
+            BuiltInFunction::ListSlice => (2, 3),
+            BuiltInFunction::ListJoin => (1, 2),
+            BuiltInFunction::ListInsert => (3, 3),
+            BuiltInFunction::ListExtend => (2, 2),
+            BuiltInFunction::ListClear => (1, 1),
+            BuiltInFunction::MapLength => (1, 1),
+            BuiltInFunction::MapKeys => (1, 1),
+            BuiltInFunction::MapValues => (1, 1),
+            BuiltInFunction::MapContains => (2, 2),
+            BuiltInFunction::MapGet => (2, 3),
+            BuiltInFunction::MapRemove => (2, 2),
+            BuiltInFunction::Iter => (1, 1),
+            BuiltInFunction::IterNext => (1, 1),
+            BuiltInFunction::IterMap => (2, 2),
+            BuiltInFunction::IterFilter => (2, 2),
+            BuiltInFunction::IterTake => (2, 2),
+            BuiltInFunction::IterZip => (2, 2),
+            BuiltInFunction::IterEnumerate => (1, 1),
+            BuiltInFunction::IterCollect => (1, 1),
+            BuiltInFunction::MathSqrt => (1, 1),
+            BuiltInFunction::MathPow => (2, 2),
+            BuiltInFunction::MathAbs => (1, 1),
+            BuiltInFunction::MathFloor => (1, 1),
+            BuiltInFunction::MathCeil => (1, 1),
+            BuiltInFunction::MathRound => (1, 1),
+            BuiltInFunction::MathMin => (1, usize::MAX),
+            BuiltInFunction::MathMax => (1, usize::MAX),
+            BuiltInFunction::MathSin => (1, 1),
+            BuiltInFunction::MathCos => (1, 1),
+            BuiltInFunction::MathTan => (1, 1),
+            BuiltInFunction::MathAsin => (1, 1),
+            BuiltInFunction::MathAcos => (1, 1),
+            BuiltInFunction::MathAtan => (1, 1),
+            BuiltInFunction::MathAtan2 => (2, 2),
+            BuiltInFunction::MathLog => (1, 2),
+            BuiltInFunction::MathLog2 => (1, 1),
+            BuiltInFunction::MathLog10 => (1, 1),
+            BuiltInFunction::MathExp => (1, 1),
+            BuiltInFunction::FsReadFile => (1, 1),
+            BuiltInFunction::FsWriteFile => (2, 2),
+            BuiltInFunction::FsAppendFile => (2, 2),
+            BuiltInFunction::FsExists => (1, 1),
+            BuiltInFunction::FsListDir => (1, 1),
+            BuiltInFunction::FsMkdir => (1, 1),
+            BuiltInFunction::FsRemove => (1, 1),
+            BuiltInFunction::FsRemoveDirAll => (1, 1),
+            BuiltInFunction::FsJoin => (1, usize::MAX),
+            BuiltInFunction::JsonParse => (1, 1),
+            BuiltInFunction::JsonStringify => (1, 2),
+            BuiltInFunction::TimeNow => (0, 0),
+            BuiltInFunction::TimeClock => (0, 0),
+            BuiltInFunction::TimeSleep => (1, 1),
+            BuiltInFunction::RandomSeed => (1, 1),
+            BuiltInFunction::RandomInt => (2, 2),
+            BuiltInFunction::RandomFloat => (0, 0),
+            BuiltInFunction::RandomChoice => (1, 1),
+            BuiltInFunction::RandomShuffle => (1, 1),
+            BuiltInFunction::RegexCompile => (1, 1),
+            BuiltInFunction::RegexIsMatch => (2, 2),
+            BuiltInFunction::RegexFind => (2, 2),
+            BuiltInFunction::RegexFindAll => (2, 2),
+            BuiltInFunction::RegexCaptures => (2, 2),
+            BuiltInFunction::RegexReplace => (3, 3),
+            BuiltInFunction::RegexSplit => (2, 2),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::{eval, run_program, ProgramError};
+    use crate::compiler::checker::TypeChecker;
+    use crate::compiler::context::CompilerContext;
+    use crate::intermediate::constant::Type;
+    use crate::runtime::executor::ExpressionError;
+    use crate::runtime::module::Module;
+
+    #[test]
+    fn args() {
+        let mut module = Module { args: vec!["--verbose".to_owned(), "x".to_owned()], ..Default::default() };
+        let result = run_program("args()".to_owned(), &mut TypeChecker::new(), &mut CompilerContext::new(), &mut module);
+        assert_eq!(result.unwrap().as_ref().repr(), "[--verbose, x]");
+        assert_eq!(eval("args()").unwrap(), "[]");
+    }
+
+    #[test]
+    fn env_variables() {
+        env::set_var("EPILANG_TEST_ENV", "value");
+        assert_eq!(eval("env(\"EPILANG_TEST_ENV\")").unwrap(), "\"value\"");
+        assert_eq!(eval("env(\"EPILANG_TEST_UNSET\")").unwrap(), "unit");
+        assert!(matches!(eval("env(1)"), Err(ProgramError::RuntimeError(ExpressionError::UnexpectedType(Type::String, Type::Int)))));
+    }
+
+    #[test]
+    fn exit_codes() {
+        let exit = |text: &str| eval(text).unwrap_err().exit_code();
+        assert_eq!(exit("exit(3); 1"), 3);
+        assert_eq!(exit("fn f() { exit() }; f()"), 0);
+        // Other errors exit with a non-zero status
+        assert_eq!(exit("let"), 1);
+        assert_eq!(exit("undefined"), 1);
+        assert_eq!(exit("[][0]"), 1);
+        assert!(matches!(eval("exit(\"1\")"), Err(ProgramError::RuntimeError(ExpressionError::UnexpectedType(Type::Int, Type::String)))));
+    }
+}
@@ -0,0 +1,305 @@
+use std::mem;
+
+use crate::intermediate::exp::Exp;
+
+use super::executor::{ExpressionError, debug_statement, evaluate, push_arguments};
+use super::function::Function;
+use super::iterator::Iter;
+use super::module::Module;
+use super::pointer::Ptr;
+use super::value::{Value, V};
+
+/// Lazy sequence of values produced by a generator function.
+///
+/// The generator body is evaluated by the module calling next, on top of its variable stack.
+/// When the body yields, its variables are moved out of the stack together with the position
+/// of the yield, and the next call pushes them back and resumes the evaluation from there.
+/// The compiler allows yield only as a statement, so only the expressions made of statements
+/// are resumable, any other expression is evaluated by the executor.
+#[derive(Debug)]
+pub struct Generator {
+    state: State,
+}
+
+#[derive(Debug)]
+enum State {
+    /// Generator body has not started yet
+    Created { function: Box<Function>, args: Vec<Option<Ptr<Value>>> },
+    /// Generator body is suspended at a yield
+    Suspended { function: Box<Function>, variables: Vec<Ptr<Value>>, position: Position },
+    /// Generator body is being evaluated
+    Running,
+    /// Generator body has returned
+    Done,
+}
+
+/// Where a suspended generator body resumes, from the body down to the yield.
+/// Scopes are relative to the stack start of the generator, which changes at each resume.
+#[derive(Debug)]
+enum Position {
+    Yield,
+    First(Box<Position>),
+    Second(Box<Position>),
+    Block { scope: usize, inner: Box<Position> },
+    Branch { then: bool, scope: usize, inner: Box<Position> },
+    While(Box<Position>),
+    /// Suspended in a loop body, with the iterator of the loop
+    For { iter: Iter, scope: usize, inner: Box<Position> },
+    Statement(Box<Position>),
+}
+
+/// Result of evaluating an expression of a generator body until it ends or yields.
+/// The values of the statements are discarded, as well as the value returned by the body.
+enum Step {
+    Done,
+    Yielded(Ptr<Value>, Position),
+}
+
+impl Step {
+    /// Adds the enclosing expression to the position of a yield
+    fn enclosed(self, position: fn(Box<Position>) -> Position) -> Step {
+        match self {
+            Step::Yielded(value, inner) => Step::Yielded(value, position(Box::new(inner))),
+            done => done,
+        }
+    }
+}
+
+impl Generator {
+    /// Creates a generator from a function with arguments already bound to its parameters
+    pub fn new(function: Function, args: Vec<Option<Ptr<Value>>>) -> Generator {
+        Generator { state: State::Created { function: Box::new(function), args } }
+    }
+
+    /// Resumes the generator body until the next yield. Returns done when the body returns.
+    pub fn next(&mut self, module: &mut Module) -> Result<V, ExpressionError> {
+        let stack_start = module.variables.len();
+        let (function, position) = match mem::replace(&mut self.state, State::Running) {
+            State::Created { function, args } => {
+                module.variables.extend_from_slice(&function.external_values);
+                if let Err(e) = push_arguments(&function.params, args, module, stack_start) {
+                    module.variables.truncate(stack_start);
+                    self.state = State::Done;
+                    return Err(e)
+                }
+                (function, None)
+            },
+            State::Suspended { function, variables, position } => {
+                module.variables.extend(variables);
+                (function, Some(position))
+            },
+            State::Running => {
+                self.state = State::Running;
+                return Err(ExpressionError::GeneratorAlreadyRunning)
+            },
+            State::Done => {
+                self.state = State::Done;
+                return Ok(V::Val(Value::Done))
+            },
+        };
+        if let Some(debugger) = module.debugger.as_mut() {
+            debugger.enter_function(stack_start)
+        }
+        let result = resume(&function.body, position, module, stack_start);
+        if let Some(debugger) = module.debugger.as_mut() {
+            debugger.exit_function()
+        }
+        let variables = module.variables.split_off(stack_start);
+        match result {
+            Ok(Step::Yielded(value, position)) => {
+                self.state = State::Suspended { function, variables, position };
+                Ok(V::Ptr(value))
+            },
+            result => {
+                self.state = State::Done;
+                result.map(|_| V::Val(Value::Done))
+            },
+        }
+    }
+}
+
+/// Evaluates an expression of a generator body, from a position if the body was suspended in it
+fn resume(exp: &Exp, position: Option<Position>, module: &mut Module, stack_start: usize) -> Result<Step, ExpressionError> {
+    match exp {
+        Exp::Yield { exp } => match position {
+            // Resumed after the yield
+            Some(_) => Ok(Step::Done),
+            None => {
+                let value = evaluate(exp, module, stack_start)?.into_ptr();
+                Ok(Step::Yielded(value, Position::Yield))
+            },
+        },
+
+        Exp::Concatenation { first, second } => {
+            let position = match position {
+                Some(Position::Second(inner)) => Some(*inner),
+                position => {
+                    let inner = position.map(|p| match p {
+                        Position::First(inner) => *inner,
+                        _ => unreachable!(),
+                    });
+                    match resume(first, inner, module, stack_start)? {
+                        Step::Done => None,
+                        step => return Ok(step.enclosed(Position::First)),
+                    }
+                },
+            };
+            Ok(resume(second, position, module, stack_start)?.enclosed(Position::Second))
+        },
+
+        Exp::Block { exp } => {
+            let (scope, position) = match position {
+                Some(Position::Block { scope, inner }) => (stack_start + scope, Some(*inner)),
+                _ => (module.variables.len(), None),
+            };
+            let result = resume(exp, position, module, stack_start);
+            match end_scope(result, scope, module)? {
+                Step::Yielded(value, inner) => {
+                    Ok(Step::Yielded(value, Position::Block { scope: scope - stack_start, inner: Box::new(inner) }))
+                },
+                done => Ok(done),
+            }
+        },
+
+        Exp::Condition { exp, then_block, else_block } => {
+            let (then, scope, position) = match position {
+                Some(Position::Branch { then, scope, inner }) => (then, stack_start + scope, Some(*inner)),
+                _ => (evaluate(exp, module, stack_start)?.as_bool(), module.variables.len(), None),
+            };
+            let block = if then { then_block } else { else_block };
+            let result = resume(block, position, module, stack_start);
+            match end_scope(result, scope, module)? {
+                Step::Yielded(value, inner) => {
+                    Ok(Step::Yielded(value, Position::Branch { then, scope: scope - stack_start, inner: Box::new(inner) }))
+                },
+                done => Ok(done),
+            }
+        },
+
+        Exp::While { guard, exp } => {
+            let mut position = match position {
+                Some(Position::While(inner)) => Some(*inner),
+                _ => None,
+            };
+            loop {
+                // Guard was already evaluated when resuming in the body
+                if position.is_none() && !evaluate(guard, module, stack_start)?.as_bool() { break }
+                if let step @ Step::Yielded(..) = resume(exp, position.take(), module, stack_start)? {
+                    return Ok(step.enclosed(Position::While))
+                }
+            }
+            Ok(Step::Done)
+        },
+
+        Exp::For { iterable, exp } => {
+            let (mut iter, mut current) = match position {
+                Some(Position::For { iter, scope, inner }) => (iter, Some((stack_start + scope, *inner))),
+                _ => {
+                    let iterable = evaluate(iterable, module, stack_start)?.into_ptr();
+                    (Iter::new(iterable, module)?, None)
+                },
+            };
+            loop {
+                let (scope, position) = match current.take() {
+                    Some((scope, inner)) => (scope, Some(inner)),
+                    None => match iter.next(module)? {
+                        Some(value) => {
+                            let scope = module.variables.len();
+                            module.variables.push(value);
+                            (scope, None)
+                        },
+                        None => break,
+                    },
+                };
+                let result = resume(exp, position, module, stack_start);
+                if let Step::Yielded(value, inner) = end_scope(result, scope, module)? {
+                    return Ok(Step::Yielded(value, Position::For { iter, scope: scope - stack_start, inner: Box::new(inner) }))
+                }
+            }
+            Ok(Step::Done)
+        },
+
+        Exp::Statement { info, exp } => {
+            let position = match position {
+                Some(Position::Statement(inner)) => Some(*inner),
+                _ => {
+                    debug_statement(info, module, stack_start)?;
+                    None
+                },
+            };
+            Ok(resume(exp, position, module, stack_start)?.enclosed(Position::Statement))
+        },
+
+        exp => evaluate(exp, module, stack_start).map(|_| Step::Done),
+    }
+}
+
+/// Removes the variables of a scope when the expression evaluated in it is done.
+/// They stay in the stack when it yields, to be saved with the other variables of the generator.
+fn end_scope(result: Result<Step, ExpressionError>, scope: usize, module: &mut Module) -> Result<Step, ExpressionError> {
+    if !matches!(result, Ok(Step::Yielded(..))) {
+        module.variables.truncate(scope);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval, ProgramError};
+    use crate::runtime::executor::ExpressionError;
+
+    #[test]
+    fn generator_yields_lazily() {
+        let counter = "fn count(n) { let i = 0; while i < n { yield i; i = i + 1 } };";
+        assert_eq!(eval(&format!("{} let r = []; for x in count(4) {{ r.push(x) }}; r", counter)).unwrap(), "[0, 1, 2, 3]");
+        assert_eq!(eval(&format!("{} let it = count(2); [it.next(), it.next(), it.next(), it.next()]", counter)).unwrap(), "[0, 1, done, done]");
+        // Infinite generators are evaluated only as far as needed
+        assert_eq!(eval("fn nat() { let i = 0; while true { yield i; i = i + 1 } }; nat().take(3).collect()").unwrap(), "[0, 1, 2]");
+    }
+
+    #[test]
+    fn generator_resumes_in_nested_statements() {
+        let program = "fn g() {
+            yield 1;
+            { let a = 10; yield a; yield a + 1 };
+            if true { let b = 5; yield b } else { yield 0 };
+            for c in [7, 8] { let d = c * 2; yield d }
+        };";
+        assert_eq!(eval(&format!("{} g().collect()", program)).unwrap(), "[1, 10, 11, 5, 14, 16]");
+    }
+
+    #[test]
+    fn generators_keep_their_own_variables() {
+        let program = "fn g(x) { let y = x * 2; yield y; y = y + 1; yield y };";
+        assert_eq!(eval(&format!("{} let a = g(1); let b = g(10); [a.next(), b.next(), a.next(), b.next()]", program)).unwrap(), "[2, 20, 3, 21]");
+        // Variables defined by the caller between two resumes are not affected
+        assert_eq!(eval(&format!("{} let a = g(1); a.next(); let z = 7; a.next(); z", program)).unwrap(), "7");
+        assert_eq!(eval("fn outer(inner) { for x in inner() { yield x * 10 }; yield 99 }; outer(|| { yield 1; yield 2 }).collect()").unwrap(), "[10, 20, 99]");
+    }
+
+    #[test]
+    fn abandoned_generators_are_dropped() {
+        let program = "let gens = []; let i = 0;
+            while i < 5000 { let g = || { yield 1; yield 2 }; let it = g(); it.next(); gens.push(it); i = i + 1 };
+            gens.len()";
+        assert_eq!(eval(program).unwrap(), "5000");
+    }
+
+    #[test]
+    fn generator_errors() {
+        assert!(matches!(
+            eval("let cell = [0]; fn g(cell) { yield cell[0].next() }; let it = g(cell); cell[0] = it; it.next()"),
+            Err(ProgramError::RuntimeError(ExpressionError::GeneratorAlreadyRunning))
+        ));
+        assert!(matches!(
+            eval("fn g() { yield 1; [][0]; yield 2 }; let it = g(); [it.next(), it.next()]"),
+            Err(ProgramError::RuntimeError(ExpressionError::IndexOutOfRange(..)))
+        ));
+        // Arguments are pushed when the generator starts
+        assert!(eval("fn g(a) { yield a }; g()").is_ok());
+        assert!(matches!(
+            eval("fn g(a) { yield a }; g().next()"),
+            Err(ProgramError::RuntimeError(ExpressionError::MissingArgument(_)))
+        ));
+    }
+}
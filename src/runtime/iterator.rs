@@ -0,0 +1,175 @@
+use std::vec;
+
+use super::executor::{ExpressionError, call_value};
+use super::module::Module;
+use super::pointer::Ptr;
+use super::value::{Value, V};
+
+/// Native iterator. Each call to next returns a value, or None when the iteration is done.
+#[derive(Debug)]
+pub enum Iter {
+    List { list: Ptr<Value>, index: usize },
+    Chars(vec::IntoIter<char>),
+    /// Iterator or generator value, advanced in place
+    Shared(Ptr<Value>),
+    /// User object defining a next method
+    Object(Ptr<Value>),
+    Map { iter: Box<Iter>, fun: Ptr<Value> },
+    Filter { iter: Box<Iter>, fun: Ptr<Value> },
+    Take { iter: Box<Iter>, remaining: usize },
+    Zip { first: Box<Iter>, second: Box<Iter> },
+    Enumerate { iter: Box<Iter>, index: i32 },
+}
+
+impl Iter {
+    /// Creates an iterator over an iterable value. Lists, strings, maps, iterators and generators
+    /// are iterable, as well as objects defining a next method or an iter method.
+    /// Iterating a map yields its keys.
+    pub fn new(value: Ptr<Value>, module: &mut Module) -> Result<Iter, ExpressionError> {
+        match value.as_ref() {
+            Value::List(_) => Ok(Iter::List { list: value, index: 0 }),
+            Value::String(s) => Ok(Iter::Chars(s.chars().collect::<Vec<char>>().into_iter())),
+            Value::Map(map) => {
+                let keys = map.entries().iter().map(|(key, _)| Ptr::from(Value::String(key.clone()))).collect();
+                Ok(Iter::List { list: Ptr::from(Value::List(keys)), index: 0 })
+            },
+            Value::Iterator(_) | Value::Generator(_) => Ok(Iter::Shared(value)),
+            Value::Object(obj) if obj.get_method("next").is_some() => Ok(Iter::Object(value)),
+            Value::Object(_) => match value.as_ref().get_method("iter") {
+                Some(method) => {
+                    let iterable = call_value(&Value::Method(method), Vec::new(), Vec::new(), module)?;
+                    Iter::new(iterable.into_ptr(), module)
+                },
+                None => Err(ExpressionError::NotIterable(value.as_ref().get_type())),
+            },
+            v => Err(ExpressionError::NotIterable(v.get_type())),
+        }
+    }
+
+    pub fn next(&mut self, module: &mut Module) -> Result<Option<Ptr<Value>>, ExpressionError> {
+        match self {
+            Iter::List { list, index } => {
+                match list.as_ref() {
+                    Value::List(values) => {
+                        let next = values.get(*index).copied();
+                        *index += 1;
+                        Ok(next)
+                    },
+                    _ => Ok(None),
+                }
+            },
+
+            Iter::Chars(chars) => {
+                Ok(chars.next().map(|c| Ptr::from(Value::String(c.to_string()))))
+            },
+
+            Iter::Shared(value) => {
+                match value.as_mut_ref() {
+                    Value::Iterator(iter) => iter.next(module),
+                    Value::Generator(generator) => from_done(generator.next(module)?),
+                    _ => Ok(None),
+                }
+            },
+
+            Iter::Object(object) => {
+                match object.as_ref().get_method("next") {
+                    Some(method) => from_done(call_value(&Value::Method(method), Vec::new(), Vec::new(), module)?),
+                    None => Err(ExpressionError::NoSuchFieldOrMethod("next".to_owned())),
+                }
+            },
+
+            Iter::Map { iter, fun } => {
+                match iter.next(module)? {
+                    Some(value) => Ok(Some(call_value(fun.as_ref(), vec![value], Vec::new(), module)?.into_ptr())),
+                    None => Ok(None),
+                }
+            },
+
+            Iter::Filter { iter, fun } => {
+                while let Some(value) = iter.next(module)? {
+                    if call_value(fun.as_ref(), vec![value], Vec::new(), module)?.as_bool() {
+                        return Ok(Some(value))
+                    }
+                }
+                Ok(None)
+            },
+
+            Iter::Take { iter, remaining } => {
+                if *remaining == 0 { return Ok(None) }
+                *remaining -= 1;
+                iter.next(module)
+            },
+
+            Iter::Zip { first, second } => {
+                match (first.next(module)?, second.next(module)?) {
+                    (Some(v1), Some(v2)) => Ok(Some(Ptr::from(Value::List(vec![v1, v2])))),
+                    _ => Ok(None),
+                }
+            },
+
+            Iter::Enumerate { iter, index } => {
+                match iter.next(module)? {
+                    Some(value) => {
+                        let pair = Value::List(vec![Ptr::from(Value::Int(*index)), value]);
+                        *index += 1;
+                        Ok(Some(Ptr::from(pair)))
+                    },
+                    None => Ok(None),
+                }
+            },
+        }
+    }
+
+    /// Consumes the iterator, collecting the remaining values in a list
+    pub fn collect(&mut self, module: &mut Module) -> Result<Vec<Ptr<Value>>, ExpressionError> {
+        let mut values = Vec::new();
+        while let Some(value) = self.next(module)? {
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+/// User iterators and generators return done when the iteration is over
+fn from_done(v: V) -> Result<Option<Ptr<Value>>, ExpressionError> {
+    match v.as_ref() {
+        Value::Done => Ok(None),
+        _ => Ok(Some(v.into_ptr())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{eval, ProgramError};
+    use crate::runtime::executor::ExpressionError;
+
+    #[test]
+    fn native_iterators() {
+        assert_eq!(eval("let it = [1, 2].iter(); [it.next(), it.next(), it.next()]").unwrap(), "[1, 2, done]");
+        assert_eq!(eval("let it = \"ab\".iter(); [it.next(), it.next(), it.next()]").unwrap(), "[a, b, done]");
+    }
+
+    #[test]
+    fn user_iterators() {
+        let range = "class Range { let i = 0 let end fn self(end) { self.end = end } fn next() { if self.i < self.end { self.i = self.i + 1; self.i } else { done } } };";
+        assert_eq!(eval(&format!("{} let r = []; for x in Range(3) {{ r.push(x) }}; r", range)).unwrap(), "[1, 2, 3]");
+        // Objects defining iter are iterable through the iterator it returns
+        assert_eq!(eval("class Pair { fn iter() { [5, 6].iter() } }; iter(Pair()).collect()").unwrap(), "[5, 6]");
+        assert_eq!(eval(&format!("{} iter(Range(2)).map(|x| {{ x * 10 }}).collect()", range)).unwrap(), "[10, 20]");
+    }
+
+    #[test]
+    fn lazy_adapters() {
+        assert_eq!(eval("iter([1, 2, 3, 4]).map(|x| { x * 2 }).filter(|x| { x > 2 }).collect()").unwrap(), "[4, 6, 8]");
+        assert_eq!(eval("iter([1, 2, 3]).zip(\"ab\".iter()).collect()").unwrap(), "[[1, a], [2, b]]");
+        assert_eq!(eval("iter([5, 6]).enumerate().collect()").unwrap(), "[[0, 5], [1, 6]]");
+        // Adapters consume only the values that are requested
+        assert_eq!(eval("fn nat() { let i = 0; while true { yield i; i = i + 1 } }; nat().map(|x| { x * 2 }).take(3).collect()").unwrap(), "[0, 2, 4]");
+    }
+
+    #[test]
+    fn not_iterable() {
+        assert!(matches!(eval("for x in 5 { x }"), Err(ProgramError::RuntimeError(ExpressionError::NotIterable(_)))));
+        assert!(matches!(eval("class A { }; iter(A())"), Err(ProgramError::RuntimeError(ExpressionError::NotIterable(_)))));
+    }
+}
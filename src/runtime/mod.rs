@@ -3,4 +3,8 @@ pub mod value;
 pub mod operations;
 pub mod pointer;
 pub mod module;
+pub mod debugger;
+mod builtins;
 mod function;
+mod generator;
+mod iterator;
@@ -1,10 +1,31 @@
 use std::collections::HashMap;
+use std::sync::mpsc::Sender;
 
 use super::value::{Value, Class};
 use super::pointer::Ptr;
+use super::debugger::DebugState;
 
 #[derive(Default, Debug)]
 pub struct Module {
     pub variables: Vec<Ptr<Value>>,
     pub classes: HashMap<usize, Ptr<Class>>,
+    /// Command line arguments following the program file path
+    pub args: Vec<String>,
+    /// State of the random generator, seeded on first use unless random.seed is called
+    pub random: Option<u64>,
+    /// Present when the module is being debugged
+    pub debugger: Option<DebugState>,
+    /// Receives the text printed by the program instead of the standard output, when present
+    pub output: Option<Sender<String>>,
+}
+
+impl Module {
+    /// Writes text printed by the program
+    pub fn print(&self, text: String) {
+        match &self.output {
+            // Receiver may be gone if the debugger is shutting down
+            Some(output) => output.send(text).unwrap_or_default(),
+            None => print!("{}", text),
+        }
+    }
 }
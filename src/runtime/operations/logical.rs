@@ -15,6 +15,7 @@ impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Value::Unit, Value::Unit) => true,
+            (Value::Done, Value::Done) => true,
             (Value::Int(i1), Value::Int(i2)) => i1 == i2,
             (Value::Float(f1), Value::Float(f2)) => f1 == f2,
             (Value::Bool(b1), Value::Bool(b2)) => b1 == b2,
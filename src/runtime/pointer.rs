@@ -34,7 +34,7 @@ impl<T> Copy for Ptr<T> {}
 
 impl<T> Clone for Ptr<T> {
     fn clone(&self) -> Self {
-        Self { value: self.value }
+        *self
     }
 }
 
@@ -1,11 +1,78 @@
 use std::fmt;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+use regex::Regex;
 
 use crate::intermediate::constant::{Constant, Type};
-use crate::intermediate::exp::BuiltInFunction;
+use crate::intermediate::exp::{BuiltInFunction, Exp};
 
 use super::pointer::Ptr;
 use super::function::{Function, Method, BuiltInMethod};
+use super::generator::Generator;
+use super::iterator::Iter;
+
+/// Built-in methods of List values
+pub const LIST_METHODS: &[(&str, BuiltInFunction)] = &[
+    ("len", BuiltInFunction::ListLength),
+    ("push", BuiltInFunction::ListPush),
+    ("pop", BuiltInFunction::ListPop),
+    ("remove", BuiltInFunction::ListRemove),
+    ("map", BuiltInFunction::ListMap),
+    ("filter", BuiltInFunction::ListFilter),
+    ("reduce", BuiltInFunction::ListReduce),
+    ("find", BuiltInFunction::ListFind),
+    ("any", BuiltInFunction::ListAny),
+    ("all", BuiltInFunction::ListAll),
+    ("sort", BuiltInFunction::ListSort),
+    ("sort_by", BuiltInFunction::ListSortBy),
+    ("reverse", BuiltInFunction::ListReverse),
+    ("index_of", BuiltInFunction::ListIndexOf),
+    ("contains", BuiltInFunction::ListContains),
+    ("slice", BuiltInFunction::ListSlice),
+    ("join", BuiltInFunction::ListJoin),
+    ("insert", BuiltInFunction::ListInsert),
+    ("extend", BuiltInFunction::ListExtend),
+    ("clear", BuiltInFunction::ListClear),
+    ("iter", BuiltInFunction::Iter),
+];
+
+/// Built-in methods of Map values
+pub const MAP_METHODS: &[(&str, BuiltInFunction)] = &[
+    ("len", BuiltInFunction::MapLength),
+    ("keys", BuiltInFunction::MapKeys),
+    ("values", BuiltInFunction::MapValues),
+    ("contains", BuiltInFunction::MapContains),
+    ("get", BuiltInFunction::MapGet),
+    ("remove", BuiltInFunction::MapRemove),
+    ("iter", BuiltInFunction::Iter),
+];
+
+/// Built-in methods of String values
+pub const STRING_METHODS: &[(&str, BuiltInFunction)] = &[
+    ("iter", BuiltInFunction::Iter),
+];
+
+/// Built-in methods of Regex values
+pub const REGEX_METHODS: &[(&str, BuiltInFunction)] = &[
+    ("is_match", BuiltInFunction::RegexIsMatch),
+    ("find", BuiltInFunction::RegexFind),
+    ("find_all", BuiltInFunction::RegexFindAll),
+    ("captures", BuiltInFunction::RegexCaptures),
+    ("replace", BuiltInFunction::RegexReplace),
+    ("split", BuiltInFunction::RegexSplit),
+];
+
+/// Built-in methods of Iterator and Generator values
+pub const ITERATOR_METHODS: &[(&str, BuiltInFunction)] = &[
+    ("iter", BuiltInFunction::Iter),
+    ("next", BuiltInFunction::IterNext),
+    ("map", BuiltInFunction::IterMap),
+    ("filter", BuiltInFunction::IterFilter),
+    ("take", BuiltInFunction::IterTake),
+    ("zip", BuiltInFunction::IterZip),
+    ("enumerate", BuiltInFunction::IterEnumerate),
+    ("collect", BuiltInFunction::IterCollect),
+];
 
 #[derive(Debug)]
 pub enum Value {
@@ -15,12 +82,17 @@ pub enum Value {
     Bool(bool),
     String(String),
     List(Vec<Ptr<Value>>),
+    Map(Map),
     Function(Function),
     BuiltInFunction(BuiltInFunction),
     Class(Ptr<Class>),
     Object(Object),
     Method(Method),
     BuiltInMethod(BuiltInMethod),
+    Generator(Generator),
+    Iterator(Iter),
+    Regex(Regex),
+    Done,
 }
 
 impl Value {
@@ -32,6 +104,8 @@ impl Value {
             Value::Float(f) => *f != 0.0,
             Value::String(s) => !s.is_empty(),
             Value::List(l) => !l.is_empty(),
+            Value::Map(m) => !m.is_empty(),
+            Value::Done => false,
             _ => true,
         }
     }
@@ -39,6 +113,7 @@ impl Value {
     pub fn get_field(&self, name: &str) -> Option<Ptr<Value>> {
         match self {
             Value::Object(o) => o.get_field(name),
+            Value::Class(c) => c.as_ref().get_static(name),
             _ => None,
         }
     }
@@ -55,20 +130,17 @@ impl Value {
     }
 
     pub fn get_builtin_methd(&self, name: &str) -> Option<BuiltInMethod> {
-        match self {
-            Value::List(_) => {
-                match name {
-                    "len" => Some(BuiltInFunction::ListLength),
-                    "push" => Some(BuiltInFunction::ListPush),
-                    "pop" => Some(BuiltInFunction::ListPop),
-                    "remove" => Some(BuiltInFunction::ListRemove),
-                    _ => None
-                }.map(|function| {
-                    BuiltInMethod { self_value: Ptr::from(self), function }
-                })
-            },
-            _ => None
-        }
+        let methods = match self {
+            Value::List(_) => LIST_METHODS,
+            Value::Map(_) => MAP_METHODS,
+            Value::String(_) => STRING_METHODS,
+            Value::Regex(_) => REGEX_METHODS,
+            Value::Iterator(_) | Value::Generator(_) => ITERATOR_METHODS,
+            _ => return None
+        };
+        methods.iter().find(|(n, _)| *n == name).map(|(_, function)| {
+            BuiltInMethod { self_value: Ptr::from(self), function: *function }
+        })
     }
 
     pub fn get_type(&self) -> Type {
@@ -79,16 +151,22 @@ impl Value {
             Value::Float(_) => Type::Float,
             Value::String(_) => Type::String,
             Value::List(_) => Type::List,
+            Value::Map(_) => Type::Map,
             Value::Function(_) => Type::Function,
             Value::BuiltInFunction(_) => Type::Function,
             Value::Class(_) => Type::Class,
             Value::Object(_) => Type::Object,
             Value::Method(_) => Type::Method,
             Value::BuiltInMethod(_) => Type::Method,
+            Value::Generator(_) => Type::Generator,
+            Value::Iterator(_) => Type::Iterator,
+            Value::Regex(_) => Type::Regex,
+            Value::Done => Type::Done,
         }
     }
 
-    pub fn to_string(&self) -> String {
+    /// String representation used by the REPL, quoting strings
+    pub fn repr(&self) -> String {
         match self {
             Value::String(s) => format!("\"{}\"", s.clone()),
             v => format!("{}", v)
@@ -104,6 +182,7 @@ impl From<&Constant> for Value {
             Constant::Float(f) => Value::Float(*f),
             Constant::String(s) => Value::String(s.clone()),
             Constant::Bool(b) => Value::Bool(*b),
+            Constant::Done => Value::Done,
         }
     }
 }
@@ -118,35 +197,69 @@ impl fmt::Display for Value {
             Value::String(s) => write!(f, "{}", s),
             Value::List(l) => {
                 write!(f, "[")?;
-                if l.len() > 0 {
-                    for i in 0..l.len() - 1 {
-                        write!(f, "{}, ", l[i])?;
-                    }
-                    write!(f, "{}", l[l.len() - 1])?;
+                for (i, value) in l.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")? }
+                    write!(f, "{}", value)?;
                 }
                 write!(f, "]")
             },
+            Value::Map(m) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in m.entries().iter().enumerate() {
+                    if i > 0 { write!(f, ", ")? }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            },
             Value::Function(func) => write!(f, "[Function at {:p}]", func),
             Value::BuiltInFunction(func) => write!(f, "[Function at {:p}]", func),
             Value::Class(class) => write!(f, "[Class {} at {:p}]", class.as_ref().name, class.as_ref()),
             Value::Object(o) => write!(f, "[{} object at {:p}]", o.class.as_ref().name, o),
             Value::Method(m) => write!(f, "[Method at {:p}]", m),
             Value::BuiltInMethod(m) => write!(f, "[Method at {:p}]", m),
+            Value::Generator(g) => write!(f, "[Generator at {:p}]", g),
+            Value::Iterator(i) => write!(f, "[Iterator at {:p}]", i),
+            Value::Regex(r) => write!(f, "[Regex {}]", r.as_str()),
+            Value::Done => write!(f, "done"),
         }
     }
 }
 
 #[derive(Debug)]
 pub struct Class {
+    pub id: usize,
     pub name: String,
     pub fields: Vec<Field>,
     pub constructor: Function,
     pub methods: HashMap<String, Ptr<Function>>,
+    pub static_fields: HashMap<String, Ptr<Value>>,
+    pub static_methods: HashMap<String, Ptr<Value>>,
+    pub private_members: HashSet<String>,
+    pub traits: Vec<usize>,
+}
+
+impl Class {
+    /// Returns a static field or a static method with the given name
+    pub fn get_static(&self, name: &str) -> Option<Ptr<Value>> {
+        self.static_fields.get(name)
+            .or_else(|| self.static_methods.get(name))
+            .copied()
+    }
+
+    pub fn implements(&self, trait_id: usize) -> bool {
+        self.traits.contains(&trait_id)
+    }
+
+    /// Private members are accessible only from the class body
+    pub fn is_accessible(&self, name: &str, from_class: Option<usize>) -> bool {
+        !self.private_members.contains(name) || from_class == Some(self.id)
+    }
 }
 
 #[derive(Debug)]
 pub struct Field {
     pub name: String,
+    pub value: Option<Exp>,
 }
 
 #[derive(Debug)]
@@ -169,6 +282,58 @@ impl Object {
     }
 }
 
+/// Map with string keys, preserving insertion order
+#[derive(Debug, Default)]
+pub struct Map {
+    entries: Vec<(String, Ptr<Value>)>,
+    indices: HashMap<String, usize>,
+}
+
+impl Map {
+    pub fn get(&self, key: &str) -> Option<Ptr<Value>> {
+        self.indices.get(key).map(|i| self.entries[*i].1)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Ptr<Value>> {
+        match self.indices.get(key) {
+            Some(i) => Some(&mut self.entries[*i].1),
+            None => None,
+        }
+    }
+
+    /// Replaces the value of an existing key without changing its position
+    pub fn insert(&mut self, key: String, value: Ptr<Value>) {
+        match self.indices.get(&key) {
+            Some(i) => self.entries[*i].1 = value,
+            None => {
+                self.indices.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            },
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Ptr<Value>> {
+        let index = self.indices.remove(key)?;
+        let (_, value) = self.entries.remove(index);
+        for i in self.indices.values_mut() {
+            if *i > index { *i -= 1 }
+        }
+        Some(value)
+    }
+
+    pub fn entries(&self) -> &[(String, Ptr<Value>)] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
 #[derive(Debug)]
 pub enum V {
     Ptr(Ptr<Value>),
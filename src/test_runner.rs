@@ -0,0 +1,184 @@
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use crate::compiler::ast::AST;
+use crate::compiler::checker::TypeChecker;
+use crate::compiler::compiler::compile;
+use crate::compiler::context::CompilerContext;
+use crate::compiler::epilang::ASTParser;
+use crate::runtime::executor::{evaluate, ExpressionError};
+use crate::runtime::module::Module;
+use crate::ProgramError;
+
+/// Test block found in a source file
+struct Test<'a> {
+    name: &'a str,
+    line: usize,
+    body: &'a AST,
+}
+
+/// Failed test, or source file that could not be loaded
+struct Failure {
+    location: String,
+    message: String,
+}
+
+/// Runs the tests in a file, or in all the .epi files in a directory and its subdirectories.
+/// Each test is evaluated in a fresh module, after the top-level statements of its file.
+/// Returns the process exit status, which is non-zero if some test fails.
+pub fn run_tests(path: &str) -> i32 {
+    let files = match discover(Path::new(path)) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("Unable to read {}: {}", path, e);
+            return 1
+        }
+    };
+
+    // Errors are reported by the runner, rather than by the default panic hook
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut passed = 0;
+    let mut failures = Vec::new();
+    for file in files {
+        let file_name = file.display().to_string();
+        match fs::read_to_string(&file) {
+            Ok(text) => {
+                let (file_passed, file_failures) = run_file_tests(&file_name, &text);
+                passed += file_passed;
+                failures.extend(file_failures);
+            },
+            Err(e) => failures.push(Failure { location: file_name, message: e.to_string() }),
+        }
+    }
+
+    let _ = panic::take_hook();
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for failure in failures.iter() {
+            println!("    {}: {}", failure.location, failure.message);
+        }
+    }
+    let status = if failures.is_empty() { "ok" } else { "FAILED" };
+    println!("\ntest result: {}. {} passed; {} failed", status, passed, failures.len());
+    if failures.is_empty() { 0 } else { 1 }
+}
+
+/// Runs the tests in the text of a source file, printing their outcome.
+/// Returns the number of passed tests and the failures.
+fn run_file_tests(file_name: &str, text: &str) -> (usize, Vec<Failure>) {
+    let ast = match ASTParser::new().parse(text) {
+        Ok(ast) => ast,
+        Err(e) => {
+            let message = ProgramError::SyntaxError(e.to_string()).to_string();
+            return (0, vec![Failure { location: file_name.to_owned(), message }])
+        }
+    };
+
+    let statements = ast.statements();
+    let tests: Vec<Test> = statements.iter().filter_map(|statement| match statement {
+        AST::Test { name, offset, body } => Some(Test { name, line: line_number(text, *offset), body }),
+        _ => None,
+    }).collect();
+
+    let mut passed = 0;
+    let mut failures = Vec::new();
+    for test in tests {
+        let location = format!("{}:{}", file_name, test.line);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| run_test(&statements, test.body)))
+            .unwrap_or_else(|payload| {
+                let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Err((format!("panicked: {}", message), None))
+            });
+        match result {
+            Ok(()) => {
+                println!("test {} \"{}\" ... ok", location, test.name);
+                passed += 1;
+            },
+            Err((message, offset)) => {
+                println!("test {} \"{}\" ... FAILED", location, test.name);
+                // Failed assertions are reported where they are called
+                let location = offset.map_or(location, |offset| format!("{}:{}", file_name, line_number(text, offset)));
+                failures.push(Failure { location: format!("{} \"{}\"", location, test.name), message });
+            },
+        }
+    }
+    (passed, failures)
+}
+
+/// Evaluates the top-level statements of a file, which are not tests, and then the test body.
+/// Errors come with the offset of the failed assertion, if any.
+fn run_test(statements: &[&AST], body: &AST) -> Result<(), (String, Option<usize>)> {
+    let mut checker = TypeChecker::new();
+    let mut ctx = CompilerContext::new();
+    let mut module = Module::default();
+    for ast in statements.iter().chain(std::iter::once(&body)) {
+        checker.check(ast).map_err(|e| (ProgramError::TypeError(e).to_string(), None))?;
+        let exp = compile(ast, &mut ctx).map_err(|e| (ProgramError::CompilerError(e).to_string(), None))?;
+        evaluate(&exp, &mut module, 0).map_err(|e| {
+            let offset = match e {
+                ExpressionError::AssertionFailed(_, offset) => offset,
+                _ => None,
+            };
+            (ProgramError::RuntimeError(e).to_string(), offset)
+        })?;
+    }
+    Ok(())
+}
+
+/// Returns the source files in a directory and its subdirectories, sorted by path
+pub fn discover(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()])
+    }
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(discover(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "epi") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn line_number(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval;
+
+    use super::run_file_tests;
+
+    #[test]
+    fn assertions() {
+        assert_eq!(eval("assert(true); assert_eq([1, [2]], [1, [2]]); 1").unwrap(), "1");
+        assert!(eval("assert(false, \"message\")").unwrap_err().to_string().ends_with("message"));
+        assert!(eval("assert_eq(1, 2, \"values\")").unwrap_err().to_string().ends_with("values: 1 != 2"));
+    }
+
+    #[test]
+    fn failures_are_reported_at_the_assertion() {
+        let text = "fn check(x) {\n    assert(x > 0, \"positive\")\n};\n\ntest \"passes\" {\n    check(1)\n};\n\ntest \"fails\" {\n    let x = 1;\n    assert_eq(x, 2)\n};\n\ntest \"fails in a function\" {\n    check(-1)\n};\n\ntest \"errors\" {\n    [][0]\n}";
+        let (passed, failures) = run_file_tests("a.epi", text);
+        assert_eq!(passed, 1);
+        let locations: Vec<&str> = failures.iter().map(|f| f.location.as_str()).collect();
+        assert_eq!(locations, ["a.epi:11 \"fails\"", "a.epi:2 \"fails in a function\"", "a.epi:18 \"errors\""]);
+        assert_eq!(failures[0].message, "AssertionError: 1 != 2");
+    }
+
+    #[test]
+    fn syntax_errors_fail_the_file() {
+        let (passed, failures) = run_file_tests("a.epi", "test \"t\" { assert(true) }; let");
+        assert_eq!(passed, 0);
+        assert_eq!(failures[0].location, "a.epi");
+    }
+}